@@ -0,0 +1,123 @@
+//! Every tray and shortcut action behind one entry point, so the tray menu, global shortcuts
+//! and the `run_action` command (for keyboard- and voice-control tools) behave identically.
+
+use crate::announce::announce;
+use crate::audio::{self, AudioController, RecorderState};
+use crate::commands::{load_profile_model, select_next_model};
+use crate::config::{self, read_audio_input_config, switch_profile, write_audio_input_config, ShortcutAction};
+use crate::jobs::JobSource;
+use crate::recording::{cancel_recording, continue_recording, toggle_pause, toggle_recording_with, transcribe_recent};
+use crate::sinks::paste_last;
+use crate::transcription::{apply_improved_transcript, flush_append_buffer, TranscriptionOverrides};
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Run `action` once. Record and hold act as a plain toggle here; holding is only meaningful
+/// for a shortcut's press and release, which `shortcuts::dispatch` handles itself.
+pub fn run_action(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, action: ShortcutAction, profile: Option<&str>, source: JobSource) {
+    run_action_with(app, recorder, action, profile, source, TranscriptionOverrides::default());
+}
+
+/// `run_action` with `overrides` for any recording the action stops and transcribes.
+pub fn run_action_with(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, action: ShortcutAction, profile: Option<&str>, source: JobSource, overrides: TranscriptionOverrides) {
+    match action {
+        ShortcutAction::Record | ShortcutAction::Toggle | ShortcutAction::Hold => { toggle_recording_with(app, recorder, source, overrides); }
+        ShortcutAction::Cancel => { cancel_recording(app, recorder); }
+        ShortcutAction::PasteLast => match paste_last(app) {
+            Ok(false) => info!("paste-last: nothing transcribed yet"),
+            Ok(true) => {}
+            Err(e) => error!("paste-last failed: {}", e),
+        },
+        ShortcutAction::SwitchProfile => match switch_profile(app, profile) {
+            Ok(name) => {
+                info!("Switched to profile {}", name);
+                if let Some(tray) = app.tray_by_id("main-tray") {
+                    let _ = tray.set_tooltip(Some(&format!("Profile: {}", name)));
+                }
+                let _ = app.emit("profile-changed", serde_json::json!({ "name": name }));
+                announce(app, &format!("Profile {}", name));
+                load_profile_model(app);
+            }
+            Err(e) => error!("switch-profile failed: {}", e),
+        },
+        ShortcutAction::PauseResume => { toggle_pause(app, recorder); }
+        ShortcutAction::FlushBuffer => {
+            if let Err(e) = flush_append_buffer(app) {
+                error!("failed to flush append buffer: {}", e);
+            }
+        }
+        ShortcutAction::UseImproved => match apply_improved_transcript(app) {
+            Ok(Some(_)) => announce(app, "Improved transcript copied"),
+            Ok(None) => info!("use-improved: no improved transcript pending"),
+            Err(e) => error!("failed to apply improved transcript: {}", e),
+        },
+        ShortcutAction::ShowWindow => show_main_window(app),
+        ShortcutAction::OpenSettings => {
+            show_main_window(app);
+            let _ = app.emit("open-settings", true);
+        }
+        ShortcutAction::OpenHistory => {
+            show_main_window(app);
+            let _ = app.emit("open-history", true);
+        }
+        ShortcutAction::NextModel => match select_next_model(app) {
+            Ok(Some(id)) => announce(app, &format!("Model {}", id)),
+            Ok(None) => announce(app, "No other model installed"),
+            Err(e) => error!("next-model failed: {}", e),
+        },
+        ShortcutAction::NextInputDevice => match select_next_input_device(app) {
+            Ok(name) => announce(app, &format!("Input {}", name)),
+            Err(e) => error!("next-input-device failed: {}", e),
+        },
+        ShortcutAction::ReplayLast => {
+            let last = recorder.lock().ok().and_then(|st| st.last_capture.clone());
+            match last {
+                Some(last) => {
+                    if let Err(e) = audio::play_samples(last.samples, last.sample_rate_hz) {
+                        error!("replay failed: {}", e);
+                    }
+                }
+                None => announce(app, "Nothing recorded yet"),
+            }
+        }
+        ShortcutAction::ContinueRecording => {
+            if continue_recording(app, recorder) {
+                announce(app, "Continuing");
+            }
+        }
+        ShortcutAction::TranscribeRecent => {
+            if !transcribe_recent(app, recorder, source) {
+                announce(app, "Nothing to transcribe");
+            }
+        }
+        ShortcutAction::Translate => {
+            toggle_recording_with(app, recorder, source, TranscriptionOverrides { translate: Some(true), ..overrides });
+        }
+        ShortcutAction::Quit => app.exit(0),
+    }
+}
+
+pub fn show_main_window(app: &AppHandle) {
+    if let Some(win) = app.get_webview_window("main") {
+        let _ = win.show();
+        let _ = win.set_focus();
+    }
+}
+
+/// Make the connected input after the current one (wrapping around) the selected device and
+/// reopen the input on it. Returns the new device name.
+fn select_next_input_device(app: &AppHandle) -> Result<String> {
+    let names = audio::list_input_device_names()?;
+    let current = config::current_input_device_name(app);
+    let idx = current.and_then(|c| names.iter().position(|n| *n == c)).map(|i| i + 1).unwrap_or(0);
+    let next = names.get(idx % names.len().max(1)).cloned().ok_or_else(|| anyhow!("No input devices connected"))?;
+    let mut cfg = read_audio_input_config(app).unwrap_or_default();
+    cfg.selected_device_name = Some(next.clone());
+    write_audio_input_config(app, &cfg)?;
+    let controller = app.try_state::<AudioController>().ok_or_else(|| anyhow!("audio controller not running"))?;
+    controller.set_devices(cfg.device_candidates())?;
+    let _ = app.emit("input-device-selected", serde_json::json!({ "name": next }));
+    Ok(next)
+}
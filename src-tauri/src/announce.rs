@@ -0,0 +1,39 @@
+//! Optional spoken feedback, so state changes can be followed without looking at the tray.
+
+use crate::config::read_spoken_feedback_config;
+use log::warn;
+use std::process::Command;
+use tauri::AppHandle;
+
+/// Speak `message` with the macOS `say` voice when spoken feedback is enabled. Returns at once.
+pub fn announce(app: &AppHandle, message: &str) {
+    let Some(mut cmd) = say_command(app, message) else { return };
+    std::thread::spawn(move || {
+        if let Err(e) = cmd.status() {
+            warn!("Spoken feedback failed: {}", e);
+        }
+    });
+}
+
+/// Like `announce`, but returns only once the message has been spoken. Returns whether it was.
+pub fn announce_and_wait(app: &AppHandle, message: &str) -> bool {
+    let Some(mut cmd) = say_command(app, message) else { return false };
+    match cmd.status() {
+        Ok(_) => true,
+        Err(e) => {
+            warn!("Spoken feedback failed: {}", e);
+            false
+        }
+    }
+}
+
+fn say_command(app: &AppHandle, message: &str) -> Option<Command> {
+    let cfg = read_spoken_feedback_config(app);
+    if !cfg.enabled { return None; }
+    let mut cmd = Command::new("say");
+    if let Some(voice) = cfg.voice.as_deref().filter(|v| !v.is_empty()) {
+        cmd.arg("-v").arg(voice);
+    }
+    cmd.arg(message);
+    Some(cmd)
+}
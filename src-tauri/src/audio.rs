@@ -1,163 +1,973 @@
 use anyhow::{anyhow, Result};
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use tauri::{AppHandle, Emitter, Manager};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use rustfft::{num_complex::Complex, Fft, FftPlanner};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+use crate::announce::{announce, announce_and_wait};
+use crate::config::{read_input_formats_config, recordings_dir, InputFormat, RecordingFormat, RecordingLimitConfig, RecordingRetentionConfig};
+use crate::jobs::JobSource;
+use crate::recording::stop_recording_and_transcribe;
+use crate::whisper_service::resample_linear;
+use crate::platform::{self, MicrophoneAuthorization};
+use crate::processing::{self, AudioBlock, Pipeline};
+use crate::system_audio::{start_system_audio_capture, SystemAudioCapture, SYSTEM_AUDIO_SAMPLE_RATE};
 use serde::Serialize;
-use std::sync::mpsc::{self, Sender};
+use std::collections::VecDeque;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::thread;
 use std::time::SystemTime;
 use hound::{WavSpec, WavWriter, SampleFormat};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{DecoderOptions, CODEC_TYPE_NULL, CODEC_TYPE_OPUS};
+use symphonia::core::errors::Error as SymphoniaError;
+use symphonia::core::formats::{FormatOptions, Packet};
+use symphonia::core::io::MediaSourceStream;
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+
+/// Fixed-capacity sample store: once full, new audio overwrites the oldest, so a
+/// forgotten recording keeps only its most recent stretch instead of growing forever.
+#[derive(Debug)]
+pub struct SampleRing {
+    samples: VecDeque<f32>,
+    max_len: usize,
+}
+
+impl SampleRing {
+    pub fn with_max_len(max_len: usize) -> Self {
+        Self { samples: VecDeque::new(), max_len: max_len.max(1) }
+    }
+
+    /// Change the bound, dropping the oldest samples if already over it.
+    pub fn set_max_len(&mut self, max_len: usize) {
+        self.max_len = max_len.max(1);
+        let excess = self.samples.len().saturating_sub(self.max_len);
+        self.samples.drain(..excess);
+    }
+
+    /// Append samples; returns how many old samples were overwritten.
+    pub fn extend(&mut self, samples: impl IntoIterator<Item = f32>) -> usize {
+        let mut overwritten = 0;
+        for s in samples {
+            if self.samples.len() == self.max_len {
+                self.samples.pop_front();
+                overwritten += 1;
+            }
+            self.samples.push_back(s);
+        }
+        overwritten
+    }
+
+    /// Move the contents out in chronological order, leaving the ring empty.
+    pub fn take(&mut self) -> Vec<f32> {
+        std::mem::take(&mut self.samples).into()
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len
+    }
+
+    /// Copy of the samples in `range`, counted from the oldest one kept.
+    pub fn copy_range(&self, range: std::ops::Range<usize>) -> Vec<f32> {
+        self.samples.range(range).copied().collect()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.samples.capacity()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.samples.shrink_to_fit();
+    }
+}
 
 #[derive(Debug)]
 pub struct RecorderState {
     pub is_recording: bool,
-    pub buffer: Vec<i16>,
+    /// A paused recording keeps its buffer but ignores incoming audio until resumed.
+    pub is_paused: bool,
+    /// Device the live input stream is actually open on, after any fallback to the default.
+    pub input_device_name: Option<String>,
+    pub buffer: SampleRing,
     pub start_instant: Option<std::time::Instant>,
     pub sample_rate_hz: u32,
     pub last_level_emit: Option<Instant>,
+    /// Most recent capture as sent for transcription, kept for replay and diagnostics.
+    pub last_capture: Option<LastCapture>,
+    /// Meeting mode: system audio from the loopback device, recorded alongside `buffer` and
+    /// bounded to the same duration.
+    pub loopback_buffer: SampleRing,
+    pub loopback_sample_rate_hz: u32,
+    /// Per-callback RMS levels (dBFS) gathered while a noise-floor calibration runs.
+    pub calibration_levels: Option<Vec<f32>>,
+    /// Mono audio gathered while a microphone test runs.
+    pub mic_test: Option<Vec<f32>>,
+    /// Voice-activity auto-stop for the current recording; `None` when disabled.
+    pub auto_stop: Option<AutoStopState>,
+    /// Warn-then-stop time box of a toggle-mode recording; `None` when disabled.
+    pub time_box: Option<RecordingCountdown>,
+    /// Samples overwritten because a recording ran past its maximum duration.
+    pub dropped_samples: u64,
+    pub shrink_count: u64,
+    /// Set once the current recording has wrapped around its ring buffer.
+    pub wrapped: bool,
+    /// Wall-clock time of the first sample of the current recording, pre-roll included.
+    pub recording_started_at: Option<SystemTime>,
+    /// Audio is discarded until then, while the recording's arming delay runs.
+    pub armed_until: Option<Instant>,
+    /// A spoken cue is playing; its audio is discarded so it is not transcribed.
+    pub speaking: bool,
+    /// Maximum-duration handling for the current recording, armed by `start_recording`.
+    pub limit: RecordingCountdown,
+    /// Leading samples of `buffer` already transcribed by the streaming worker.
+    pub streamed_samples: usize,
+    /// Mono audio gathered for the next `audio-frame` event.
+    frame_samples: Vec<f32>,
+    /// The most recent audio, for the spectrum in `audio-level` events.
+    spectrum_samples: SampleRing,
+    last_frame_emit: Option<Instant>,
+    /// The last `pre_roll_ms` of audio captured while idle, prepended when recording starts.
+    pub pre_roll: SampleRing,
+    pub pre_roll_ms: u64,
+    /// The last `retroactive_ms` of audio captured while idle, for transcribing after the fact.
+    pub retroactive: SampleRing,
+    pub retroactive_ms: u64,
+    /// When the input last delivered audio; the watchdog rebuilds a stream that goes quiet.
+    last_callback: Option<Instant>,
+    last_idle_shrink: Option<Instant>,
+}
+
+/// While idle, spare buffer capacity is released at most this often.
+const IDLE_SHRINK_INTERVAL_SECS: u64 = 60;
+
+/// Recorder memory counters for `get_memory_usage`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RecorderMemoryUsage {
+    pub buffer_samples: usize,
+    pub buffer_capacity: usize,
+    pub loopback_samples: usize,
+    pub loopback_capacity: usize,
+    pub last_capture_samples: usize,
+    pub calibration_levels: usize,
+    pub total_bytes: usize,
+    pub dropped_samples: u64,
+    pub shrink_count: u64,
 }
 
 impl RecorderState {
+    pub fn memory_usage(&self) -> RecorderMemoryUsage {
+        let last_capture_samples = self.last_capture.as_ref().map(|c| c.samples.len()).unwrap_or(0);
+        let calibration_levels = self.calibration_levels.as_ref().map(|l| l.capacity()).unwrap_or(0);
+        RecorderMemoryUsage {
+            buffer_samples: self.buffer.len(),
+            buffer_capacity: self.buffer.capacity(),
+            loopback_samples: self.loopback_buffer.len(),
+            loopback_capacity: self.loopback_buffer.capacity(),
+            last_capture_samples,
+            calibration_levels,
+            total_bytes: (self.buffer.capacity() + self.loopback_buffer.capacity() + last_capture_samples) * std::mem::size_of::<f32>()
+                + calibration_levels * std::mem::size_of::<f32>(),
+            dropped_samples: self.dropped_samples,
+            shrink_count: self.shrink_count,
+        }
+    }
+
+    /// Release spare capacity left over from earlier recordings.
+    pub fn shrink_idle_buffers(&mut self) {
+        if self.is_recording { return; }
+        if self.buffer.capacity() > 0 || self.loopback_buffer.capacity() > 0 {
+            self.buffer.shrink_to_fit();
+            self.loopback_buffer.shrink_to_fit();
+            self.shrink_count += 1;
+        }
+    }
+}
+
+/// Which countdown a recording warning or stop comes from.
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum CountdownKind {
+    /// The maximum duration of every recording.
+    Limit,
+    /// The time box of a toggle-mode recording; `continue_recording` starts a fresh one.
+    TimeBox,
+}
+
+/// Warn-then-stop countdown over captured samples, so pauses do not count. Drives both the
+/// maximum recording duration and the time box.
+#[derive(Debug, Clone)]
+pub struct RecordingCountdown {
+    pub kind: CountdownKind,
+    /// Samples counted so far, including any pre-roll the recording started with.
+    pub position: u64,
+    /// Position at which the warning fires; `u64::MAX` for none.
+    pub warn_at_samples: u64,
+    pub end_at_samples: u64,
+    /// Stop and transcribe at the end. A limit that does not stop overwrites the oldest audio.
+    pub stops: bool,
+    pub warned: bool,
+    pub triggered: bool,
+}
+
+impl RecordingCountdown {
+    pub fn new(kind: CountdownKind, warn_at_samples: u64, end_at_samples: u64, stops: bool) -> Self {
+        Self { kind, position: 0, warn_at_samples, end_at_samples, stops, warned: false, triggered: false }
+    }
+
+    /// Count `samples` more captured samples. Returns the warning when it falls due, and
+    /// whether the recording should stop now.
+    fn advance(&mut self, samples: u64, rate: u64) -> (Option<RecordingWarningEvent>, bool) {
+        self.position += samples;
+        let mut warning = None;
+        if !self.warned && self.position >= self.warn_at_samples {
+            self.warned = true;
+            let remaining_secs = self.end_at_samples.saturating_sub(self.position) / rate.max(1);
+            warning = Some(RecordingWarningEvent { kind: self.kind, remaining_secs, stops: self.stops });
+        }
+        let stop = self.stops && !self.triggered && self.position >= self.end_at_samples;
+        if stop { self.triggered = true; }
+        (warning, stop)
+    }
+
+    fn rescale(&mut self, scale: impl Fn(u64) -> u64) {
+        self.position = scale(self.position);
+        if self.warn_at_samples != u64::MAX { self.warn_at_samples = scale(self.warn_at_samples); }
+        self.end_at_samples = scale(self.end_at_samples);
+    }
+}
+
+impl Default for RecordingCountdown {
+    fn default() -> Self {
+        Self::new(CountdownKind::Limit, u64::MAX, u64::MAX, false)
+    }
+}
+
+/// Sent as `recording-warning` when a recording is about to stop or start overwriting.
+#[derive(Serialize, Clone, Debug)]
+pub struct RecordingWarningEvent {
+    pub kind: CountdownKind,
+    pub remaining_secs: u64,
+    /// The recording stops at the end; otherwise its oldest audio starts being overwritten.
+    pub stops: bool,
+}
+
+/// Silence tracking for auto-stop, armed by `start_recording` from the saved settings.
+#[derive(Debug, Clone)]
+pub struct AutoStopState {
+    pub silence_ms: u64,
+    /// Last time the VAD stage marked a block as speech; `None` until the speaker starts.
+    pub last_speech: Option<Instant>,
+    pub triggered: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct LastCapture {
+    pub samples: Vec<f32>,
+    pub sample_rate_hz: u32,
+}
+
+impl RecorderState {
+    /// Resize the pre-roll window; 0 disables it.
+    pub fn set_pre_roll_ms(&mut self, ms: u64) {
+        self.pre_roll_ms = ms;
+        self.pre_roll.clear();
+        self.pre_roll.set_max_len(self.sample_rate_hz as usize * ms as usize / 1000);
+    }
+
+    /// Resize the retroactive window; 0 disables it.
+    pub fn set_retroactive_ms(&mut self, ms: u64) {
+        self.retroactive_ms = ms;
+        self.retroactive.clear();
+        self.retroactive.set_max_len(self.sample_rate_hz as usize * ms as usize / 1000);
+    }
+
+    /// Whether incoming audio belongs to the recording: not paused, no cue playing and past the
+    /// arming delay.
+    pub fn capturing(&self) -> bool {
+        self.is_recording && !self.is_paused && !self.speaking && !self.armed_until.is_some_and(|t| Instant::now() < t)
+    }
+
+    /// Switch to a new capture rate; the idle windows restart empty at the new size. A running
+    /// recording is converted to the new rate so it stays one continuous track.
+    pub fn set_sample_rate_hz(&mut self, sample_rate_hz: u32) {
+        let previous = self.sample_rate_hz;
+        self.sample_rate_hz = sample_rate_hz;
+        if self.is_recording && previous != sample_rate_hz {
+            self.resample_recording(previous, sample_rate_hz);
+        }
+        self.set_pre_roll_ms(self.pre_roll_ms);
+        self.set_retroactive_ms(self.retroactive_ms);
+    }
+
+    /// Resample the audio captured so far, and every position counted in samples, from `from` Hz
+    /// to `to` Hz; used when the input is reopened on a device with another rate mid-recording.
+    fn resample_recording(&mut self, from: u32, to: u32) {
+        log::info!("Input rate changed mid-recording ({} -> {} Hz); resampling {} samples", from, to, self.buffer.len());
+        let scale = |n: u64| n * to as u64 / from as u64;
+        let max_len = scale(self.buffer.max_len() as u64) as usize;
+        let captured = self.buffer.take();
+        self.buffer.set_max_len(max_len);
+        self.buffer.extend(resample_linear(&captured, from, to));
+        self.streamed_samples = (scale(self.streamed_samples as u64) as usize).min(self.buffer.len());
+        self.limit.rescale(scale);
+        if let Some(tb) = self.time_box.as_mut() {
+            tb.rescale(scale);
+        }
+    }
+
     pub fn new() -> Self {
         Self {
             is_recording: false,
-            buffer: Vec::new(),
+            is_paused: false,
+            input_device_name: None,
+            buffer: SampleRing::with_max_len(16_000 * RecordingLimitConfig::default().max_duration_secs as usize),
             start_instant: None,
             sample_rate_hz: 16_000,
             last_level_emit: None,
+            last_capture: None,
+            loopback_buffer: SampleRing::with_max_len(16_000 * RecordingLimitConfig::default().max_duration_secs as usize),
+            loopback_sample_rate_hz: 16_000,
+            calibration_levels: None,
+            mic_test: None,
+            auto_stop: None,
+            time_box: None,
+            dropped_samples: 0,
+            shrink_count: 0,
+            wrapped: false,
+            recording_started_at: None,
+            armed_until: None,
+            speaking: false,
+            limit: RecordingCountdown::default(),
+            streamed_samples: 0,
+            frame_samples: Vec::new(),
+            spectrum_samples: SampleRing::with_max_len(SPECTRUM_FFT_SIZE),
+            last_frame_emit: None,
+            pre_roll: SampleRing::with_max_len(1),
+            pre_roll_ms: 0,
+            retroactive: SampleRing::with_max_len(1),
+            retroactive_ms: 0,
+            last_callback: None,
+            last_idle_shrink: None,
         }
     }
 }
 
-pub fn build_input_stream(recorder: Arc<Mutex<RecorderState>>, preferred_device_name: Option<String>, app: AppHandle) -> Result<cpal::Stream> {
+/// Capture rate whisper works at; picking it at the device avoids resampling later.
+const PREFERRED_SAMPLE_RATE_HZ: u32 = 16_000;
+/// RNNoise only runs at 48 kHz, so that rate is preferred while noise suppression is on.
+const DENOISE_SAMPLE_RATE_HZ: u32 = 48_000;
+
+/// One range of stream formats a device accepts, as reported by CoreAudio.
+#[derive(Serialize, Clone, Debug)]
+pub struct SupportedInputFormat {
+    pub channels: u16,
+    pub min_sample_rate_hz: u32,
+    pub max_sample_rate_hz: u32,
+    pub sample_format: String,
+}
+
+fn resolve_input_device(host: &cpal::Host, name: Option<&str>) -> Result<cpal::Device> {
+    match name.and_then(|n| find_input_device_by_name(host, n)) {
+        Some(d) => Ok(d),
+        None => host.default_input_device().ok_or_else(|| anyhow!("No default input device")),
+    }
+}
+
+pub fn supported_input_formats(device_name: Option<&str>) -> Result<Vec<SupportedInputFormat>> {
+    device_formats(&resolve_input_device(&cpal::default_host(), device_name)?)
+}
+
+fn device_formats(device: &cpal::Device) -> Result<Vec<SupportedInputFormat>> {
+    Ok(device
+        .supported_input_configs()?
+        .map(|c| SupportedInputFormat {
+            channels: c.channels(),
+            min_sample_rate_hz: c.min_sample_rate().0,
+            max_sample_rate_hz: c.max_sample_rate().0,
+            sample_format: c.sample_format().to_string(),
+        })
+        .collect())
+}
+
+/// Rates offered from inside a supported range; the range ends are offered too.
+const COMMON_SAMPLE_RATES_HZ: [u32; 12] = [8_000, 11_025, 16_000, 22_050, 24_000, 32_000, 44_100, 48_000, 88_200, 96_000, 176_400, 192_000];
+
+/// The stream format a device opens with when none is requested.
+#[derive(Serialize, Clone, Debug)]
+pub struct DefaultInputFormat {
+    pub channels: u16,
+    pub sample_rate_hz: u32,
+    pub sample_format: String,
+}
+
+/// Everything a device reports about itself, so settings can offer only formats it accepts.
+#[derive(Serialize, Clone, Debug)]
+pub struct InputDeviceCapabilities {
+    pub name: String,
+    pub is_default: bool,
+    /// `None` when the device would not report one, for example while it is being unplugged.
+    pub default_format: Option<DefaultInputFormat>,
+    /// Distinct channel counts across `formats`, ascending.
+    pub channel_counts: Vec<u16>,
+    /// Rates inside at least one of `formats`, ascending.
+    pub sample_rates_hz: Vec<u32>,
+    pub formats: Vec<SupportedInputFormat>,
+}
+
+/// Capabilities of every connected input device. A device that fails to answer is still
+/// listed, with empty formats.
+pub fn input_device_capabilities() -> Result<Vec<InputDeviceCapabilities>> {
     let host = cpal::default_host();
-    let device = match preferred_device_name {
-        Some(name) => match find_input_device_by_name(&host, &name) {
-            Some(d) => d,
-            None => host
-                .default_input_device()
-                .ok_or_else(|| anyhow!("No default input device"))?,
-        },
-        None => host
-            .default_input_device()
-            .ok_or_else(|| anyhow!("No default input device"))?,
-    };
-    let config = device.default_input_config()?;
+    let default_name = default_input_device_name();
+    let mut devices = Vec::new();
+    for device in host.input_devices()? {
+        let Ok(name) = device.name() else { continue };
+        let formats = device_formats(&device).unwrap_or_else(|e| {
+            log::warn!("could not read formats of {}: {}", name, e);
+            Vec::new()
+        });
+        let default_format = device.default_input_config().ok().map(|c| DefaultInputFormat {
+            channels: c.channels(),
+            sample_rate_hz: c.sample_rate().0,
+            sample_format: c.sample_format().to_string(),
+        });
+        let channel_counts: std::collections::BTreeSet<u16> = formats.iter().map(|f| f.channels).collect();
+        let mut sample_rates_hz = std::collections::BTreeSet::new();
+        for f in &formats {
+            sample_rates_hz.insert(f.min_sample_rate_hz);
+            sample_rates_hz.insert(f.max_sample_rate_hz);
+            sample_rates_hz.extend(COMMON_SAMPLE_RATES_HZ.iter().copied().filter(|r| (f.min_sample_rate_hz..=f.max_sample_rate_hz).contains(r)));
+        }
+        devices.push(InputDeviceCapabilities {
+            is_default: default_name.as_deref() == Some(name.as_str()),
+            name,
+            default_format,
+            channel_counts: channel_counts.into_iter().collect(),
+            sample_rates_hz: sample_rates_hz.into_iter().collect(),
+            formats,
+        });
+    }
+    Ok(devices)
+}
+
+/// A device config with exactly `channels` at `sample_rate_hz`, if the device offers one.
+fn find_stream_config(device: &cpal::Device, channels: u16, sample_rate_hz: u32) -> Option<cpal::SupportedStreamConfig> {
+    let rate = cpal::SampleRate(sample_rate_hz);
+    device
+        .supported_input_configs()
+        .ok()?
+        .filter(|c| c.channels() == channels && c.min_sample_rate() <= rate && rate <= c.max_sample_rate())
+        .filter(|c| matches!(c.sample_format(), cpal::SampleFormat::I16 | cpal::SampleFormat::U16 | cpal::SampleFormat::F32))
+        .map(|c| c.with_sample_rate(rate))
+        .next()
+}
+
+/// Pick the stream format: the saved one when the device still supports it, else mono at
+/// `preferred_rate`, else the device default.
+fn choose_stream_config(device: &cpal::Device, saved: Option<&InputFormat>, preferred_rate: u32) -> Result<cpal::SupportedStreamConfig> {
+    if let Some(f) = saved {
+        match find_stream_config(device, f.channels, f.sample_rate_hz) {
+            Some(c) => return Ok(c),
+            None => log::warn!("Saved input format {} Hz x{} not supported; choosing automatically", f.sample_rate_hz, f.channels),
+        }
+    }
+    if let Some(c) = find_stream_config(device, 1, preferred_rate) {
+        return Ok(c);
+    }
+    Ok(device.default_input_config()?)
+}
+
+pub fn build_input_stream(recorder: Arc<Mutex<RecorderState>>, preferred_device_name: Option<String>, app: AppHandle, failed: Arc<AtomicBool>) -> Result<cpal::Stream> {
+    let host = cpal::default_host();
+    let device = resolve_input_device(&host, preferred_device_name.as_deref())?;
+    let device_name = device.name().unwrap_or_default();
+    let saved = read_input_formats_config(&app).devices.get(&device_name).cloned();
+    let preferred_rate = if processing::denoise_enabled() { DENOISE_SAMPLE_RATE_HZ } else { PREFERRED_SAMPLE_RATE_HZ };
+    let config = choose_stream_config(&device, saved.as_ref(), preferred_rate)?;
     let sample_rate = config.sample_rate().0;
-    let channels = config.channels() as usize;
+    log::info!("Opening input '{}' at {} Hz x{}", device_name, sample_rate, config.channels());
     if let Ok(mut st) = recorder.lock() {
-        st.sample_rate_hz = sample_rate;
+        st.set_sample_rate_hz(sample_rate);
+        st.input_device_name = Some(device_name);
     }
-    let stream = match config.sample_format() {
-        cpal::SampleFormat::I16 => {
-            let app_handle = app.clone();
-            device.build_input_stream(
-                &config.into(),
-                move |data: &[i16], _| on_audio(data, channels, &recorder, &app_handle),
-                on_err,
-                None,
-            )?
-        }
-        cpal::SampleFormat::U16 => {
-            let app_handle = app.clone();
-            device.build_input_stream(
-                &config.into(),
-                move |data: &[u16], _| {
-                    let converted: Vec<i16> = data.iter().map(|s| ((*s as i32) - 32768) as i16).collect();
-                    on_audio(&converted, channels, &recorder, &app_handle);
-                },
-                on_err,
-                None,
-            )?
+    let mut pipeline = Pipeline::new(sample_rate);
+    let on_error = stream_error_handler(app.clone(), recorder.clone(), "input", device.name().unwrap_or_default(), failed);
+    build_device_stream(&device, config, on_error, move |data, channels| {
+        on_audio(pipeline.process(downmix(data, channels)), &recorder, &app)
+    })
+}
+
+fn downmix(data: &[f32], channels: usize) -> Vec<f32> {
+    if channels <= 1 { return data.to_vec(); }
+    data.chunks_exact(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect()
+}
+
+/// A running capture: a CPAL device stream or ScreenCaptureKit system audio. Dropping it stops capture.
+#[allow(dead_code)] // held only to keep the capture alive
+pub enum InputStream {
+    Device(cpal::Stream),
+    SystemAudio(SystemAudioCapture),
+}
+
+/// Open the recording input: the named device, the system default, or system audio.
+/// `failed` is raised if this stream later reports an error.
+fn open_input_stream(recorder: Arc<Mutex<RecorderState>>, device: Option<String>, app: AppHandle, failed: Arc<AtomicBool>) -> Result<InputStream> {
+    INPUT_WARM.store(false, Ordering::Relaxed);
+    // The VAD threshold follows the noise floor of the device being opened.
+    processing::configure(&app);
+    if device.as_deref() == Some(SYSTEM_AUDIO_DEVICE) {
+        if let Ok(mut st) = recorder.lock() {
+            st.set_sample_rate_hz(SYSTEM_AUDIO_SAMPLE_RATE);
+            st.input_device_name = Some(SYSTEM_AUDIO_DEVICE.to_string());
         }
-        cpal::SampleFormat::F32 => {
-            let app_handle = app.clone();
-            device.build_input_stream(
-                &config.into(),
-                move |data: &[f32], _| {
-                    let converted: Vec<i16> = data.iter().map(|s| (s * i16::MAX as f32) as i16).collect();
-                    on_audio(&converted, channels, &recorder, &app_handle);
-                },
-                on_err,
-                None,
-            )?
+        let mut pipeline = Pipeline::new(SYSTEM_AUDIO_SAMPLE_RATE);
+        let capture = start_system_audio_capture(move |data, channels| on_audio(pipeline.process(downmix(data, channels)), &recorder, &app))?;
+        return Ok(InputStream::SystemAudio(capture));
+    }
+    let stream = build_input_stream(recorder, device, app, failed)?;
+    stream.play()?;
+    Ok(InputStream::Device(stream))
+}
+
+fn push_loopback(recorder: &Arc<Mutex<RecorderState>>, data: &[f32], channels: usize) {
+    if let Ok(mut st) = recorder.lock() {
+        if st.capturing() {
+            let samples = downmix(data, channels);
+            let overwritten = if st.limit.stops {
+                // Like the mic track, fill up to the limit only.
+                let room = st.loopback_buffer.max_len() - st.loopback_buffer.len();
+                st.loopback_buffer.extend(samples.into_iter().take(room))
+            } else {
+                st.loopback_buffer.extend(samples)
+            };
+            st.dropped_samples += overwritten as u64;
         }
+    }
+}
+
+/// Meeting mode's second track: captures `device_name` into `loopback_buffer` while recording.
+/// Unlike the mic there is no fallback device, since recording the mic twice would be useless.
+pub fn build_loopback_stream(recorder: Arc<Mutex<RecorderState>>, device_name: &str, app: AppHandle, failed: Arc<AtomicBool>) -> Result<cpal::Stream> {
+    let host = cpal::default_host();
+    let device = find_input_device_by_name(&host, device_name)
+        .ok_or_else(|| anyhow!("Loopback device not found: {}", device_name))?;
+    let config = choose_stream_config(&device, None, PREFERRED_SAMPLE_RATE_HZ)?;
+    if let Ok(mut st) = recorder.lock() {
+        st.loopback_sample_rate_hz = config.sample_rate().0;
+    }
+    let on_error = stream_error_handler(app, recorder.clone(), "loopback", device_name.to_string(), failed);
+    build_device_stream(&device, config, on_error, move |data, channels| push_loopback(&recorder, data, channels))
+}
+
+/// Open `device` with `config` and hand interleaved f32 samples in [-1, 1] to `on_samples`.
+fn build_device_stream<F, E>(device: &cpal::Device, config: cpal::SupportedStreamConfig, on_error: E, mut on_samples: F) -> Result<cpal::Stream>
+where
+    F: FnMut(&[f32], usize) + Send + 'static,
+    E: FnMut(cpal::StreamError) + Send + 'static,
+{
+    let channels = config.channels() as usize;
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::I16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[i16], _| {
+                let converted: Vec<f32> = data.iter().map(|s| *s as f32 / i16::MAX as f32).collect();
+                on_samples(&converted, channels);
+            },
+            on_error,
+            None,
+        )?,
+        cpal::SampleFormat::U16 => device.build_input_stream(
+            &config.into(),
+            move |data: &[u16], _| {
+                let converted: Vec<f32> = data.iter().map(|s| (*s as f32 - 32768.0) / 32768.0).collect();
+                on_samples(&converted, channels);
+            },
+            on_error,
+            None,
+        )?,
+        cpal::SampleFormat::F32 => device.build_input_stream(
+            &config.into(),
+            move |data: &[f32], _| on_samples(data, channels),
+            on_error,
+            None,
+        )?,
         _ => return Err(anyhow!("Unsupported sample format")),
     };
     Ok(stream)
 }
 
+/// Recordings whose overall RMS is below this are flagged as too quiet.
+pub const QUIET_RMS_DBFS: f32 = -50.0;
+/// With a calibrated device, a capture this close to the noise floor is flagged as well.
+const QUIET_MARGIN_OVER_FLOOR_DB: f32 = 6.0;
+
+/// Loudness summary of a whole capture, in dBFS.
+#[derive(Serialize, Clone, Debug)]
+pub struct LevelStats {
+    pub rms_db: f32,
+    pub peak_db: f32,
+}
+
+impl LevelStats {
+    pub fn is_too_quiet(&self, noise_floor_db: Option<f32>) -> bool {
+        let threshold = match noise_floor_db {
+            Some(floor) => QUIET_RMS_DBFS.max(floor + QUIET_MARGIN_OVER_FLOOR_DB),
+            None => QUIET_RMS_DBFS,
+        };
+        self.rms_db < threshold
+    }
+}
+
+pub fn level_stats(samples: &[f32]) -> LevelStats {
+    let mut sum_squares = 0.0f64;
+    let mut peak = 0.0f32;
+    for &v in samples {
+        sum_squares += (v * v) as f64;
+        peak = peak.max(v.abs());
+    }
+    let rms = if samples.is_empty() { 0.0 } else { (sum_squares / samples.len() as f64).sqrt() as f32 };
+    LevelStats {
+        rms_db: 20.0 * rms.max(1e-12).log10(),
+        peak_db: 20.0 * peak.max(1e-12).log10(),
+    }
+}
+
 #[derive(Serialize, Clone, Debug)]
 struct AudioLevelEvent {
     rms: f32,
     peak: f32,
     db: f32,
     recording: bool,
+    /// Log-spaced spectrum bands in [0, 1], lowest frequency first; only when enabled.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    bands: Option<Vec<f32>>,
+}
+
+/// Minimum time between `audio-level` events; set from the audio-level settings.
+static LEVEL_INTERVAL_MS: AtomicU64 = AtomicU64::new(50);
+/// Spectrum bands per `audio-level` event; 0 leaves them out.
+static SPECTRUM_BANDS: AtomicUsize = AtomicUsize::new(0);
+pub const MAX_SPECTRUM_BANDS: usize = 64;
+/// Samples per spectrum FFT (~21 ms at 48 kHz, ~64 ms at 16 kHz).
+const SPECTRUM_FFT_SIZE: usize = 1024;
+const SPECTRUM_MIN_HZ: f32 = 50.0;
+/// Band levels are scaled from this dBFS (0) up to full scale (1).
+const SPECTRUM_FLOOR_DB: f32 = -80.0;
+
+pub fn set_audio_level_options(interval_ms: u64, spectrum_bands: usize) {
+    LEVEL_INTERVAL_MS.store(interval_ms.max(AUDIO_FRAME_INTERVAL_MS as u64), Ordering::Relaxed);
+    SPECTRUM_BANDS.store(spectrum_bands.min(MAX_SPECTRUM_BANDS), Ordering::Relaxed);
+}
+
+/// Levels of the most recent `SPECTRUM_FFT_SIZE` samples in `bands` log-spaced bands from
+/// `SPECTRUM_MIN_HZ` to Nyquist, from a Hann-windowed FFT.
+fn spectrum_bands(samples: &[f32], sample_rate_hz: u32, bands: usize) -> Vec<f32> {
+    static FFT: OnceLock<Arc<dyn Fft<f32>>> = OnceLock::new();
+    let fft = FFT.get_or_init(|| FftPlanner::new().plan_fft_forward(SPECTRUM_FFT_SIZE));
+    let n = SPECTRUM_FFT_SIZE;
+    let tail = &samples[samples.len().saturating_sub(n)..];
+    let pad = n - tail.len();
+    let mut buf: Vec<Complex<f32>> = (0..n)
+        .map(|i| {
+            let v = if i < pad { 0.0 } else { tail[i - pad] };
+            let w = 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / (n - 1) as f32).cos();
+            Complex::new(v * w, 0.0)
+        })
+        .collect();
+    fft.process(&mut buf);
+    let bin_hz = sample_rate_hz as f32 / n as f32;
+    let nyquist = sample_rate_hz as f32 / 2.0;
+    // A full-scale sine peaks at n/4 with the Hann window's 0.5 coherent gain.
+    let full_scale = n as f32 / 4.0;
+    let ratio = (nyquist / SPECTRUM_MIN_HZ).max(1.0);
+    (0..bands)
+        .map(|b| {
+            let lo_hz = SPECTRUM_MIN_HZ * ratio.powf(b as f32 / bands as f32);
+            let hi_hz = SPECTRUM_MIN_HZ * ratio.powf((b + 1) as f32 / bands as f32);
+            let lo = ((lo_hz / bin_hz) as usize).clamp(1, n / 2 - 1);
+            let hi = ((hi_hz / bin_hz).ceil() as usize).clamp(lo + 1, n / 2);
+            let peak = buf[lo..hi].iter().map(|c| c.norm()).fold(0.0f32, f32::max);
+            let db = 20.0 * (peak / full_scale).max(1e-9).log10();
+            ((db - SPECTRUM_FLOOR_DB) / -SPECTRUM_FLOOR_DB).clamp(0.0, 1.0)
+        })
+        .collect()
+}
+
+/// Set while a window wants `audio-frame` events; off by default since they are frequent.
+static AUDIO_FRAMES_SUBSCRIBED: AtomicBool = AtomicBool::new(false);
+/// `audio-frame` events are sent at most this often (~60 Hz).
+const AUDIO_FRAME_INTERVAL_MS: u128 = 16;
+/// Peaks per `audio-frame`.
+const AUDIO_FRAME_PEAKS: usize = 32;
+
+pub fn set_audio_frames_subscribed(subscribed: bool) {
+    AUDIO_FRAMES_SUBSCRIBED.store(subscribed, Ordering::Relaxed);
+}
+
+/// Waveform slice for live rendering: peak magnitudes in [0, 1], oldest first.
+#[derive(Serialize, Clone, Debug)]
+struct AudioFrameEvent {
+    peaks: Vec<f32>,
+    duration_ms: u64,
+    recording: bool,
+}
+
+/// Snapshot of the recorder for `get_recording_state`.
+#[derive(Serialize, Clone, Debug)]
+pub struct RecordingStateInfo {
+    pub recording: bool,
+    pub paused: bool,
+    /// Wall-clock time since recording started, pauses included.
+    pub elapsed_ms: u64,
+    /// Audio captured so far, pre-roll included.
+    pub recorded_ms: u64,
+    pub max_duration_ms: u64,
+    pub sample_rate_hz: u32,
+    pub input_device: Option<String>,
 }
 
-fn on_audio(input: &[i16], channels: usize, recorder: &Arc<Mutex<RecorderState>>, app: &AppHandle) {
-    // Compute mono RMS and peak (normalized to [-1,1])
+impl RecorderState {
+    pub fn state_info(&self) -> RecordingStateInfo {
+        let rate = self.sample_rate_hz.max(1) as u64;
+        let recording = self.is_recording;
+        RecordingStateInfo {
+            recording,
+            paused: self.is_paused,
+            elapsed_ms: self.start_instant.map(|t| t.elapsed().as_millis() as u64).unwrap_or(0),
+            recorded_ms: if recording { self.buffer.len() as u64 * 1000 / rate } else { 0 },
+            max_duration_ms: self.buffer.max_len() as u64 * 1000 / rate,
+            sample_rate_hz: self.sample_rate_hz,
+            input_device: self.input_device_name.clone(),
+        }
+    }
+}
+
+fn on_audio(block: AudioBlock, recorder: &Arc<Mutex<RecorderState>>, app: &AppHandle) {
+    INPUT_WARM.store(true, Ordering::Relaxed);
+    let input = &block.samples;
+    // Compute RMS and peak
     let mut sum_squares: f32 = 0.0;
     let mut peak_abs: f32 = 0.0;
-    let max_i16 = i16::MAX as f32;
-    let mut frames_count: usize = 0;
-    if channels == 1 {
-        for &s in input.iter() {
-            let v = (s as f32) / max_i16;
-            sum_squares += v * v;
-            let a = v.abs();
-            if a > peak_abs { peak_abs = a; }
-        }
-        frames_count = input.len();
-    } else if channels > 1 {
-        for frame in input.chunks_exact(channels) {
-            let sum: i32 = frame.iter().map(|v| *v as i32).sum();
-            let avg = (sum as f32) / (channels as f32 * max_i16);
-            sum_squares += avg * avg;
-            let a = avg.abs();
-            if a > peak_abs { peak_abs = a; }
-            frames_count += 1;
-        }
+    for &v in input.iter() {
+        sum_squares += v * v;
+        peak_abs = peak_abs.max(v.abs());
     }
+    let frames_count = input.len();
     if frames_count > 0 {
         let rms = (sum_squares / frames_count as f32).sqrt();
         let db = 20.0 * (rms.max(1e-12)).log10();
         // Update recorder state, append audio if recording, and throttle event emission (~20 Hz)
         let mut should_emit = false;
         let mut is_recording_now = false;
+        let mut auto_stop_now = false;
+        let mut wrapped_now = false;
+        let mut warnings_now: Vec<RecordingWarningEvent> = Vec::new();
+        let mut countdown_stop_now: Option<CountdownKind> = None;
+        let mut frame_now: Option<AudioFrameEvent> = None;
+        let mut spectrum_input: Option<(Vec<f32>, u32, usize)> = None;
+        let bands = SPECTRUM_BANDS.load(Ordering::Relaxed);
         if let Ok(mut st) = recorder.lock() {
-            // Append to buffer only when recording
-            if st.is_recording {
+            st.last_callback = Some(Instant::now());
+            if let Some(levels) = st.calibration_levels.as_mut() {
+                levels.push(db);
+            }
+            let mono = || input.iter().copied();
+            if let Some(test) = st.mic_test.as_mut() {
+                test.extend(mono());
+            }
+            if AUDIO_FRAMES_SUBSCRIBED.load(Ordering::Relaxed) {
+                st.frame_samples.extend(mono());
+                let now = Instant::now();
+                if st.last_frame_emit.map(|t| now.duration_since(t).as_millis() >= AUDIO_FRAME_INTERVAL_MS).unwrap_or(true) {
+                    st.last_frame_emit = Some(now);
+                    let samples = std::mem::take(&mut st.frame_samples);
+                    let duration_ms = samples.len() as u64 * 1000 / st.sample_rate_hz.max(1) as u64;
+                    frame_now = Some(AudioFrameEvent { peaks: waveform_peaks(&samples, AUDIO_FRAME_PEAKS), duration_ms, recording: st.is_recording && !st.is_paused });
+                }
+            }
+            if bands > 0 {
+                st.spectrum_samples.extend(mono());
+            }
+            // Append to buffer only when recording; while idle, keep the pre-roll and retroactive windows fresh
+            if !st.is_recording && st.pre_roll_ms > 0 {
+                st.pre_roll.extend(mono());
+            }
+            if !st.is_recording && st.retroactive_ms > 0 {
+                st.retroactive.extend(mono());
+            }
+            if st.capturing() {
                 is_recording_now = true;
-                if channels == 1 {
-                    st.buffer.extend_from_slice(input);
+                let overwritten = if st.limit.stops {
+                    // Fill up to the limit only; the recording stops rather than wraps.
+                    let room = st.buffer.max_len() - st.buffer.len();
+                    st.buffer.extend(mono().take(room))
                 } else {
-                    for frame in input.chunks_exact(channels) {
-                        let sum: i32 = frame.iter().map(|v| *v as i32).sum();
-                        let avg = (sum / channels as i32) as i16;
-                        st.buffer.push(avg);
+                    st.buffer.extend(mono())
+                };
+                if overwritten > 0 {
+                    st.dropped_samples += overwritten as u64;
+                    st.streamed_samples = st.streamed_samples.saturating_sub(overwritten);
+                    if !st.wrapped {
+                        st.wrapped = true;
+                        wrapped_now = true;
+                    }
+                }
+                let rate = st.sample_rate_hz as u64;
+                let RecorderState { limit, time_box, .. } = &mut *st;
+                for countdown in std::iter::once(limit).chain(time_box.as_mut()) {
+                    let (warning, stop) = countdown.advance(frames_count as u64, rate);
+                    warnings_now.extend(warning);
+                    if stop && countdown_stop_now.is_none() {
+                        countdown_stop_now = Some(countdown.kind);
+                    }
+                }
+                if let Some(vad) = st.auto_stop.as_mut() {
+                    let now = Instant::now();
+                    if block.speech == Some(true) {
+                        vad.last_speech = Some(now);
+                    } else if let (Some(false), Some(last)) = (block.speech, vad.last_speech) {
+                        // Only stop after speech has started, so a slow start is not cut off.
+                        if !vad.triggered && now.duration_since(last).as_millis() as u64 >= vad.silence_ms {
+                            vad.triggered = true;
+                            auto_stop_now = true;
+                        }
                     }
                 }
             }
             let now = Instant::now();
+            if !st.is_recording
+                && st.last_idle_shrink.map(|t| now.duration_since(t).as_secs() >= IDLE_SHRINK_INTERVAL_SECS).unwrap_or(true)
+            {
+                st.last_idle_shrink = Some(now);
+                st.shrink_idle_buffers();
+            }
             let do_emit = match st.last_level_emit {
-                Some(t) => now.duration_since(t).as_millis() >= 50,
+                Some(t) => now.duration_since(t).as_millis() as u64 >= LEVEL_INTERVAL_MS.load(Ordering::Relaxed),
                 None => true,
             };
             if do_emit {
                 st.last_level_emit = Some(now);
                 should_emit = true;
+                if bands > 0 {
+                    let len = st.spectrum_samples.len();
+                    spectrum_input = Some((st.spectrum_samples.copy_range(0..len), st.sample_rate_hz, bands));
+                }
             }
         }
         if should_emit {
-            let _ = app.emit("audio-level", AudioLevelEvent { rms, peak: peak_abs, db, recording: is_recording_now });
+            // The FFT runs outside the recorder lock.
+            let bands = spectrum_input.map(|(samples, rate, bands)| spectrum_bands(&samples, rate, bands));
+            let _ = app.emit("audio-level", AudioLevelEvent { rms, peak: peak_abs, db, recording: is_recording_now, bands });
+        }
+        if let Some(frame) = frame_now {
+            let _ = app.emit("audio-frame", frame);
+        }
+        if wrapped_now {
+            log::warn!("Recording exceeded its maximum duration; oldest audio is being overwritten");
+            let _ = app.emit("recording-buffer-wrapped", true);
+        }
+        for event in warnings_now {
+            let tooltip = match (event.kind, event.stops) {
+                (CountdownKind::TimeBox, _) => format!("Recording… stops in {}s unless continued", event.remaining_secs),
+                (CountdownKind::Limit, true) => format!("Recording… stops in {}s", event.remaining_secs),
+                (CountdownKind::Limit, false) => format!("Recording… oldest audio overwritten in {}s", event.remaining_secs),
+            };
+            if let Some(tray) = app.tray_by_id("main-tray") {
+                let _ = tray.set_tooltip(Some(&tooltip));
+            }
+            if event.kind == CountdownKind::TimeBox {
+                announce_over_recording(app, recorder, format!("Recording stops in {} seconds", event.remaining_secs));
+            }
+            let _ = app.emit("recording-warning", event);
+        }
+        if let Some(kind) = countdown_stop_now {
+            let app = app.clone();
+            let recorder = recorder.clone();
+            thread::spawn(move || {
+                let (event, source) = match kind {
+                    CountdownKind::Limit => ("recording-limit-reached", JobSource::RecordingLimit),
+                    CountdownKind::TimeBox => ("recording-time-box-reached", JobSource::TimeBox),
+                };
+                log::info!("Recording reached its {:?} countdown; stopping", kind);
+                let _ = app.emit(event, true);
+                stop_recording_and_transcribe(&app, &recorder, source);
+            });
+        }
+        if auto_stop_now {
+            // Leave the audio callback before saving and transcribing.
+            let app = app.clone();
+            let recorder = recorder.clone();
+            thread::spawn(move || {
+                log::info!("Silence detected; stopping recording");
+                let _ = app.emit("recording-auto-stopped", true);
+                stop_recording_and_transcribe(&app, &recorder, JobSource::AutoStop);
+            });
         }
     }
 }
 
-fn on_err(err: cpal::StreamError) {
-    eprintln!("Audio stream error: {err}");
+/// Speak `message` during a recording with capture suspended, so the cue is not transcribed.
+fn announce_over_recording(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, message: String) {
+    let app = app.clone();
+    let recorder = recorder.clone();
+    thread::spawn(move || {
+        if let Ok(mut st) = recorder.lock() { st.speaking = true; }
+        announce_and_wait(&app, &message);
+        if let Ok(mut st) = recorder.lock() { st.speaking = false; }
+    });
+}
+
+/// Cleared when the input opens and set by its first callback.
+static INPUT_WARM: AtomicBool = AtomicBool::new(false);
+
+/// Problem with a running or reopening stream, emitted as `audio-error` so the UI can say the
+/// microphone was lost instead of silently recording nothing.
+#[derive(Clone, Debug, Serialize)]
+pub struct AudioErrorEvent {
+    /// `stream-error`, `stream-stalled`, `device-lost`, `rebuild-failed` or `loopback-failed`.
+    pub kind: &'static str,
+    /// `input` or `loopback`.
+    pub stream: &'static str,
+    pub device: Option<String>,
+    pub message: String,
+    /// A recording was running, so part of it may be missing.
+    pub recording: bool,
+}
+
+fn report_audio_error(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, kind: &'static str, stream: &'static str, device: Option<String>, message: String) {
+    log::warn!("Audio {} error ({}) on {:?}: {}", stream, kind, device, message);
+    let recording = recorder.lock().map(|st| st.is_recording).unwrap_or(false);
+    let _ = app.emit("audio-error", AudioErrorEvent { kind, stream, device, message, recording });
+}
+
+/// CPAL error callback for one stream. It raises that stream's own `failed` flag, so the audio
+/// thread reopens only the stream that broke, and a late error from a stream already replaced
+/// cannot tear down its successor.
+fn stream_error_handler(app: AppHandle, recorder: Arc<Mutex<RecorderState>>, stream: &'static str, device: String, failed: Arc<AtomicBool>) -> impl FnMut(cpal::StreamError) + Send + 'static {
+    move |err| {
+        failed.store(true, Ordering::Relaxed);
+        let kind = match err {
+            cpal::StreamError::DeviceNotAvailable => "device-lost",
+            _ => "stream-error",
+        };
+        report_audio_error(&app, &recorder, kind, stream, Some(device.clone()), err.to_string());
+    }
 }
 
 
@@ -171,6 +981,40 @@ pub fn list_input_device_names() -> Result<Vec<String>> {
     Ok(names)
 }
 
+/// Pseudo device name that selects ScreenCaptureKit system audio instead of a microphone.
+pub const SYSTEM_AUDIO_DEVICE: &str = "System Audio";
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AudioInputKind {
+    Microphone,
+    SystemAudio,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct AudioInputDevice {
+    pub name: String,
+    pub kind: AudioInputKind,
+}
+
+/// Input devices plus the system-audio capture source, for the input and loopback pickers.
+pub fn list_audio_input_devices() -> Result<Vec<AudioInputDevice>> {
+    let mut devices: Vec<AudioInputDevice> = list_input_device_names()?
+        .into_iter()
+        .map(|name| AudioInputDevice { name, kind: AudioInputKind::Microphone })
+        .collect();
+    devices.push(AudioInputDevice { name: SYSTEM_AUDIO_DEVICE.to_string(), kind: AudioInputKind::SystemAudio });
+    Ok(devices)
+}
+
+pub fn default_input_device_name() -> Option<String> {
+    cpal::default_host().default_input_device().and_then(|d| d.name().ok())
+}
+
+fn input_device_present(name: &str) -> bool {
+    name == SYSTEM_AUDIO_DEVICE || find_input_device_by_name(&cpal::default_host(), name).is_some()
+}
+
 fn find_input_device_by_name(host: &cpal::Host, name: &str) -> Option<cpal::Device> {
     if let Ok(devices) = host.input_devices() {
         for d in devices {
@@ -183,57 +1027,382 @@ fn find_input_device_by_name(host: &cpal::Host, name: &str) -> Option<cpal::Devi
 }
 
 
+/// How often the audio thread checks whether a missing preferred input device is back.
+const DEVICE_WATCH_INTERVAL_SECS: u64 = 3;
+/// A recording whose input has delivered nothing for this long is treated as a dead stream.
+const STREAM_STALL_SECS: u64 = 4;
+
+/// Seconds since the input last delivered audio, when that is too long for a running recording.
+fn stalled_secs(recorder: &Arc<Mutex<RecorderState>>) -> Option<u64> {
+    let st = recorder.lock().ok()?;
+    if !st.is_recording { return None; }
+    let quiet = st.last_callback?.elapsed().as_secs();
+    (quiet >= STREAM_STALL_SECS).then_some(quiet)
+}
+
+/// Emitted after the input stream was rebuilt on its own: its device went away or failed,
+/// a device appeared while none was open, or a higher-priority preferred device came back.
+#[derive(Clone, Serialize)]
+pub struct AudioDeviceChangedEvent {
+    /// Device now being captured from, or the one the next recording will use while the input is closed.
+    pub device: Option<String>,
+    pub reason: &'static str,
+}
+
+/// Emitted whenever the input stream is opened: which device is in use and whether it is
+/// a fallback for a preferred device that is not connected.
+#[derive(Clone, Serialize)]
+pub struct AudioInputSelectedEvent {
+    pub device: Option<String>,
+    pub preferred: Option<String>,
+    pub fallback: bool,
+}
+
+/// Emitted as `input-device-switched` when a higher-ranked device reappears and the input
+/// moves back to it from a fallback.
+#[derive(Clone, Serialize)]
+pub struct InputDeviceSwitchedEvent {
+    pub device: Option<String>,
+}
+
+/// Why no input could be opened; emitted as `no-input-device` when capture becomes
+/// impossible and again whenever a recording is attempted meanwhile.
+#[derive(Clone, Debug, Serialize)]
+pub struct NoInputDeviceEvent {
+    /// `permission-denied`, `no-device`, `open-failed` or `open-timeout`.
+    pub reason: &'static str,
+    pub message: String,
+}
+
+/// Set while no input stream is open; cleared once the audio thread opens one again.
+static INPUT_UNAVAILABLE: Mutex<Option<NoInputDeviceEvent>> = Mutex::new(None);
+
+/// Why recording is currently impossible, or `None` when an input is open.
+pub fn input_unavailable() -> Option<NoInputDeviceEvent> {
+    INPUT_UNAVAILABLE.lock().ok()?.clone()
+}
+
+/// Microphone access is checked before opening, since a denied device still opens and
+/// delivers silence. The system-audio capture has its own permission and skips this.
+fn mic_permission_denied(device: Option<&str>) -> bool {
+    device != Some(SYSTEM_AUDIO_DEVICE)
+        && matches!(platform::microphone_authorization(), MicrophoneAuthorization::Denied | MicrophoneAuthorization::Restricted)
+}
+
+/// Returns whether this is a new outage rather than another failed retry.
+fn mark_input_unavailable(app: &AppHandle, event: NoInputDeviceEvent) -> bool {
+    let Ok(mut current) = INPUT_UNAVAILABLE.lock() else { return false };
+    // Report each outage once, not on every retry.
+    if current.as_ref().is_some_and(|c| c.reason == event.reason) { return false; }
+    log::warn!("No usable input device ({}): {}", event.reason, event.message);
+    post_tray_tooltip(app, "No microphone available");
+    announce(app, "No microphone available");
+    let _ = app.emit("no-input-device", event.clone());
+    *current = Some(event);
+    true
+}
+
+fn mark_input_available(app: &AppHandle) {
+    let Ok(mut current) = INPUT_UNAVAILABLE.lock() else { return };
+    if current.take().is_some() {
+        log::info!("Input device available again");
+        post_tray_tooltip(app, "Commander");
+    }
+}
+
+/// Set the tray tooltip without waiting for the main thread, which may itself be waiting
+/// on the controller in `acquire`.
+fn post_tray_tooltip(app: &AppHandle, tooltip: &'static str) {
+    let handle = app.clone();
+    let _ = app.run_on_main_thread(move || {
+        if let Some(tray) = handle.tray_by_id("main-tray") {
+            let _ = tray.set_tooltip(Some(tooltip));
+        }
+    });
+}
+
+/// First device of the ordered candidate list that is currently connected.
+fn first_present_device(candidates: &[String]) -> Option<String> {
+    candidates.iter().find(|n| input_device_present(n)).cloned()
+}
+
 #[derive(Debug)]
 pub enum AudioCommand {
-    Rebuild { device: Option<String> },
+    /// Reopen the input on the first connected device of `devices`, or the system default.
+    /// The input stays closed while nothing holds it.
+    Rebuild { devices: Vec<String> },
+    SetLoopback { device: Option<String> },
+    /// Take a hold on the input, opening it if needed; `ready` reports whether it is open.
+    Acquire { ready: Sender<bool> },
+    /// Drop a hold; the microphone is released once none are left.
+    Release,
 }
 
+/// How long `acquire` waits for the input to open.
+const INPUT_OPEN_TIMEOUT_MS: u64 = 3000;
+/// How long `acquire` waits for the first callback of a freshly opened input.
+const INPUT_WARMUP_TIMEOUT_MS: u64 = 300;
+
 #[derive(Clone)]
 pub struct AudioController {
     tx: Arc<Mutex<Sender<AudioCommand>>>,
 }
 
 impl AudioController {
-    pub fn set_device(&self, name: Option<String>) -> Result<()> {
+    fn send(&self, cmd: AudioCommand) -> Result<()> {
         let tx = self.tx.lock().map_err(|_| anyhow!("controller unavailable"))?;
-        tx.send(AudioCommand::Rebuild { device: name })
+        tx.send(cmd)
             .map_err(|e| anyhow!(format!("failed to send audio command: {}", e)))
     }
+
+    /// Switch to the first connected device of the ordered `devices` list.
+    pub fn set_devices(&self, devices: Vec<String>) -> Result<()> {
+        self.send(AudioCommand::Rebuild { devices })
+    }
+
+    pub fn set_loopback_device(&self, name: Option<String>) -> Result<()> {
+        self.send(AudioCommand::SetLoopback { device: name })
+    }
+
+    /// Open the input if it is closed and wait until audio is flowing. Returns false when it
+    /// could not be opened. Every call must be balanced by `release`, whatever it returned.
+    /// Blocks for up to a few seconds, so never call it on the main thread.
+    pub fn acquire(&self) -> bool {
+        let (ready_tx, ready_rx) = mpsc::channel();
+        if self.send(AudioCommand::Acquire { ready: ready_tx }).is_err() { return false; }
+        let opened = ready_rx.recv_timeout(Duration::from_millis(INPUT_OPEN_TIMEOUT_MS)).unwrap_or(false);
+        // A fresh stream takes a moment to deliver its first buffer; recording before then
+        // would size buffers for a rate the device has not confirmed and lose the first word.
+        let warmup_until = Instant::now() + Duration::from_millis(INPUT_WARMUP_TIMEOUT_MS);
+        while opened && !INPUT_WARM.load(Ordering::Relaxed) && Instant::now() < warmup_until {
+            thread::sleep(Duration::from_millis(5));
+        }
+        opened
+    }
+
+    /// `acquire` for async commands, waiting on a blocking thread instead of the runtime.
+    pub async fn acquire_async(&self) -> bool {
+        let controller = self.clone();
+        tauri::async_runtime::spawn_blocking(move || controller.acquire()).await.unwrap_or(false)
+    }
+
+    pub fn release(&self) {
+        let _ = self.send(AudioCommand::Release);
+    }
+}
+
+/// Pre-roll and the retroactive buffer need a continuous feed, so they keep the input open
+/// even without holds.
+fn input_wanted(holds: usize, recorder: &Arc<Mutex<RecorderState>>) -> bool {
+    holds > 0 || recorder.lock().map(|st| st.pre_roll_ms > 0 || st.retroactive_ms > 0).unwrap_or(false)
+}
+
+fn start_loopback(recorder: &Arc<Mutex<RecorderState>>, device: Option<&str>, app: &AppHandle, failed: Arc<AtomicBool>) -> Option<InputStream> {
+    let device = device?;
+    let opened = if device == SYSTEM_AUDIO_DEVICE {
+        if let Ok(mut st) = recorder.lock() {
+            st.loopback_sample_rate_hz = SYSTEM_AUDIO_SAMPLE_RATE;
+        }
+        let recorder = recorder.clone();
+        start_system_audio_capture(move |data, channels| push_loopback(&recorder, data, channels)).map(InputStream::SystemAudio)
+    } else {
+        build_loopback_stream(recorder.clone(), device, app.clone(), failed).and_then(|s| {
+            s.play()?;
+            Ok(InputStream::Device(s))
+        })
+    };
+    match opened {
+        Ok(s) => Some(s),
+        Err(e) => {
+            report_audio_error(app, recorder, "loopback-failed", "loopback", Some(device.to_string()), e.to_string());
+            None
+        }
+    }
 }
 
 pub fn start_audio_controller(
     recorder: Arc<Mutex<RecorderState>>,
     app: AppHandle,
-    initial_device: Option<String>,
+    initial_devices: Vec<String>,
+    initial_loopback: Option<String>,
 ) -> AudioController {
     let (tx, rx) = mpsc::channel::<AudioCommand>();
     let controller = AudioController { tx: Arc::new(Mutex::new(tx)) };
+    #[cfg(feature = "mock-audio")]
+    if let Some(path) = std::env::var_os("COMMANDER_MOCK_AUDIO_WAV") {
+        mock_input::start(recorder, app, std::path::PathBuf::from(path), rx);
+        return controller;
+    }
     let recorder_cloned = recorder.clone();
     let app_cloned = app.clone();
     thread::spawn(move || {
-        let mut current_device = initial_device;
-        let mut stream: Option<cpal::Stream> = match build_input_stream(recorder_cloned.clone(), current_device.clone(), app_cloned.clone()) {
-            Ok(s) => {
-                if let Err(e) = s.play() { eprintln!("Failed to play input stream: {}", e); }
-                Some(s)
-            }
-            Err(e) => {
-                eprintln!("Failed to build input stream initially: {}", e);
-                None
+        // Ordered preferences, and the one actually opened (`None` = system default).
+        let mut candidates = initial_devices;
+        let mut chosen: Option<String> = None;
+        // System default as last seen; followed whenever no candidate is in use.
+        let mut system_default = default_input_device_name();
+        let mut stream: Option<InputStream> = None;
+        // The loopback track is only captured alongside an open input.
+        let mut loopback_device = initial_loopback;
+        let mut loopback_stream: Option<InputStream> = None;
+        // Raised by the error callback of the stream currently open; replaced on every open.
+        let mut input_failed = Arc::new(AtomicBool::new(false));
+        let mut loopback_failed = Arc::new(AtomicBool::new(false));
+        // Outstanding `acquire` calls, and those still waiting for the input to open.
+        let mut holds = 0usize;
+        let mut waiting: Vec<Sender<bool>> = Vec::new();
+        // Why the next rebuild happens, when the watcher (not the user) asked for it.
+        let mut change_reason: Option<&'static str> = None;
+        let mut next = Some(AudioCommand::Rebuild { devices: candidates.clone() });
+        loop {
+            if next.is_none() {
+                for ready in waiting.drain(..) {
+                    let _ = ready.send(stream.is_some());
+                }
             }
-        };
-        while let Ok(cmd) = rx.recv() {
+            let cmd = match next.take().map(Ok).unwrap_or_else(|| rx.recv_timeout(Duration::from_secs(DEVICE_WATCH_INTERVAL_SECS))) {
+                Ok(cmd) => cmd,
+                Err(RecvTimeoutError::Timeout) => {
+                    // A broken loopback is reopened on its own; the microphone keeps running.
+                    if loopback_failed.load(Ordering::Relaxed) && loopback_stream.take().is_some() {
+                        log::warn!("Loopback stream failed; reopening");
+                        loopback_failed = Arc::new(AtomicBool::new(false));
+                        loopback_stream = start_loopback(&recorder_cloned, loopback_device.as_deref(), &app_cloned, loopback_failed.clone());
+                    }
+                    let active = recorder_cloned.lock().ok().and_then(|st| st.input_device_name.clone());
+                    let reason = if active.as_deref().is_some_and(|n| !input_device_present(n)) {
+                        // Unplugging does not always raise a stream error, so report it here unless
+                        // the error callback already did.
+                        if !input_failed.swap(false, Ordering::Relaxed) {
+                            report_audio_error(&app_cloned, &recorder_cloned, "device-lost", "input", active.clone(), "Input device disconnected".to_string());
+                        }
+                        Some("device-disconnected")
+                    } else if input_failed.swap(false, Ordering::Relaxed) {
+                        Some("stream-error")
+                    } else if let Some(quiet) = stream.as_ref().and_then(|_| stalled_secs(&recorder_cloned)) {
+                        // Some devices stop calling back without raising an error; without this
+                        // the recording would end up silently empty.
+                        report_audio_error(&app_cloned, &recorder_cloned, "stream-stalled", "input", active.clone(), format!("No audio received for {} s", quiet));
+                        Some("stream-stalled")
+                    } else if stream.is_none() && input_wanted(holds, &recorder_cloned) && (default_input_device_name().is_some() || first_present_device(&candidates).is_some()) {
+                        Some("device-connected")
+                    } else {
+                        None
+                    };
+                    if let Some(reason) = reason {
+                        // The old stream is dead either way, so this rebuild also happens mid-recording.
+                        log::warn!("Input stream needs rebuilding ({}); reopening", reason);
+                        change_reason = Some(reason);
+                        AudioCommand::Rebuild { devices: candidates.clone() }
+                    } else if chosen.is_none() && default_input_device_name().is_some_and(|d| system_default.as_ref() != Some(&d)) {
+                        let recording = recorder_cloned.lock().map(|st| st.is_recording).unwrap_or(false);
+                        // Like a preferred device, the new default waits for the recording to end.
+                        if recording { continue; }
+                        system_default = default_input_device_name();
+                        log::info!("System default input changed to '{}'", system_default.as_deref().unwrap_or_default());
+                        if stream.is_none() {
+                            let _ = app_cloned.emit("audio-device-changed", AudioDeviceChangedEvent { device: system_default.clone(), reason: "default-device-changed" });
+                            continue;
+                        }
+                        change_reason = Some("default-device-changed");
+                        AudioCommand::Rebuild { devices: candidates.clone() }
+                    } else {
+                        // A closed input picks the best device when it next opens.
+                        if stream.is_none() { continue; }
+                        let best = first_present_device(&candidates);
+                        if best.is_none() || best == chosen { continue; }
+                        let recording = recorder_cloned.lock().map(|st| st.is_recording).unwrap_or(false);
+                        // Never swap streams under a running recording; try again on the next tick.
+                        if recording { continue; }
+                        log::info!("Preferred input device '{}' appeared; switching to it", best.as_deref().unwrap_or_default());
+                        change_reason = Some("preferred-device-connected");
+                        AudioCommand::Rebuild { devices: candidates.clone() }
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
             match cmd {
-                AudioCommand::Rebuild { device } => {
+                AudioCommand::SetLoopback { device } => {
+                    if let Some(s) = loopback_stream.take() { drop(s); }
+                    loopback_device = device;
+                    if stream.is_some() {
+                        loopback_failed = Arc::new(AtomicBool::new(false));
+                        loopback_stream = start_loopback(&recorder_cloned, loopback_device.as_deref(), &app_cloned, loopback_failed.clone());
+                    }
+                }
+                AudioCommand::Acquire { ready } => {
+                    holds += 1;
+                    if stream.is_some() {
+                        let _ = ready.send(true);
+                    } else {
+                        waiting.push(ready);
+                        next = Some(AudioCommand::Rebuild { devices: candidates.clone() });
+                    }
+                }
+                AudioCommand::Release => {
+                    holds = holds.saturating_sub(1);
+                    if stream.is_some() && !input_wanted(holds, &recorder_cloned) {
+                        // Closing the stream is what turns off the microphone indicator.
+                        log::info!("Input idle; releasing the microphone");
+                        stream = None;
+                        loopback_stream = None;
+                        if let Ok(mut st) = recorder_cloned.lock() {
+                            st.input_device_name = None;
+                            st.shrink_idle_buffers();
+                        }
+                    }
+                }
+                AudioCommand::Rebuild { devices } => {
                     if let Some(s) = stream.take() { drop(s); }
-                    current_device = device;
-                    match build_input_stream(recorder_cloned.clone(), current_device.clone(), app_cloned.clone()) {
+                    if let Ok(mut st) = recorder_cloned.lock() { st.input_device_name = None; }
+                    let reason = change_reason.take();
+                    candidates = devices;
+                    chosen = first_present_device(&candidates);
+                    system_default = default_input_device_name();
+                    if !input_wanted(holds, &recorder_cloned) {
+                        // Opened by the next `acquire`.
+                        loopback_stream = None;
+                        continue;
+                    }
+                    if mic_permission_denied(chosen.as_deref()) {
+                        // Retried on each tick until access is granted in System Settings.
+                        mark_input_unavailable(&app_cloned, NoInputDeviceEvent {
+                            reason: "permission-denied",
+                            message: "Microphone access is denied in System Settings → Privacy & Security → Microphone".to_string(),
+                        });
+                        continue;
+                    }
+                    input_failed = Arc::new(AtomicBool::new(false));
+                    match open_input_stream(recorder_cloned.clone(), chosen.clone(), app_cloned.clone(), input_failed.clone()) {
                         Ok(s) => {
-                            if let Err(e) = s.play() { eprintln!("Failed to play rebuilt input stream: {}", e); }
                             stream = Some(s);
+                            // The stall clock starts at open, before the first callback.
+                            if let Ok(mut st) = recorder_cloned.lock() { st.last_callback = Some(Instant::now()); }
+                            if loopback_stream.is_none() {
+                                loopback_failed = Arc::new(AtomicBool::new(false));
+                                loopback_stream = start_loopback(&recorder_cloned, loopback_device.as_deref(), &app_cloned, loopback_failed.clone());
+                            }
+                            mark_input_available(&app_cloned);
+                            let device = recorder_cloned.lock().ok().and_then(|st| st.input_device_name.clone());
+                            let preferred = candidates.first().cloned();
+                            let fallback = preferred.is_some() && chosen != preferred;
+                            if fallback {
+                                log::warn!("Preferred input device {:?} not connected; using {:?}", preferred, device);
+                            }
+                            let _ = app_cloned.emit("audio-input-selected", AudioInputSelectedEvent { device: device.clone(), preferred, fallback });
+                            if reason == Some("preferred-device-connected") {
+                                let _ = app_cloned.emit("input-device-switched", InputDeviceSwitchedEvent { device: device.clone() });
+                            }
+                            if let Some(reason) = reason {
+                                let _ = app_cloned.emit("audio-device-changed", AudioDeviceChangedEvent { device, reason });
+                            }
                         }
                         Err(e) => {
-                            eprintln!("Failed to rebuild input stream: {}", e);
+                            if let Ok(mut st) = recorder_cloned.lock() { st.input_device_name = None; }
+                            let reason = if chosen.is_none() && default_input_device_name().is_none() { "no-device" } else { "open-failed" };
+                            if mark_input_unavailable(&app_cloned, NoInputDeviceEvent { reason, message: e.to_string() }) {
+                                report_audio_error(&app_cloned, &recorder_cloned, "rebuild-failed", "input", chosen.clone(), e.to_string());
+                            }
                             stream = None;
                         }
                     }
@@ -244,22 +1413,545 @@ pub fn start_audio_controller(
     controller
 }
 
+/// Test-only input source that replays a WAV file through `on_audio` in real time,
+/// exactly as the CPAL callback would.
+#[cfg(feature = "mock-audio")]
+mod mock_input {
+    use super::{on_audio, read_wav_mono, AudioCommand, RecorderState};
+    use crate::processing::{self, Pipeline};
+    use std::path::PathBuf;
+    use std::sync::mpsc::Receiver;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+    use tauri::AppHandle;
+
+    /// Callback period; matches a typical CPAL buffer of ~10 ms.
+    const CHUNK_MS: u64 = 10;
+
+    pub fn start(recorder: Arc<Mutex<RecorderState>>, app: AppHandle, path: PathBuf, rx: Receiver<AudioCommand>) {
+        std::thread::spawn(move || {
+            let (samples, sample_rate) = match read_wav_mono(&path) {
+                Ok(v) => v,
+                Err(e) => { log::error!("mock audio: failed to read {}: {}", path.display(), e); return; }
+            };
+            if let Ok(mut st) = recorder.lock() {
+                st.set_sample_rate_hz(sample_rate);
+            }
+            log::info!("mock audio: looping {} ({} Hz)", path.display(), sample_rate);
+            let chunk_len = (sample_rate as usize * CHUNK_MS as usize / 1000).max(1);
+            if samples.is_empty() { return; }
+            processing::configure(&app);
+            let mut pipeline = Pipeline::new(sample_rate);
+            loop {
+                for chunk in samples.chunks(chunk_len) {
+                    // The file source is always open, so holds succeed and device switches are ignored.
+                    while let Ok(cmd) = rx.try_recv() {
+                        match cmd {
+                            AudioCommand::Acquire { ready } => { let _ = ready.send(true); }
+                            cmd => log::debug!("mock audio: ignoring {:?}", cmd),
+                        }
+                    }
+                    on_audio(pipeline.process(chunk.to_vec()), &recorder, &app);
+                    std::thread::sleep(Duration::from_millis(CHUNK_MS));
+                }
+            }
+        });
+    }
+}
+
+/// Sum two tracks of the same rate; the shorter one is padded with silence.
+pub fn mix_tracks(a: &[f32], b: &[f32]) -> Vec<f32> {
+    (0..a.len().max(b.len()))
+        .map(|i| (a.get(i).copied().unwrap_or(0.0) + b.get(i).copied().unwrap_or(0.0)).clamp(-1.0, 1.0))
+        .collect()
+}
+
+/// Interleave two tracks of the same rate as left/right stereo frames.
+pub fn interleave_tracks(left: &[f32], right: &[f32]) -> Vec<f32> {
+    (0..left.len().max(right.len()))
+        .flat_map(|i| [left.get(i).copied().unwrap_or(0.0), right.get(i).copied().unwrap_or(0.0)])
+        .collect()
+}
 
-pub fn save_recording_wav(app: &AppHandle, samples: &[i16], sample_rate_hz: u32) -> Result<std::path::PathBuf> {
+/// Save interleaved `samples` with `channels` channels to the recordings directory, as a
+/// float WAV or as 24-bit FLAC.
+/// Named after `started_at_ms`, when the recording began (ms since the Unix epoch).
+pub fn save_recording(app: &AppHandle, samples: &[f32], sample_rate_hz: u32, channels: u16, format: RecordingFormat, started_at_ms: u64) -> Result<std::path::PathBuf> {
     if samples.is_empty() { return Err(anyhow!("no samples to save")); }
-    let base = app.path().app_data_dir().map_err(|e| anyhow!(format!("failed to get app data dir: {}", e)))?;
-    let dir = base.join("recordings");
+    let dir = recordings_dir(app).map_err(|e| anyhow!(format!("failed to get recordings dir: {}", e)))?;
     std::fs::create_dir_all(&dir).map_err(|e| anyhow!(format!("failed to create recordings dir: {}", e)))?;
-    let epoch = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis();
-    let filename = format!("rec_{}_{}hz.wav", epoch, sample_rate_hz);
+    let filename = format!("rec_{}_{}hz.{}", started_at_ms, sample_rate_hz, format.extension());
     let path = dir.join(filename);
-    let spec = WavSpec { channels: 1, sample_rate: sample_rate_hz, bits_per_sample: 16, sample_format: SampleFormat::Int };
-    let mut writer = WavWriter::create(&path, spec).map_err(|e| anyhow!(format!("failed to create wav: {}", e)))?;
+    match format {
+        RecordingFormat::Wav => write_float_wav(&path, samples, sample_rate_hz, channels)?,
+        RecordingFormat::Flac => write_flac(&path, samples, sample_rate_hz, channels)?,
+    }
+    Ok(path)
+}
+
+pub(crate) fn write_float_wav(path: &std::path::Path, samples: &[f32], sample_rate_hz: u32, channels: u16) -> Result<()> {
+    let spec = WavSpec { channels, sample_rate: sample_rate_hz, bits_per_sample: 32, sample_format: SampleFormat::Float };
+    let mut writer = WavWriter::create(path, spec).map_err(|e| anyhow!(format!("failed to create wav: {}", e)))?;
     for s in samples {
         writer.write_sample(*s).map_err(|e| anyhow!(format!("failed to write sample: {}", e)))?;
     }
     writer.finalize().map_err(|e| anyhow!(format!("failed to finalize wav: {}", e)))?;
+    Ok(())
+}
+
+/// FLAC is integer-only; 24 bits keeps it indistinguishable from the f32 capture.
+const FLAC_BITS_PER_SAMPLE: usize = 24;
+
+fn write_flac(path: &std::path::Path, samples: &[f32], sample_rate_hz: u32, channels: u16) -> Result<()> {
+    use flacenc::component::BitRepr;
+    use flacenc::error::Verify;
+    let scale = ((1i32 << (FLAC_BITS_PER_SAMPLE - 1)) - 1) as f32;
+    let ints: Vec<i32> = samples.iter().map(|s| (s.clamp(-1.0, 1.0) * scale).round() as i32).collect();
+    let config = flacenc::config::Encoder::default()
+        .into_verified()
+        .map_err(|(_, e)| anyhow!(format!("invalid flac encoder config: {:?}", e)))?;
+    let source = flacenc::source::MemSource::from_samples(&ints, channels as usize, FLAC_BITS_PER_SAMPLE, sample_rate_hz as usize);
+    let stream = flacenc::encode_with_fixed_block_size(&config, source, config.block_size)
+        .map_err(|e| anyhow!(format!("failed to encode flac: {:?}", e)))?;
+    let mut sink = flacenc::bitsink::ByteSink::new();
+    stream.write(&mut sink).map_err(|e| anyhow!(format!("failed to write flac: {:?}", e)))?;
+    std::fs::write(path, sink.as_slice()).map_err(|e| anyhow!(format!("failed to write flac: {}", e)))?;
+    Ok(())
+}
+
+fn read_flac_mono(path: &std::path::Path) -> Result<(Vec<f32>, u32)> {
+    let mut reader = claxon::FlacReader::open(path).map_err(|e| anyhow!(format!("failed to open flac: {}", e)))?;
+    let info = reader.streaminfo();
+    let scale = (1i64 << (info.bits_per_sample.max(1) - 1)) as f32;
+    let interleaved: Vec<f32> = reader
+        .samples()
+        .map(|s| s.map(|v| v as f32 / scale))
+        .collect::<std::result::Result<_, _>>()
+        .map_err(|e| anyhow!(format!("failed to read sample: {}", e)))?;
+    Ok((downmix(&interleaved, info.channels.max(1) as usize), info.sample_rate))
+}
+
+fn is_flac(path: &std::path::Path) -> bool {
+    path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("flac"))
+}
+
+/// A WAV or FLAC file, the formats `read_recording_mono` can load.
+pub(crate) fn is_saved_recording(path: &std::path::Path) -> bool {
+    is_flac(path) || path.extension().and_then(|e| e.to_str()).is_some_and(|e| e.eq_ignore_ascii_case("wav"))
+}
+
+/// Load a saved recording (WAV or FLAC) as mono f32 in [-1, 1].
+pub fn read_recording_mono(path: &std::path::Path) -> Result<(Vec<f32>, u32)> {
+    if is_flac(path) { read_flac_mono(path) } else { read_wav_mono(path) }
+}
+
+fn recording_duration_ms(path: &std::path::Path) -> u64 {
+    if is_flac(path) {
+        claxon::FlacReader::open(path)
+            .map(|r| {
+                let info = r.streaminfo();
+                info.samples.unwrap_or(0) * 1000 / info.sample_rate.max(1) as u64
+            })
+            .unwrap_or(0)
+    } else {
+        hound::WavReader::open(path)
+            .map(|r| r.duration() as u64 * 1000 / r.spec().sample_rate.max(1) as u64)
+            .unwrap_or(0)
+    }
+}
+
+/// Load a WAV file as mono f32 in [-1, 1], averaging channels and rescaling integer formats.
+pub fn read_wav_mono(path: &std::path::Path) -> Result<(Vec<f32>, u32)> {
+    let reader = hound::WavReader::open(path).map_err(|e| anyhow!(format!("failed to open wav: {}", e)))?;
+    decode_wav_mono(reader)
+}
+
+/// Same as `read_wav_mono` for a WAV file held in memory, such as an upload.
+pub fn read_wav_mono_bytes(bytes: &[u8]) -> Result<(Vec<f32>, u32)> {
+    let reader = hound::WavReader::new(std::io::Cursor::new(bytes)).map_err(|e| anyhow!(format!("failed to open wav: {}", e)))?;
+    decode_wav_mono(reader)
+}
+
+fn decode_wav_mono<R: std::io::Read>(mut reader: hound::WavReader<R>) -> Result<(Vec<f32>, u32)> {
+    let spec = reader.spec();
+    let channels = spec.channels.max(1) as usize;
+    let interleaved: Vec<f32> = match spec.sample_format {
+        SampleFormat::Int => {
+            let scale = (1i64 << (spec.bits_per_sample.max(1) - 1)) as f32;
+            reader
+                .samples::<i32>()
+                .map(|s| s.map(|v| v as f32 / scale))
+                .collect::<std::result::Result<_, _>>()
+                .map_err(|e| anyhow!(format!("failed to read sample: {}", e)))?
+        }
+        SampleFormat::Float => reader
+            .samples::<f32>()
+            .map(|s| s.map(|v| v.clamp(-1.0, 1.0)))
+            .collect::<std::result::Result<_, _>>()
+            .map_err(|e| anyhow!(format!("failed to read sample: {}", e)))?,
+    };
+    Ok((downmix(&interleaved, channels), spec.sample_rate))
+}
+
+/// Opus always decodes at 48 kHz, in frames of at most 120 ms.
+const OPUS_SAMPLE_RATE: u32 = 48_000;
+const OPUS_MAX_FRAME: usize = 5760;
+
+/// An uploaded recording as mono f32: WAV, or the m4a (AAC) and WebM (Opus) files that phone
+/// recorder apps and browsers produce. Compressed audio longer than `max_secs` is refused,
+/// since a small file can decode to hours of samples.
+pub fn read_upload_mono_bytes(bytes: &[u8], max_secs: u64) -> Result<(Vec<f32>, u32)> {
+    if bytes.starts_with(b"RIFF") {
+        return read_wav_mono_bytes(bytes);
+    }
+    let mss = MediaSourceStream::new(Box::new(std::io::Cursor::new(bytes.to_vec())), Default::default());
+    let mut format = symphonia::default::get_probe()
+        .format(&Hint::new(), mss, &FormatOptions::default(), &MetadataOptions::default())
+        .map_err(|e| anyhow!(format!("unsupported audio format: {}", e)))?
+        .format;
+    let track = format
+        .tracks()
+        .iter()
+        .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+        .cloned()
+        .ok_or_else(|| anyhow!("no audio track"))?;
+    let params = &track.codec_params;
+    type PacketDecoder = Box<dyn FnMut(&Packet) -> Result<Vec<f32>>>;
+    // symphonia has no Opus decoder, so WebM/Opus packets go to libopus.
+    let (mut decode, channels, sample_rate, skip): (PacketDecoder, usize, u32, usize) = if params.codec == CODEC_TYPE_OPUS {
+        let head = params.extra_data.as_deref().unwrap_or_default();
+        let channels = opus_channels(head).or(params.channels.map(|c| c.count())).unwrap_or(1);
+        let layout = match channels {
+            1 => opus::Channels::Mono,
+            2 => opus::Channels::Stereo,
+            n => return Err(anyhow!("unsupported opus channel count: {}", n)),
+        };
+        let mut decoder = opus::Decoder::new(OPUS_SAMPLE_RATE, layout).map_err(|e| anyhow!(format!("failed to open opus: {}", e)))?;
+        let mut out = vec![0.0f32; OPUS_MAX_FRAME * channels];
+        let decode: PacketDecoder = Box::new(move |packet| {
+            let frames = decoder
+                .decode_float(&packet.data, &mut out, false)
+                .map_err(|e| anyhow!(format!("failed to decode opus: {}", e)))?;
+            Ok(out[..frames * channels].to_vec())
+        });
+        (decode, channels, OPUS_SAMPLE_RATE, opus_pre_skip(head))
+    } else {
+        let mut decoder = symphonia::default::get_codecs()
+            .make(params, &DecoderOptions::default())
+            .map_err(|e| anyhow!(format!("unsupported audio codec: {}", e)))?;
+        let sample_rate = params.sample_rate.ok_or_else(|| anyhow!("audio track has no sample rate"))?;
+        let channels = params.channels.map(|c| c.count()).unwrap_or(1);
+        let mut buf: Option<SampleBuffer<f32>> = None;
+        let decode: PacketDecoder = Box::new(move |packet| {
+            let decoded = match decoder.decode(packet) {
+                Ok(decoded) => decoded,
+                // A corrupt packet only loses its own few milliseconds.
+                Err(SymphoniaError::DecodeError(_)) => return Ok(Vec::new()),
+                Err(e) => return Err(anyhow!(format!("failed to decode audio: {}", e))),
+            };
+            let buf = buf.get_or_insert_with(|| SampleBuffer::new(decoded.capacity() as u64, *decoded.spec()));
+            buf.copy_interleaved_ref(decoded);
+            Ok(buf.samples().to_vec())
+        });
+        (decode, channels, sample_rate, 0)
+    };
+    let mut interleaved = Vec::new();
+    loop {
+        let packet = match format.next_packet() {
+            Ok(packet) => packet,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(anyhow!(format!("failed to read audio: {}", e))),
+        };
+        if packet.track_id() == track.id {
+            interleaved.extend(decode(&packet)?);
+        }
+        if interleaved.len() as u64 > max_secs * sample_rate as u64 * channels as u64 {
+            return Err(anyhow!("recording is longer than {} minutes", max_secs / 60));
+        }
+    }
+    let mut mono = downmix(&interleaved, channels.max(1));
+    mono.drain(..skip.min(mono.len()));
+    Ok((mono, sample_rate))
+}
+
+/// Channel count from an `OpusHead` header, if `head` is one.
+fn opus_channels(head: &[u8]) -> Option<usize> {
+    if !head.starts_with(b"OpusHead") { return None; }
+    head.get(9).map(|&c| c as usize)
+}
+
+/// Samples of encoder delay at the start of an Opus stream that are not part of the audio.
+fn opus_pre_skip(head: &[u8]) -> usize {
+    match head {
+        [b'O', b'p', b'u', b's', b'H', b'e', b'a', b'd', _, _, lo, hi, ..] => u16::from_le_bytes([*lo, *hi]) as usize,
+        _ => 0,
+    }
+}
+
+/// Resolve a bare file name inside the recordings directory, rejecting path components.
+pub fn recording_path(app: &AppHandle, filename: &str) -> Result<std::path::PathBuf> {
+    let name = std::path::Path::new(filename)
+        .file_name()
+        .filter(|n| n.to_string_lossy() == filename)
+        .ok_or_else(|| anyhow!("invalid recording name: {}", filename))?;
+    let path = recordings_dir(app)?.join(name);
+    if !path.is_file() {
+        return Err(anyhow!("recording not found: {}", filename));
+    }
     Ok(path)
 }
 
+#[derive(Serialize, Clone, Debug)]
+pub struct RecordingInfo {
+    pub name: String,
+    pub duration_ms: u64,
+    pub size_bytes: u64,
+    /// Last-modified time in milliseconds since the Unix epoch.
+    pub modified_ms: u128,
+}
+
+/// Saved recordings, newest first.
+pub fn list_recordings(app: &AppHandle) -> Result<Vec<RecordingInfo>> {
+    let dir = recordings_dir(app)?;
+    if !dir.exists() { return Ok(Vec::new()); }
+    let mut out = Vec::new();
+    for entry in std::fs::read_dir(&dir)?.flatten() {
+        let path = entry.path();
+        if !is_saved_recording(&path) { continue; }
+        let Ok(md) = entry.metadata() else { continue };
+        let duration_ms = recording_duration_ms(&path);
+        let modified_ms = md
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+            .map(|d| d.as_millis())
+            .unwrap_or(0);
+        out.push(RecordingInfo {
+            name: entry.file_name().to_string_lossy().to_string(),
+            duration_ms,
+            size_bytes: md.len(),
+            modified_ms,
+        });
+    }
+    out.sort_by(|a, b| b.modified_ms.cmp(&a.modified_ms));
+    Ok(out)
+}
+
+/// Saved recordings with their modification times, newest first.
+fn recording_files(app: &AppHandle) -> Result<Vec<(std::path::PathBuf, SystemTime)>> {
+    let dir = recordings_dir(app)?;
+    if !dir.exists() { return Ok(Vec::new()); }
+    let mut out = Vec::new();
+    for entry in std::fs::read_dir(&dir)?.flatten() {
+        let path = entry.path();
+        if !is_saved_recording(&path) { continue; }
+        let Ok(modified) = entry.metadata().and_then(|md| md.modified()) else { continue };
+        out.push((path, modified));
+    }
+    out.sort_by(|a, b| b.1.cmp(&a.1));
+    Ok(out)
+}
+
+/// Delete saved recordings beyond `max_count` (oldest first) or older than `max_age_days`.
+/// Returns how many were deleted.
+pub fn sweep_recordings(app: &AppHandle, cfg: &RecordingRetentionConfig) -> Result<usize> {
+    let max_age = cfg.max_age_days.map(|days| Duration::from_secs(days * 24 * 60 * 60));
+    let now = SystemTime::now();
+    let mut deleted = 0;
+    for (i, (path, modified)) in recording_files(app)?.into_iter().enumerate() {
+        let too_many = cfg.max_count.is_some_and(|max| i >= max);
+        let too_old = max_age.is_some_and(|max| now.duration_since(modified).unwrap_or_default() > max);
+        if !too_many && !too_old { continue; }
+        match std::fs::remove_file(&path) {
+            Ok(()) => deleted += 1,
+            Err(e) => log::warn!("Failed to delete old recording {}: {}", path.display(), e),
+        }
+    }
+    Ok(deleted)
+}
 
+/// Delete every saved recording. Returns how many were deleted.
+pub fn clear_recordings(app: &AppHandle) -> Result<usize> {
+    let files = recording_files(app)?;
+    for (path, _) in &files {
+        std::fs::remove_file(path)?;
+    }
+    Ok(files.len())
+}
+
+/// Outcome of a microphone test: loudness plus a coarse peak envelope for drawing.
+#[derive(Serialize, Clone, Debug)]
+pub struct MicTestResult {
+    pub device: Option<String>,
+    pub duration_ms: u64,
+    pub sample_rate_hz: u32,
+    pub levels: LevelStats,
+    /// Peak amplitude (0..1) per bucket, oldest first.
+    pub waveform: Vec<f32>,
+}
+
+/// Reduce `samples` to `buckets` peak values in 0..1.
+pub fn waveform_peaks(samples: &[f32], buckets: usize) -> Vec<f32> {
+    if samples.is_empty() || buckets == 0 { return Vec::new(); }
+    let per_bucket = samples.len().div_ceil(buckets);
+    samples
+        .chunks(per_bucket)
+        .map(|c| c.iter().fold(0.0f32, |m, v| m.max(v.abs())))
+        .map(|v| v.min(1.0))
+        .collect()
+}
+
+/// Play mono PCM through the default output device on a background thread.
+pub fn play_samples(samples: Vec<f32>, sample_rate_hz: u32) -> Result<()> {
+    if samples.is_empty() { return Err(anyhow!("nothing to play")); }
+    thread::spawn(move || {
+        if let Err(e) = play_blocking(&samples, sample_rate_hz) {
+            log::error!("Playback failed: {}", e);
+        }
+    });
+    Ok(())
+}
+
+/// A short decaying click, played when a recording is armed.
+pub fn play_tick() {
+    const RATE_HZ: u32 = 48_000;
+    const TICK_MS: u32 = 25;
+    let len = (RATE_HZ * TICK_MS / 1000) as usize;
+    let samples = (0..len)
+        .map(|i| {
+            let t = i as f32 / RATE_HZ as f32;
+            0.3 * (2.0 * std::f32::consts::PI * 2000.0 * t).sin() * (-t * 250.0).exp()
+        })
+        .collect();
+    let _ = play_samples(samples, RATE_HZ);
+}
+
+fn play_blocking(samples: &[f32], sample_rate_hz: u32) -> Result<()> {
+    let host = cpal::default_host();
+    let device = host
+        .default_output_device()
+        .ok_or_else(|| anyhow!("No default output device"))?;
+    let supported = device.default_output_config()?;
+    let out_rate = supported.sample_rate().0;
+    let channels = supported.channels() as usize;
+    let data = Arc::new(resample_linear(samples, sample_rate_hz, out_rate));
+    let duration = std::time::Duration::from_millis(data.len() as u64 * 1000 / out_rate.max(1) as u64);
+    let config: cpal::StreamConfig = supported.config();
+    let stream = match supported.sample_format() {
+        cpal::SampleFormat::F32 => build_playback_stream::<f32>(&device, &config, data, channels)?,
+        cpal::SampleFormat::I16 => build_playback_stream::<i16>(&device, &config, data, channels)?,
+        cpal::SampleFormat::U16 => build_playback_stream::<u16>(&device, &config, data, channels)?,
+        _ => return Err(anyhow!("Unsupported output sample format")),
+    };
+    stream.play()?;
+    // Let the device drain its last buffer before the stream is dropped.
+    thread::sleep(duration + std::time::Duration::from_millis(200));
+    Ok(())
+}
+
+fn build_playback_stream<T>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    data: Arc<Vec<f32>>,
+    channels: usize,
+) -> Result<cpal::Stream>
+where
+    T: cpal::SizedSample + cpal::FromSample<f32>,
+{
+    let mut pos = 0usize;
+    let stream = device.build_output_stream(
+        config,
+        move |out: &mut [T], _| {
+            for frame in out.chunks_mut(channels.max(1)) {
+                let v = data.get(pos).copied().unwrap_or(0.0);
+                pos += 1;
+                let sample = T::from_sample_(v);
+                for o in frame.iter_mut() { *o = sample; }
+            }
+        },
+        |err| log::error!("Playback stream error: {err}"),
+        None,
+    )?;
+    Ok(stream)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_ring_overwrites_the_oldest_samples() {
+        let mut ring = SampleRing::with_max_len(3);
+        assert_eq!(ring.extend([1.0, 2.0]), 0);
+        assert_eq!(ring.extend([3.0, 4.0, 5.0]), 2);
+        assert_eq!(ring.copy_range(1..3), vec![4.0, 5.0]);
+        assert_eq!(ring.take(), vec![3.0, 4.0, 5.0]);
+        assert!(ring.is_empty());
+    }
+
+    #[test]
+    fn sample_ring_shrinking_drops_the_oldest_samples() {
+        let mut ring = SampleRing::with_max_len(5);
+        ring.extend([1.0, 2.0, 3.0, 4.0]);
+        ring.set_max_len(2);
+        assert_eq!(ring.max_len(), 2);
+        assert_eq!(ring.take(), vec![3.0, 4.0]);
+        assert_eq!(SampleRing::with_max_len(0).max_len(), 1);
+    }
+
+    #[test]
+    fn mix_tracks_pads_and_clamps() {
+        assert_eq!(mix_tracks(&[0.5, 0.75, 0.1], &[0.25, 0.5]), vec![0.75, 1.0, 0.1]);
+        assert_eq!(mix_tracks(&[-0.75], &[-0.5]), vec![-1.0]);
+    }
+
+    #[test]
+    fn interleave_tracks_pads_the_shorter_channel() {
+        assert_eq!(interleave_tracks(&[1.0, 2.0], &[3.0]), vec![1.0, 3.0, 2.0, 0.0]);
+        assert!(interleave_tracks(&[], &[]).is_empty());
+    }
+
+    #[test]
+    fn spectrum_bands_peak_in_the_band_of_a_tone() {
+        let rate = 16_000;
+        let tone: Vec<f32> = (0..SPECTRUM_FFT_SIZE)
+            .map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / rate as f32).sin())
+            .collect();
+        let bands = spectrum_bands(&tone, rate, 8);
+        assert_eq!(bands.len(), 8);
+        let loudest = (0..bands.len()).max_by(|a, b| bands[*a].total_cmp(&bands[*b])).unwrap();
+        // 1 kHz falls between 50 * 160^(4/8) and 50 * 160^(5/8) Hz.
+        assert_eq!(loudest, 4);
+        assert!(bands[4] > 0.9, "full-scale tone measured {}", bands[4]);
+        assert!(spectrum_bands(&[0.0; 256], rate, 8).iter().all(|b| *b == 0.0));
+    }
+
+    #[test]
+    fn flac_round_trips_within_24_bit_precision() {
+        let path = std::env::temp_dir().join(format!("commander-flac-test-{}.flac", std::process::id()));
+        let samples: Vec<f32> = (0..5000).map(|i| 0.8 * (i as f32 * 0.01).sin()).collect();
+        write_flac(&path, &samples, 16_000, 1).unwrap();
+        let read = read_recording_mono(&path);
+        let _ = std::fs::remove_file(&path);
+        let (decoded, rate) = read.unwrap();
+        assert_eq!(rate, 16_000);
+        assert_eq!(decoded.len(), samples.len());
+        assert!(samples.iter().zip(&decoded).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+
+    #[test]
+    fn opus_head_gives_channels_and_pre_skip() {
+        let head = [b"OpusHead".as_slice(), &[1, 2, 0x38, 0x01, 0x80, 0xbb, 0, 0, 0, 0, 0]].concat();
+        assert_eq!(opus_channels(&head), Some(2));
+        assert_eq!(opus_pre_skip(&head), 312);
+        assert_eq!(opus_channels(b"not a header"), None);
+        assert_eq!(opus_pre_skip(b"OpusHe"), 0);
+    }
+
+    #[test]
+    fn saved_recordings_are_recognised_by_extension() {
+        assert!(is_saved_recording(std::path::Path::new("rec_1_16000hz.FLAC")));
+        assert!(is_saved_recording(std::path::Path::new("take.wav")));
+        assert!(!is_saved_recording(std::path::Path::new("notes.txt")));
+    }
+}
@@ -1,15 +1,61 @@
-use crate::audio::{RecorderState, list_input_device_names, AudioController, save_recording_wav};
-use crate::config::{AutoPasteConfig, HoldToRecordConfig, LanguageConfig, PromptConfig, ShortcutConfig, read_model_config, write_model_config, read_audio_input_config, write_audio_input_config, AudioInputConfig};
-use crate::http_server::{load_model, is_model_loaded};
-use crate::transcription::transcribe_and_copy;
-use crate::tray::{make_recording_icon, make_transcribing_icon};
-#[cfg(target_os = "macos")]
-use crate::platform;
+use crate::audio::{self, AudioInputDevice, RecorderMemoryUsage, RecorderState, RecordingInfo, AudioController};
+use crate::http_server::{self, current_service, load_model, is_model_loaded, recent_requests, set_idle_unload, set_inference, set_rate_limit, AccessLogEntry};
+use crate::config::{
+    self, AppendModeConfig, ArmingDelayConfig, AudioLevelsConfig, AudioPipelineConfig,
+    AudioStageKind, AutoPasteAppRule, AutoPasteConfig, AutoPasteRulesConfig, AutoPasteTimingConfig,
+    AutoStopConfig, DecodingConfig, FastPreviewConfig, MAX_DECODING_CANDIDATES, HoldToRecordConfig, InferenceConfig,
+    InlineTimestampsConfig, InputFormat, InputFormatsConfig, InputSourceLanguageConfig,
+    LanguageConfig, LatencyBudgetConfig, MediaPauseConfig, MeetingModeConfig, ModelIdleConfig,
+    NoiseProfile, NoiseProfilesConfig, PasteReviewConfig, PreRollConfig, ProfilesConfig,
+    PromptConfig, QualityGuardConfig, RateLimitConfig, RecordingBlocklistConfig,
+    RecordingLimitConfig, RecordingRetentionConfig, RetroactiveConfig, ShortcutAction,
+    ShortcutBindingsConfig, ShortcutConfig, SpokenFeedbackConfig, StorageConfig, StreamingConfig,
+    TimeBoxConfig, WatchFolderConfig, WhisperParamsConfig, read_append_mode_config,
+    read_arming_delay_config, read_audio_input_config, read_audio_levels_config,
+    read_audio_pipeline_config, read_auto_paste_rules, read_auto_paste_timing_config,
+    read_auto_stop_config, read_companion_config, read_decoding_config, read_fast_preview_config,
+    read_inference_config, read_input_formats_config, read_input_source_language_config,
+    read_latency_budget_config, read_media_pause_config, read_meeting_mode_config,
+    read_model_config, read_model_idle_config, read_noise_profiles, read_paste_review_config,
+    read_pre_roll_config, read_profiles_config, read_quality_guard_config, read_rate_limit_config,
+    read_recording_blocklist, read_recording_limit_config, read_recording_retention_config,
+    read_retroactive_config, read_shortcut_bindings, read_spoken_feedback_config,
+    read_storage_config, read_streaming_config, read_time_box_config, read_watch_folder_config,
+    read_whisper_params_config, write_append_mode_config, write_arming_delay_config,
+    write_audio_input_config, write_audio_levels_config, write_audio_pipeline_config,
+    write_auto_paste_rules, write_auto_paste_timing_config, write_auto_stop_config,
+    write_companion_config, write_decoding_config, write_fast_preview_config,
+    write_inference_config, write_input_formats_config, write_input_source_language_config,
+    write_latency_budget_config, write_media_pause_config, write_meeting_mode_config,
+    write_model_config, write_model_idle_config, write_noise_profiles, write_paste_review_config,
+    write_pre_roll_config, write_profiles_config, write_quality_guard_config,
+    write_rate_limit_config, write_recording_blocklist, write_recording_limit_config,
+    write_recording_retention_config, write_retroactive_config, write_shortcut_bindings,
+    write_spoken_feedback_config, write_storage_config, write_streaming_config,
+    write_time_box_config, write_watch_folder_config, write_whisper_params_config,
+};
+use crate::actions;
+use crate::processing;
+use crate::companion::{self, CompanionStatus};
+use crate::shortcuts;
+use crate::sinks;
+use crate::phrases;
+use crate::sessions;
+use crate::perf_stats::{read_perf_stats, reset_perf_stats, ModelPerfStats};
+use std::collections::HashMap;
+use crate::health::{report_health, StartupHealth};
+use crate::transcription::{self, TranscriptionOverrides};
+use crate::jobs::{self, JobSource, TranscriptionJob};
+use crate::recording::{self, ToggleOutcome};
+use crate::whisper_service::{cpu_count, resample_linear, LanguageProbability, MAX_PROMPT_TOKENS};
+use crate::platform::{self, FrontmostApp};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
-use tauri::{image::Image, AppHandle, Manager, Emitter};
+use tauri::{AppHandle, Manager, Emitter};
 use anyhow::Result as AnyResult;
+use log::{info, warn};
 use std::io::Write;
+use whisper_rs::DtwModelPreset;
 
 #[tauri::command]
 pub fn get_current_shortcut(app_handle: tauri::AppHandle) -> Result<ShortcutConfig, String> {
@@ -72,6 +118,16 @@ pub fn save_default_language(app_handle: tauri::AppHandle, language: Option<Stri
     Ok(())
 }
 
+#[tauri::command]
+pub fn get_input_source_language_enabled(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(read_input_source_language_config(&app_handle).enabled)
+}
+
+#[tauri::command]
+pub fn save_input_source_language_enabled(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    write_input_source_language_config(&app_handle, &InputSourceLanguageConfig { enabled }).map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub fn get_default_prompt(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
     let config_dir = app_handle
@@ -98,12 +154,40 @@ pub fn save_default_prompt(app_handle: tauri::AppHandle, prompt: Option<String>)
         .map_err(|e| format!("Failed to get config dir: {}", e))?;
     std::fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
     let config_path = config_dir.join("prompt.json");
+    let validation = validate_prompt(prompt.clone())?;
+    if validation.exceeds_limit {
+        let _ = app_handle.emit("prompt-too-long", &validation);
+    }
     let cfg = PromptConfig { prompt };
     let content = serde_json::to_string_pretty(&cfg).map_err(|e| format!("Failed to serialize prompt config: {}", e))?;
     std::fs::write(&config_path, content).map_err(|e| format!("Failed to write prompt config: {}", e))?;
     Ok(())
 }
 
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct PromptValidation {
+    pub token_count: usize,
+    pub max_tokens: usize,
+    pub exceeds_limit: bool,
+    /// True when no model is loaded and the count is a chars/4 approximation.
+    pub estimated: bool,
+}
+
+#[tauri::command]
+pub fn validate_prompt(prompt: Option<String>) -> Result<PromptValidation, String> {
+    let prompt = prompt.unwrap_or_default();
+    let (token_count, estimated) = match current_service() {
+        Some(svc) => (svc.count_tokens(&prompt).map_err(|e| e.to_string())?, false),
+        None => (prompt.chars().count().div_ceil(4), true),
+    };
+    Ok(PromptValidation {
+        token_count,
+        max_tokens: MAX_PROMPT_TOKENS,
+        exceeds_limit: token_count > MAX_PROMPT_TOKENS,
+        estimated,
+    })
+}
+
 #[tauri::command]
 pub fn get_auto_paste_enabled(app_handle: tauri::AppHandle) -> Result<bool, String> {
     let config_dir = app_handle
@@ -136,6 +220,47 @@ pub fn save_auto_paste_enabled(app_handle: tauri::AppHandle, enabled: bool) -> R
     Ok(())
 }
 
+#[tauri::command]
+pub fn get_auto_paste_timing(app_handle: tauri::AppHandle) -> Result<AutoPasteTimingConfig, String> {
+    Ok(read_auto_paste_timing_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_auto_paste_timing(app_handle: tauri::AppHandle, config: AutoPasteTimingConfig) -> Result<(), String> {
+    write_auto_paste_timing_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_auto_paste_rules(app_handle: tauri::AppHandle) -> Result<AutoPasteRulesConfig, String> {
+    Ok(read_auto_paste_rules(&app_handle))
+}
+
+#[tauri::command]
+pub fn set_auto_paste_rule(app_handle: tauri::AppHandle, app: String, allow: bool) -> Result<(), String> {
+    let app = app.trim().to_string();
+    if app.is_empty() {
+        return Err("App identifier must not be empty".to_string());
+    }
+    let mut cfg = read_auto_paste_rules(&app_handle);
+    match cfg.rules.iter_mut().find(|r| r.app.eq_ignore_ascii_case(&app)) {
+        Some(rule) => rule.allow = allow,
+        None => cfg.rules.push(AutoPasteAppRule { app, allow }),
+    }
+    write_auto_paste_rules(&app_handle, &cfg).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_auto_paste_rule(app_handle: tauri::AppHandle, app: String) -> Result<(), String> {
+    let mut cfg = read_auto_paste_rules(&app_handle);
+    cfg.rules.retain(|r| !r.app.eq_ignore_ascii_case(app.trim()));
+    write_auto_paste_rules(&app_handle, &cfg).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_frontmost_app() -> Result<Option<FrontmostApp>, String> {
+    Ok(platform::frontmost_application())
+}
+
 #[tauri::command]
 pub fn get_hold_to_record_enabled(app_handle: tauri::AppHandle) -> Result<bool, String> {
     let config_dir = app_handle
@@ -156,90 +281,189 @@ pub fn get_hold_to_record_enabled(app_handle: tauri::AppHandle) -> Result<bool,
 
 #[tauri::command]
 pub fn save_hold_to_record_enabled(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
+    let mut cfg = config::read_hold_to_record_config(&app_handle);
+    cfg.enabled = enabled;
+    config::write_hold_to_record_config(&app_handle, &cfg).map_err(|e| format!("Failed to write hold-to-record config: {}", e))
+}
+
+#[tauri::command]
+pub fn get_hold_to_record_config(app_handle: tauri::AppHandle) -> Result<HoldToRecordConfig, String> {
+    Ok(config::read_hold_to_record_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_hold_to_record_config(app_handle: tauri::AppHandle, config: HoldToRecordConfig) -> Result<(), String> {
+    config::write_hold_to_record_config(&app_handle, &config).map_err(|e| format!("Failed to write hold-to-record config: {}", e))
+}
+
+#[tauri::command]
+pub fn get_inline_timestamps_enabled(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    let config_dir = app_handle
+        .path()
+        .app_config_dir()
+        .map_err(|e| format!("Failed to get config dir: {}", e))?;
+    let config_path = config_dir.join("inline_timestamps.json");
+    if config_path.exists() {
+        let content = std::fs::read_to_string(&config_path)
+            .map_err(|e| format!("Failed to read inline timestamps config: {}", e))?;
+        let cfg: InlineTimestampsConfig = serde_json::from_str(&content)
+            .map_err(|e| format!("Failed to parse inline timestamps config: {}", e))?;
+        Ok(cfg.enabled)
+    } else {
+        Ok(false)
+    }
+}
+
+#[tauri::command]
+pub fn save_inline_timestamps_enabled(app_handle: tauri::AppHandle, enabled: bool) -> Result<(), String> {
     let config_dir = app_handle
         .path()
         .app_config_dir()
         .map_err(|e| format!("Failed to get config dir: {}", e))?;
     std::fs::create_dir_all(&config_dir).map_err(|e| format!("Failed to create config dir: {}", e))?;
-    let config_path = config_dir.join("hold_to_record.json");
-    let cfg = HoldToRecordConfig { enabled };
-    let content = serde_json::to_string_pretty(&cfg).map_err(|e| format!("Failed to serialize hold-to-record config: {}", e))?;
-    std::fs::write(&config_path, content).map_err(|e| format!("Failed to write hold-to-record config: {}", e))?;
+    let config_path = config_dir.join("inline_timestamps.json");
+    let cfg = InlineTimestampsConfig { enabled };
+    let content = serde_json::to_string_pretty(&cfg).map_err(|e| format!("Failed to serialize inline timestamps config: {}", e))?;
+    std::fs::write(&config_path, content).map_err(|e| format!("Failed to write inline timestamps config: {}", e))?;
     Ok(())
 }
 
+/// Async so that opening the microphone happens off the main thread.
 #[tauri::command]
-pub fn toggle_recording(
-    recorder: tauri::State<Arc<Mutex<RecorderState>>>,
+pub async fn toggle_recording(
+    recorder: tauri::State<'_, Arc<Mutex<RecorderState>>>,
     app_handle: AppHandle,
+    options: Option<TranscriptionOverrides>,
 ) -> Result<String, String> {
     if !is_model_loaded() {
         let _ = app_handle.emit("no-model-selected", true);
         return Err("No model selected. Please select and download a model first.".to_string());
     }
-    let recording_icon = make_recording_icon();
-    let transcribing_icon = make_transcribing_icon();
-    let maybe_wav = {
-        let mut data = Vec::<i16>::new();
-        let mut is_recording = false;
-        if let Ok(mut st) = recorder.lock() {
-            if st.is_recording {
-                st.is_recording = false;
-                std::mem::swap(&mut data, &mut st.buffer);
-                st.start_instant = None;
-            } else {
-                st.is_recording = true;
-                st.buffer.clear();
-                st.start_instant = Some(Instant::now());
-                is_recording = true;
-            }
-        }
-        (data, is_recording)
-    };
+    let options = options.unwrap_or_default();
+    check_model_override(&app_handle, &options)?;
+    let recorder = recorder.inner().clone();
+    let outcome = tauri::async_runtime::spawn_blocking(move || {
+        recording::toggle_recording_with(&app_handle, &recorder, JobSource::Command, options)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    match outcome {
+        ToggleOutcome::Started => Ok("Recording started".to_string()),
+        ToggleOutcome::Stopped => Ok("Recording stopped, transcribing...".to_string()),
+        ToggleOutcome::Blocked => Err("Recording is blocked while the current app is in the foreground".to_string()),
+    }
+}
 
-    if maybe_wav.0.is_empty() {
-        if let Some(tray) = app_handle.tray_by_id("main-tray") {
-            let _ = tray.set_tooltip(Some("Recording…"));
-            let _ = tray.set_icon(Some(recording_icon));
-        }
-        let _ = app_handle.emit("recording-start", true);
-        Ok("Recording started".to_string())
+/// Pause or resume the current recording. Returns the new paused state.
+#[tauri::command]
+pub fn toggle_pause(
+    recorder: tauri::State<Arc<Mutex<RecorderState>>>,
+    app_handle: AppHandle,
+) -> Result<bool, String> {
+    recording::toggle_pause(&app_handle, &recorder).ok_or_else(|| "Not recording".to_string())
+}
+
+/// Keep a time-boxed recording going after its `recording-warning`.
+#[tauri::command]
+pub fn continue_recording(
+    recorder: tauri::State<Arc<Mutex<RecorderState>>>,
+    app_handle: AppHandle,
+) -> Result<(), String> {
+    if recording::continue_recording(&app_handle, &recorder) {
+        Ok(())
     } else {
-        if let Some(tray) = app_handle.tray_by_id("main-tray") {
-            let _ = tray.set_tooltip(Some("Transcribing…"));
-            let _ = tray.set_icon(Some(transcribing_icon));
-        }
-        let _ = app_handle.emit("transcription-start", true);
-        let app2 = app_handle.clone();
-        let sample_rate = recorder
-            .lock()
-            .ok()
-            .map(|s| s.sample_rate_hz)
-            .unwrap_or(16_000);
-        // Save WAV for debugging
-        let _ = save_recording_wav(&app_handle, &maybe_wav.0, sample_rate)
-            .map(|p| println!("Saved recording: {}", p.display()));
-        let _ = app_handle.emit("recording-stop", true);
-        std::thread::spawn(move || {
-            if let Err(err) = transcribe_and_copy(&app2, maybe_wav.0, sample_rate) {
-                eprintln!("transcription error: {err:?}");
-                if let Some(tray) = app2.tray_by_id("main-tray") {
-                    let _ = tray.set_tooltip(Some("Transcription failed"));
-                    let default_icon = Image::from_bytes(include_bytes!("../icons/commander-icon.png")).ok();
-                    if let Some(icon) = default_icon { let _ = tray.set_icon(Some(icon)); }
-                }
-                let _ = app2.emit("transcription-failed", true);
-                let app3 = app2.clone();
-                std::thread::spawn(move || {
-                    std::thread::sleep(std::time::Duration::from_secs(2));
-                    if let Some(tray) = app3.tray_by_id("main-tray") {
-                        let _ = tray.set_tooltip(Some("Commander"));
-                    }
-                });
-            }
-        });
-        Ok("Recording stopped, transcribing...".to_string())
+        Err("No time-boxed recording is running".to_string())
+    }
+}
+
+#[tauri::command]
+pub fn get_media_pause(app_handle: tauri::AppHandle) -> Result<MediaPauseConfig, String> {
+    Ok(read_media_pause_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_media_pause(app_handle: tauri::AppHandle, config: MediaPauseConfig) -> Result<(), String> {
+    write_media_pause_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_time_box(app_handle: tauri::AppHandle) -> Result<TimeBoxConfig, String> {
+    Ok(read_time_box_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_time_box(app_handle: tauri::AppHandle, config: TimeBoxConfig) -> Result<(), String> {
+    if config.enabled && config.stop_after_secs == 0 {
+        return Err("The stop time must be greater than zero".to_string());
+    }
+    write_time_box_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+/// Longest arming delay; beyond this the start of what was said gets lost.
+const MAX_ARMING_DELAY_MS: u64 = 3000;
+
+#[tauri::command]
+pub fn get_arming_delay(app_handle: tauri::AppHandle) -> Result<ArmingDelayConfig, String> {
+    Ok(read_arming_delay_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_arming_delay(app_handle: tauri::AppHandle, config: ArmingDelayConfig) -> Result<(), String> {
+    if config.delay_ms > MAX_ARMING_DELAY_MS {
+        return Err(format!("The arming delay can be at most {} ms", MAX_ARMING_DELAY_MS));
     }
+    write_arming_delay_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_recording_state(recorder: tauri::State<Arc<Mutex<RecorderState>>>) -> Result<audio::RecordingStateInfo, String> {
+    let st = recorder.lock().map_err(|_| "recorder unavailable".to_string())?;
+    Ok(st.state_info())
+}
+
+/// Turn `audio-frame` waveform events on or off for live rendering.
+#[tauri::command]
+pub fn subscribe_audio_frames(enabled: bool) -> Result<(), String> {
+    audio::set_audio_frames_subscribed(enabled);
+    Ok(())
+}
+
+/// Stop the current recording and transcribe it with one-off overrides; saved settings are untouched.
+#[tauri::command]
+pub fn stop_and_transcribe(
+    recorder: tauri::State<Arc<Mutex<RecorderState>>>,
+    app_handle: AppHandle,
+    options: Option<TranscriptionOverrides>,
+) -> Result<(), String> {
+    let options = options.unwrap_or_default();
+    check_model_override(&app_handle, &options)?;
+    if recording::stop_recording_and_transcribe_with(&app_handle, &recorder, JobSource::Command, options) {
+        Ok(())
+    } else {
+        Err("Not recording".to_string())
+    }
+}
+
+fn check_model_override(app_handle: &AppHandle, options: &TranscriptionOverrides) -> Result<(), String> {
+    match options.model.as_deref() {
+        Some(id) if installed_model_path(app_handle, id).is_none() => Err(format!("Model {} is not installed", id)),
+        _ => Ok(()),
+    }
+}
+
+#[tauri::command]
+pub fn get_recording_blocklist(app_handle: tauri::AppHandle) -> Result<Vec<String>, String> {
+    Ok(read_recording_blocklist(&app_handle).apps)
+}
+
+#[tauri::command]
+pub fn save_recording_blocklist(app_handle: tauri::AppHandle, apps: Vec<String>) -> Result<(), String> {
+    let apps = apps
+        .into_iter()
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+        .collect();
+    write_recording_blocklist(&app_handle, &RecordingBlocklistConfig { apps }).map_err(|e| e.to_string())
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -247,8 +471,35 @@ pub struct ModelEntry {
     pub id: String,
     pub name: String,
     pub url: String,
+    /// Alternate download locations tried in order when `url` fails.
+    pub mirrors: Vec<String>,
     pub filename: String,
     pub approx_size_mb: u64,
+    /// SHA-256 of the file as published on Hugging Face; downloads from a mirror must match it.
+    pub sha256: String,
+    /// Alignment heads whisper.cpp uses for DTW word timings with this model.
+    #[serde(skip)]
+    pub dtw_preset: Option<DtwModelPreset>,
+}
+
+impl ModelEntry {
+    /// Primary URL followed by mirrors, in failover order.
+    pub fn download_urls(&self) -> Vec<String> {
+        std::iter::once(self.url.clone()).chain(self.mirrors.iter().cloned()).collect()
+    }
+}
+
+/// Path of an installed catalog model, or `None` if the id is unknown or not downloaded.
+pub(crate) fn installed_model_path(app_handle: &AppHandle, id: &str) -> Option<std::path::PathBuf> {
+    let entry = model_catalog().into_iter().find(|m| m.id == id)?;
+    let path = config::models_dir(app_handle).ok()?.join(entry.filename);
+    path.exists().then_some(path)
+}
+
+/// DTW preset of the catalog model stored at `model_path`, matched by file name.
+pub fn dtw_preset_for_path(model_path: &str) -> Option<DtwModelPreset> {
+    let filename = std::path::Path::new(model_path).file_name()?.to_str()?;
+    model_catalog().into_iter().find(|m| m.filename == filename)?.dtw_preset
 }
 
 fn model_catalog() -> Vec<ModelEntry> {
@@ -257,22 +508,31 @@ fn model_catalog() -> Vec<ModelEntry> {
             id: "large-v3-turbo".to_string(),
             name: "Large v3 Turbo".to_string(),
             url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo.bin?download=true".to_string(),
+            mirrors: vec![
+                "https://hf-mirror.com/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo.bin?download=true".to_string(),
+            ],
             filename: "ggml-large-v3-turbo.bin".to_string(),
             approx_size_mb: 6000,
+            sha256: "1fc70f774d38eb169993ac391eea357ef47c88757ef72ee5943879b7e8e2bc69".to_string(),
+            dtw_preset: Some(DtwModelPreset::LargeV3Turbo),
         },
         ModelEntry {
             id: "large-v3-turbo-q5_0".to_string(),
             name: "Large v3 Turbo (Q5_0)".to_string(),
             url: "https://huggingface.co/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo-q5_0.bin?download=true".to_string(),
+            mirrors: vec![
+                "https://hf-mirror.com/ggerganov/whisper.cpp/resolve/main/ggml-large-v3-turbo-q5_0.bin?download=true".to_string(),
+            ],
             filename: "ggml-large-v3-turbo-q5_0.bin".to_string(),
             approx_size_mb: 3500,
+            sha256: "394221709cd5ad1f40c46e6031ca61bce88931e6e088c188294c6d5a55ffa7e2".to_string(),
+            dtw_preset: Some(DtwModelPreset::LargeV3Turbo),
         },
     ]
 }
 
 fn models_dir(app: &AppHandle) -> AnyResult<std::path::PathBuf> {
-    let dir = app.path().app_data_dir()?;
-    let dir = dir.join("models");
+    let dir = config::models_dir(app)?;
     std::fs::create_dir_all(&dir)?;
     Ok(dir)
 }
@@ -333,6 +593,88 @@ pub fn select_model(app_handle: tauri::AppHandle, id: String) -> Result<(), Stri
     Ok(())
 }
 
+/// Release the loaded model's memory. The selection is kept, so `select_model` or the next
+/// start loads it again. Returns the path of the model that was unloaded.
+#[tauri::command]
+pub fn unload_model() -> Result<Option<String>, String> {
+    http_server::unload_model().map_err(|e| e.to_string())
+}
+
+/// Load the installed model after the selected one (wrapping around). Returns its id, or
+/// `None` when no other model is installed.
+pub(crate) fn select_next_model(app_handle: &AppHandle) -> Result<Option<String>, String> {
+    let installed: Vec<String> = model_catalog()
+        .into_iter()
+        .map(|m| m.id)
+        .filter(|id| installed_model_path(app_handle, id).is_some())
+        .collect();
+    let selected = read_model_config(app_handle).and_then(|c| c.selected_id);
+    let idx = selected.as_ref().and_then(|s| installed.iter().position(|id| id == s)).map(|i| i + 1).unwrap_or(0);
+    let Some(next) = installed.get(idx % installed.len().max(1)).cloned() else { return Ok(None) };
+    if selected.as_ref() == Some(&next) {
+        return Ok(None);
+    }
+    select_model(app_handle.clone(), next.clone())?;
+    Ok(Some(next))
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct ProfileModelEvent {
+    pub profile: String,
+    pub model_id: String,
+    pub error: Option<String>,
+}
+
+/// Bumped by every profile model load; a load that is no longer the latest is skipped or
+/// discarded, so quick profile switches end on the last profile's model.
+static PROFILE_MODEL_GENERATION: AtomicU64 = AtomicU64::new(0);
+/// Profile model loads run one at a time.
+static PROFILE_MODEL_LOAD: Mutex<()> = Mutex::new(());
+
+/// Load the active profile's pinned model in the background, emitting
+/// `profile-model-loading` and then `profile-model-loaded` or `profile-model-error`.
+/// Does nothing when the profile pins no model or it is already loaded; a pinned model that
+/// is not installed is reported as an error and the current model stays.
+pub(crate) fn load_profile_model(app_handle: &AppHandle) {
+    // Any switch, even to a profile that loads nothing, supersedes loads still pending.
+    let generation = PROFILE_MODEL_GENERATION.fetch_add(1, Ordering::SeqCst) + 1;
+    let profile = config::active_profile(app_handle);
+    let Some(model_id) = profile.model_id else { return };
+    let event = |error: Option<String>| ProfileModelEvent { profile: profile.name.clone(), model_id: model_id.clone(), error };
+    let Some(path) = installed_model_path(app_handle, &model_id) else {
+        let _ = app_handle.emit("profile-model-error", event(Some("Model not installed".to_string())));
+        return;
+    };
+    let path = path.to_string_lossy().to_string();
+    let loaded = event(None);
+    let app_handle = app_handle.clone();
+    let is_latest = move || PROFILE_MODEL_GENERATION.load(Ordering::SeqCst) == generation;
+    std::thread::spawn(move || {
+        // Checked once earlier loads are done, since one of them may change the current model.
+        let _serial = PROFILE_MODEL_LOAD.lock().unwrap_or_else(|e| e.into_inner());
+        if !is_latest() {
+            info!("Skipping the load of {} for profile {}; a newer switch is pending", loaded.model_id, loaded.profile);
+            return;
+        }
+        if current_service().is_some_and(|s| s.backend_info().model_path == path) {
+            return;
+        }
+        let _ = app_handle.emit("profile-model-loading", loaded.clone());
+        if let Err(e) = load_model(path) {
+            let _ = app_handle.emit("profile-model-error", ProfileModelEvent { error: Some(format!("Failed to load model: {}", e)), ..loaded });
+            return;
+        }
+        // The next load replaces this model anyway, so keep the selection for it.
+        if !is_latest() { return; }
+        let mut cfg = read_model_config(&app_handle).unwrap_or_default();
+        cfg.selected_id = Some(loaded.model_id.clone());
+        if let Err(e) = write_model_config(&app_handle, &cfg) {
+            warn!("Failed to save model selection: {}", e);
+        }
+        let _ = app_handle.emit("profile-model-loaded", loaded);
+    });
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct DownloadProgress {
     pub id: String,
@@ -354,39 +696,111 @@ pub fn download_model(app_handle: tauri::AppHandle, id: String) -> Result<(), St
         let id_clone = id.clone();
         rt.block_on(async move {
             let client = reqwest::Client::new();
-            let resp = match client.get(&m.url).send().await { Ok(r) => r, Err(e) => { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; } };
-            let total = resp.content_length();
-            let _ = app_handle.emit("model-download-start", serde_json::json!({"id": id_clone, "total_bytes": total}));
-            let mut stream = resp.bytes_stream();
-            let mut file = match std::fs::File::create(&partial_path) { Ok(f) => f, Err(e) => { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; } };
-            let mut received: u64 = 0;
-            use futures_util::StreamExt;
-            while let Some(chunk) = stream.next().await {
-                match chunk {
-                    Ok(bytes) => {
-                        if let Err(e) = file.write_all(&bytes) { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; }
-                        received += bytes.len() as u64;
-                        let _ = app_handle.emit("model-download-progress", serde_json::json!({"id": id_clone, "received_bytes": received, "total_bytes": total}));
+            let mut downloaded_from: Option<String> = None;
+            let mut last_error = String::new();
+            for url in m.download_urls() {
+                // Mirrors are third parties, so what they serve has to match the catalog checksum.
+                let downloaded = download_to_file(&client, &app_handle, &id_clone, &url, &partial_path)
+                    .await
+                    .and_then(|digest| match url == m.url || digest.eq_ignore_ascii_case(&m.sha256) {
+                        true => Ok(()),
+                        false => Err(format!("checksum mismatch: expected {}, got {}", m.sha256, digest)),
+                    });
+                match downloaded {
+                    Ok(()) => { downloaded_from = Some(url); break; }
+                    Err(e) => {
+                        warn!("Model download from {} failed: {}", url, e);
+                        let _ = app_handle.emit("model-download-mirror-failed", serde_json::json!({"id": id_clone, "url": url, "error": e}));
+                        last_error = e;
                     }
-                    Err(e) => { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; }
                 }
             }
-            if let Err(e) = file.flush() { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; }
+            let Some(url) = downloaded_from else {
+                let _ = std::fs::remove_file(&partial_path);
+                let _ = app_handle.emit("model-download-error", last_error);
+                return;
+            };
             if let Err(e) = std::fs::rename(&partial_path, &final_path) { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; }
             // Auto-select and load
             if let Err(e) = load_model(final_path.to_string_lossy().to_string()) { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; }
             let mut cfg = read_model_config(&app_handle).unwrap_or_default();
             cfg.selected_id = Some(id_clone.clone());
             if let Err(e) = write_model_config(&app_handle, &cfg) { let _ = app_handle.emit("model-download-error", format!("{}", e)); return; }
-            let _ = app_handle.emit("model-download-complete", serde_json::json!({"id": id_clone, "selected": true}));
+            let _ = app_handle.emit("model-download-complete", serde_json::json!({"id": id_clone, "selected": true, "url": url}));
         });
     });
     Ok(())
 }
 
+/// Stream one URL into `partial_path`, emitting start/progress events tagged with the URL.
+/// Returns the SHA-256 of what was written, as lowercase hex.
+async fn download_to_file(
+    client: &reqwest::Client,
+    app_handle: &AppHandle,
+    id: &str,
+    url: &str,
+    partial_path: &std::path::Path,
+) -> Result<String, String> {
+    use sha2::Digest;
+    let resp = client.get(url).send().await.map_err(|e| e.to_string())?;
+    if !resp.status().is_success() {
+        return Err(format!("HTTP {}", resp.status()));
+    }
+    let total = resp.content_length();
+    let _ = app_handle.emit("model-download-start", serde_json::json!({"id": id, "total_bytes": total, "url": url}));
+    let mut stream = resp.bytes_stream();
+    let mut file = std::fs::File::create(partial_path).map_err(|e| e.to_string())?;
+    let mut received: u64 = 0;
+    let mut hasher = sha2::Sha256::new();
+    use futures_util::StreamExt;
+    while let Some(chunk) = stream.next().await {
+        let bytes = chunk.map_err(|e| e.to_string())?;
+        file.write_all(&bytes).map_err(|e| e.to_string())?;
+        hasher.update(&bytes);
+        received += bytes.len() as u64;
+        let _ = app_handle.emit("model-download-progress", serde_json::json!({"id": id, "received_bytes": received, "total_bytes": total, "url": url}));
+    }
+    file.flush().map_err(|e| e.to_string())?;
+    Ok(hasher.finalize().iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+#[tauri::command]
+pub fn list_audio_input_devices() -> Result<Vec<AudioInputDevice>, String> {
+    audio::list_audio_input_devices().map_err(|e| e.to_string())
+}
+
+/// Stream formats the device (or the system default input) supports.
+#[tauri::command]
+pub fn list_input_stream_formats(device_name: Option<String>) -> Result<Vec<audio::SupportedInputFormat>, String> {
+    audio::supported_input_formats(device_name.as_deref()).map_err(|e| e.to_string())
+}
+
+/// Per-device rates, channel counts and default format for the input settings.
+#[tauri::command]
+pub fn list_input_device_capabilities() -> Result<Vec<audio::InputDeviceCapabilities>, String> {
+    audio::input_device_capabilities().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
-pub fn list_audio_input_devices() -> Result<Vec<String>, String> {
-    list_input_device_names().map_err(|e| e.to_string())
+pub fn get_input_formats(app_handle: tauri::AppHandle) -> Result<InputFormatsConfig, String> {
+    Ok(read_input_formats_config(&app_handle))
+}
+
+/// Save (or clear, with `None`) the stream format for one device and reopen the input.
+#[tauri::command]
+pub fn save_input_format(
+    app_handle: tauri::AppHandle,
+    controller: tauri::State<AudioController>,
+    device_name: String,
+    format: Option<InputFormat>,
+) -> Result<(), String> {
+    let mut cfg = read_input_formats_config(&app_handle);
+    match format {
+        Some(f) => { cfg.devices.insert(device_name, f); }
+        None => { cfg.devices.remove(&device_name); }
+    }
+    write_input_formats_config(&app_handle, &cfg).map_err(|e| e.to_string())?;
+    controller.set_devices(config::input_device_candidates(&app_handle)).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -396,15 +810,43 @@ pub fn get_selected_audio_input_device(app_handle: tauri::AppHandle) -> Result<O
 
 #[tauri::command]
 pub fn save_selected_audio_input_device(app_handle: tauri::AppHandle, name: Option<String>) -> Result<(), String> {
-    let cfg = AudioInputConfig { selected_device_name: name };
+    let mut cfg = read_audio_input_config(&app_handle).unwrap_or_default();
+    cfg.selected_device_name = name;
     write_audio_input_config(&app_handle, &cfg).map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub fn get_fallback_audio_input_devices(app_handle: tauri::AppHandle) -> Result<Vec<String>, String> {
+    Ok(read_audio_input_config(&app_handle).map(|c| c.fallback_device_names).unwrap_or_default())
+}
+
+/// Save the ordered devices to try when the selected one is not connected, and reopen the input.
+#[tauri::command]
+pub fn save_fallback_audio_input_devices(app_handle: tauri::AppHandle, controller: tauri::State<AudioController>, names: Vec<String>) -> Result<(), String> {
+    let mut cfg = read_audio_input_config(&app_handle).unwrap_or_default();
+    cfg.fallback_device_names = names;
+    write_audio_input_config(&app_handle, &cfg).map_err(|e| e.to_string())?;
+    controller.set_devices(cfg.device_candidates()).map_err(|e| e.to_string())
+}
+
+
+#[tauri::command]
+pub fn get_follow_system_default(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    Ok(read_audio_input_config(&app_handle).is_some_and(|c| c.follow_system_default))
+}
+
+/// Switch between following the system default input and the selected device list, and reopen the input.
+#[tauri::command]
+pub fn save_follow_system_default(app_handle: tauri::AppHandle, controller: tauri::State<AudioController>, enabled: bool) -> Result<(), String> {
+    let mut cfg = read_audio_input_config(&app_handle).unwrap_or_default();
+    cfg.follow_system_default = enabled;
+    write_audio_input_config(&app_handle, &cfg).map_err(|e| e.to_string())?;
+    controller.set_devices(cfg.device_candidates()).map_err(|e| e.to_string())
+}
 
 #[tauri::command]
 pub fn apply_selected_audio_input_device(app_handle: tauri::AppHandle, controller: tauri::State<AudioController>) -> Result<(), String> {
-    let selected = read_audio_input_config(&app_handle).and_then(|c| c.selected_device_name);
-    controller.set_device(selected).map_err(|e| e.to_string())
+    controller.set_devices(config::input_device_candidates(&app_handle)).map_err(|e| e.to_string())
 }
 
 
@@ -421,3 +863,739 @@ pub fn open_accessibility_settings() -> Result<(), String> {
     Ok(())
 }
 
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn is_microphone_authorized() -> Result<bool, String> {
+    Ok(platform::microphone_authorization() == platform::MicrophoneAuthorization::Authorized)
+}
+
+/// Full microphone authorization state, so the UI can tell an unanswered prompt from a denial.
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub fn get_microphone_status() -> Result<platform::MicrophoneAuthorization, String> {
+    Ok(platform::microphone_authorization())
+}
+
+/// Show the system microphone prompt unless the user already answered it, then report the
+/// outcome as a `microphone-status` event. Once denied, access can only be granted in System Settings.
+#[cfg(target_os = "macos")]
+#[tauri::command]
+pub async fn request_microphone_access(app_handle: tauri::AppHandle) -> Result<bool, String> {
+    let answer = platform::request_microphone_access();
+    let granted = tauri::async_runtime::spawn_blocking(move || answer.recv().unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())?;
+    let status = platform::microphone_authorization();
+    let _ = app_handle.emit("microphone-status", serde_json::json!({"status": status, "authorized": granted}));
+    Ok(granted)
+}
+
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct StorageLocations {
+    pub config: StorageConfig,
+    pub temp_dir: String,
+    pub recordings_dir: String,
+    pub models_dir: String,
+}
+
+#[tauri::command]
+pub fn get_storage_locations(app_handle: tauri::AppHandle) -> Result<StorageLocations, String> {
+    Ok(StorageLocations {
+        config: read_storage_config(&app_handle),
+        temp_dir: config::temp_dir(&app_handle).to_string_lossy().to_string(),
+        recordings_dir: config::recordings_dir(&app_handle).map_err(|e| e.to_string())?.to_string_lossy().to_string(),
+        models_dir: config::models_dir(&app_handle).map_err(|e| e.to_string())?.to_string_lossy().to_string(),
+    })
+}
+
+#[tauri::command]
+pub fn save_storage_locations(app_handle: tauri::AppHandle, storage: StorageConfig) -> Result<(), String> {
+    let old_models = config::models_dir(&app_handle).map_err(|e| e.to_string())?;
+    for dir in [&storage.temp_dir, &storage.recordings_dir, &storage.models_dir].into_iter().flatten() {
+        if !dir.trim().is_empty() {
+            std::fs::create_dir_all(dir.trim()).map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+        }
+    }
+    write_storage_config(&app_handle, &storage).map_err(|e| e.to_string())?;
+    let new_models = config::models_dir(&app_handle).map_err(|e| e.to_string())?;
+    if old_models != new_models && old_models.exists() {
+        let moved = migrate_model_files(&old_models, &new_models).map_err(|e| format!("Failed to migrate models: {}", e))?;
+        let _ = app_handle.emit("models-migrated", serde_json::json!({
+            "from": old_models.to_string_lossy(),
+            "to": new_models.to_string_lossy(),
+            "files": moved,
+        }));
+        http_server::models_moved(&old_models, &new_models);
+    }
+    Ok(())
+}
+
+/// Move downloaded model files into a new directory, copying across volumes when rename fails.
+fn migrate_model_files(from: &std::path::Path, to: &std::path::Path) -> AnyResult<Vec<String>> {
+    std::fs::create_dir_all(to)?;
+    let mut moved = Vec::new();
+    for m in model_catalog() {
+        let src = from.join(&m.filename);
+        let dst = to.join(&m.filename);
+        if !src.exists() || dst.exists() { continue; }
+        if std::fs::rename(&src, &dst).is_err() {
+            let partial = to.join(format!("{}.partial", &m.filename));
+            std::fs::copy(&src, &partial)?;
+            std::fs::rename(&partial, &dst)?;
+            std::fs::remove_file(&src)?;
+        }
+        moved.push(m.filename);
+    }
+    Ok(moved)
+}
+
+#[tauri::command]
+pub fn get_recent_http_requests() -> Result<Vec<AccessLogEntry>, String> {
+    Ok(recent_requests())
+}
+
+#[tauri::command]
+pub fn get_model_idle_config(app_handle: tauri::AppHandle) -> Result<ModelIdleConfig, String> {
+    Ok(read_model_idle_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_model_idle_config(app_handle: tauri::AppHandle, config: ModelIdleConfig) -> Result<(), String> {
+    write_model_idle_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    set_idle_unload(config);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_inference_config(app_handle: tauri::AppHandle) -> Result<InferenceConfig, String> {
+    Ok(read_inference_config(&app_handle))
+}
+
+/// Logical CPUs on this machine, for choosing a thread count.
+#[tauri::command]
+pub fn get_cpu_count() -> usize {
+    cpu_count()
+}
+
+/// Save the inference options; the loaded model is reloaded when an option it was loaded with changed.
+#[tauri::command]
+pub async fn save_inference_config(app_handle: tauri::AppHandle, config: InferenceConfig) -> Result<(), String> {
+    if config.threads == Some(0) {
+        return Err("threads must be at least 1".into());
+    }
+    let previous = read_inference_config(&app_handle);
+    write_inference_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    set_inference(&config);
+    if previous.use_gpu == config.use_gpu && previous.flash_attn == config.flash_attn && previous.dtw_timestamps == config.dtw_timestamps {
+        return Ok(());
+    }
+    tauri::async_runtime::spawn_blocking(http_server::reload_model)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_rate_limit_config(app_handle: tauri::AppHandle) -> Result<RateLimitConfig, String> {
+    Ok(read_rate_limit_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_rate_limit_config(app_handle: tauri::AppHandle, config: RateLimitConfig) -> Result<(), String> {
+    write_rate_limit_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    set_rate_limit(config);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_whisper_params(app_handle: tauri::AppHandle) -> Result<WhisperParamsConfig, String> {
+    Ok(read_whisper_params_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_whisper_params(app_handle: tauri::AppHandle, params: WhisperParamsConfig) -> Result<(), String> {
+    write_whisper_params_config(&app_handle, &params).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_decoding_config(app_handle: tauri::AppHandle) -> Result<DecodingConfig, String> {
+    Ok(read_decoding_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_decoding_config(app_handle: tauri::AppHandle, config: DecodingConfig) -> Result<(), String> {
+    if !(1..=MAX_DECODING_CANDIDATES).contains(&config.beam_size) || !(1..=MAX_DECODING_CANDIDATES).contains(&config.best_of) {
+        return Err(format!("Beam size and best-of must be between 1 and {}", MAX_DECODING_CANDIDATES));
+    }
+    if !(0.0..=1.0).contains(&config.temperature) {
+        return Err("Temperature must be between 0 and 1".to_string());
+    }
+    write_decoding_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn transcribe_saved_recording(app_handle: tauri::AppHandle, filename: String) -> Result<(), String> {
+    if !is_model_loaded() {
+        let _ = app_handle.emit("no-model-selected", true);
+        return Err("No model selected. Please select and download a model first.".to_string());
+    }
+    recording::transcribe_saved_recording(&app_handle, &filename).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_recordings(app_handle: tauri::AppHandle) -> Result<Vec<RecordingInfo>, String> {
+    audio::list_recordings(&app_handle).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn delete_recording(app_handle: tauri::AppHandle, filename: String) -> Result<(), String> {
+    let path = audio::recording_path(&app_handle, &filename).map_err(|e| e.to_string())?;
+    std::fs::remove_file(&path).map_err(|e| format!("Failed to delete recording: {}", e))
+}
+
+#[tauri::command]
+pub fn get_recording_retention(app_handle: tauri::AppHandle) -> Result<RecordingRetentionConfig, String> {
+    Ok(read_recording_retention_config(&app_handle))
+}
+
+/// Save the retention settings and apply the new limits right away. Returns how many
+/// recordings were deleted.
+#[tauri::command]
+pub fn save_recording_retention(app_handle: tauri::AppHandle, config: RecordingRetentionConfig) -> Result<usize, String> {
+    write_recording_retention_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    audio::sweep_recordings(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn open_recordings_dir(app_handle: tauri::AppHandle) -> Result<(), String> {
+    let dir = config::recordings_dir(&app_handle).map_err(|e| e.to_string())?;
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    platform::open_in_finder(&dir).map_err(|e| format!("Failed to open recordings folder: {}", e))
+}
+
+#[tauri::command]
+pub fn get_companion_status(app_handle: tauri::AppHandle) -> Result<CompanionStatus, String> {
+    Ok(companion::companion_status(&app_handle))
+}
+
+/// Turn the phone companion listener on or off; the returned status carries the pairing QR code.
+#[tauri::command]
+pub async fn set_companion_enabled(app_handle: tauri::AppHandle, enabled: bool) -> Result<CompanionStatus, String> {
+    let mut cfg = read_companion_config(&app_handle);
+    cfg.enabled = enabled;
+    write_companion_config(&app_handle, &cfg).map_err(|e| e.to_string())?;
+    // Restarting waits for the old listener to release its port, so keep it off the main thread.
+    let app = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || companion::apply_companion_config(&app))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(companion::companion_status(&app_handle))
+}
+
+/// Issue a new pairing token, unpairing every phone.
+#[tauri::command]
+pub async fn reset_companion_pairing(app_handle: tauri::AppHandle) -> Result<CompanionStatus, String> {
+    let app = app_handle.clone();
+    tauri::async_runtime::spawn_blocking(move || companion::reset_pairing(&app))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+    Ok(companion::companion_status(&app_handle))
+}
+
+/// Delete all saved recordings. Returns how many were deleted.
+#[tauri::command]
+pub fn clear_recordings(app_handle: tauri::AppHandle) -> Result<usize, String> {
+    audio::clear_recordings(&app_handle).map_err(|e| format!("Failed to clear recordings: {}", e))
+}
+
+#[tauri::command]
+pub fn play_recording(app_handle: tauri::AppHandle, filename: String) -> Result<(), String> {
+    let path = audio::recording_path(&app_handle, &filename).map_err(|e| e.to_string())?;
+    let (samples, sample_rate) = audio::read_recording_mono(&path).map_err(|e| e.to_string())?;
+    audio::play_samples(samples, sample_rate).map_err(|e| e.to_string())
+}
+
+/// Buckets in the waveform returned by `test_microphone`.
+const MIC_TEST_WAVEFORM_BUCKETS: usize = 200;
+
+/// Record a few seconds from the current input, then play it back and return its waveform so
+/// users can check the device before dictating.
+#[tauri::command]
+pub async fn test_microphone(
+    recorder: tauri::State<'_, Arc<Mutex<RecorderState>>>,
+    controller: tauri::State<'_, AudioController>,
+    duration_ms: Option<u64>,
+    playback: Option<bool>,
+) -> Result<audio::MicTestResult, String> {
+    {
+        let mut st = recorder.lock().map_err(|_| "recorder unavailable".to_string())?;
+        if st.is_recording {
+            return Err("Stop recording before testing the microphone".to_string());
+        }
+        st.mic_test = Some(Vec::new());
+    }
+    if controller.acquire_async().await {
+        tokio::time::sleep(std::time::Duration::from_millis(duration_ms.unwrap_or(3000).clamp(1000, 10_000))).await;
+    }
+    let (samples, sample_rate_hz, device) = {
+        let mut st = recorder.lock().map_err(|_| "recorder unavailable".to_string())?;
+        (st.mic_test.take().unwrap_or_default(), st.sample_rate_hz, st.input_device_name.clone())
+    };
+    controller.release();
+    if samples.is_empty() {
+        return Err("No audio received from the input device".to_string());
+    }
+    let result = audio::MicTestResult {
+        device,
+        duration_ms: samples.len() as u64 * 1000 / sample_rate_hz.max(1) as u64,
+        sample_rate_hz,
+        levels: audio::level_stats(&samples),
+        waveform: audio::waveform_peaks(&samples, MIC_TEST_WAVEFORM_BUCKETS),
+    };
+    if playback.unwrap_or(true) {
+        audio::play_samples(samples, sample_rate_hz).map_err(|e| e.to_string())?;
+    }
+    Ok(result)
+}
+
+#[tauri::command]
+pub fn replay_last_recording(recorder: tauri::State<Arc<Mutex<RecorderState>>>) -> Result<(), String> {
+    let last = recorder
+        .lock()
+        .map_err(|_| "recorder unavailable".to_string())?
+        .last_capture
+        .clone()
+        .ok_or_else(|| "Nothing has been recorded yet".to_string())?;
+    audio::play_samples(last.samples, last.sample_rate_hz).map_err(|e| e.to_string())
+}
+
+/// Suggest likely languages for the last capture so the UI can flag a wrong default.
+#[tauri::command]
+pub async fn detect_language_from_last_recording(recorder: tauri::State<'_, Arc<Mutex<RecorderState>>>) -> Result<Vec<LanguageProbability>, String> {
+    let last = recorder
+        .lock()
+        .map_err(|_| "recorder unavailable".to_string())?
+        .last_capture
+        .clone()
+        .ok_or_else(|| "Nothing has been recorded yet".to_string())?;
+    let service = current_service().ok_or_else(|| "No model loaded".to_string())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let audio_16k = resample_linear(&last.samples, last.sample_rate_hz, 16_000);
+        service.detect_language(&audio_16k, 3)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+    .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_append_mode(app_handle: tauri::AppHandle) -> Result<AppendModeConfig, String> {
+    Ok(read_append_mode_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_append_mode(app_handle: tauri::AppHandle, config: AppendModeConfig) -> Result<(), String> {
+    write_append_mode_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn flush_append_buffer(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
+    transcription::flush_append_buffer(&app_handle).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_transcription_jobs() -> Result<Vec<TranscriptionJob>, String> {
+    Ok(jobs::recent_jobs())
+}
+
+#[tauri::command]
+pub fn get_transcription_job(id: u64) -> Result<Option<TranscriptionJob>, String> {
+    Ok(jobs::get_job(id))
+}
+
+#[tauri::command]
+pub fn get_profiles(app_handle: tauri::AppHandle) -> Result<ProfilesConfig, String> {
+    Ok(read_profiles_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_profiles(app_handle: tauri::AppHandle, config: ProfilesConfig) -> Result<(), String> {
+    if config.profiles.is_empty() {
+        return Err("At least one profile is required".to_string());
+    }
+    write_profiles_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+/// Run any tray/shortcut action by name, e.g. `next-model` or `open-history`, so keyboard
+/// and voice-control tools can drive everything the tray menu offers.
+#[tauri::command]
+pub fn run_action(
+    app_handle: tauri::AppHandle,
+    recorder: tauri::State<Arc<Mutex<RecorderState>>>,
+    action: ShortcutAction,
+    profile: Option<String>,
+) -> Result<(), String> {
+    actions::run_action(&app_handle, &recorder, action, profile.as_deref(), JobSource::Command);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_spoken_feedback(app_handle: tauri::AppHandle) -> Result<SpokenFeedbackConfig, String> {
+    Ok(read_spoken_feedback_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_spoken_feedback(app_handle: tauri::AppHandle, config: SpokenFeedbackConfig) -> Result<(), String> {
+    write_spoken_feedback_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn set_active_profile(app_handle: tauri::AppHandle, name: String) -> Result<(), String> {
+    let name = config::switch_profile(&app_handle, Some(&name)).map_err(|e| e.to_string())?;
+    let _ = app_handle.emit("profile-changed", serde_json::json!({ "name": name }));
+    load_profile_model(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_shortcut_bindings(app_handle: tauri::AppHandle) -> Result<ShortcutBindingsConfig, String> {
+    Ok(read_shortcut_bindings(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_shortcut_bindings(app_handle: tauri::AppHandle, config: ShortcutBindingsConfig) -> Result<(), String> {
+    for binding in &config.bindings {
+        binding.to_shortcut().map_err(|e| e.to_string())?;
+    }
+    write_shortcut_bindings(&app_handle, &config).map_err(|e| e.to_string())?;
+    shortcuts::register_shortcuts(&app_handle);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn run_startup_health_check(app_handle: tauri::AppHandle) -> Result<StartupHealth, String> {
+    Ok(report_health(&app_handle))
+}
+
+#[tauri::command]
+pub fn get_model_perf_stats(app_handle: tauri::AppHandle) -> Result<HashMap<String, ModelPerfStats>, String> {
+    Ok(read_perf_stats(&app_handle).models)
+}
+
+#[tauri::command]
+pub fn get_latency_budget(app_handle: tauri::AppHandle) -> Result<LatencyBudgetConfig, String> {
+    Ok(read_latency_budget_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_latency_budget(app_handle: tauri::AppHandle, config: LatencyBudgetConfig) -> Result<(), String> {
+    if config.target_ms == 0 {
+        return Err("Latency target must be greater than zero".to_string());
+    }
+    write_latency_budget_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn reset_model_perf_stats(app_handle: tauri::AppHandle, model_id: Option<String>) -> Result<(), String> {
+    reset_perf_stats(&app_handle, model_id.as_deref()).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_meeting_mode(app_handle: tauri::AppHandle) -> Result<MeetingModeConfig, String> {
+    Ok(read_meeting_mode_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_meeting_mode(app_handle: tauri::AppHandle, controller: tauri::State<AudioController>, config: MeetingModeConfig) -> Result<(), String> {
+    write_meeting_mode_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    controller.set_loopback_device(config.active_loopback_device()).map_err(|e| e.to_string())
+}
+
+/// Measure the ambient level of the current input device and store it as its noise profile.
+/// Stay quiet while it runs.
+#[tauri::command]
+pub async fn calibrate_noise_floor(app_handle: tauri::AppHandle, recorder: tauri::State<'_, Arc<Mutex<RecorderState>>>, controller: tauri::State<'_, AudioController>, duration_ms: Option<u64>) -> Result<NoiseProfile, String> {
+    {
+        let mut st = recorder.lock().map_err(|_| "recorder unavailable".to_string())?;
+        if st.is_recording {
+            return Err("Stop recording before calibrating".to_string());
+        }
+        st.calibration_levels = Some(Vec::new());
+    }
+    if controller.acquire_async().await {
+        tokio::time::sleep(std::time::Duration::from_millis(duration_ms.unwrap_or(3000).clamp(500, 10_000))).await;
+    }
+    // Read while the input is still open, so this is the device that was measured.
+    let device = config::current_input_device_name(&app_handle);
+    let mut levels = recorder
+        .lock()
+        .map_err(|_| "recorder unavailable".to_string())?
+        .calibration_levels
+        .take()
+        .unwrap_or_default();
+    controller.release();
+    let device = device.ok_or_else(|| "No input device".to_string())?;
+    if levels.is_empty() {
+        return Err("No audio received from the input device".to_string());
+    }
+    // Median rather than mean, so a cough or a door does not skew the floor.
+    levels.sort_by(|a, b| a.total_cmp(b));
+    let profile = NoiseProfile {
+        noise_floor_db: levels[levels.len() / 2],
+        calibrated_at_ms: std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis(),
+    };
+    let mut profiles = read_noise_profiles(&app_handle);
+    profiles.devices.insert(device.clone(), profile.clone());
+    write_noise_profiles(&app_handle, &profiles).map_err(|e| e.to_string())?;
+    processing::configure(&app_handle);
+    let _ = app_handle.emit("noise-floor-calibrated", serde_json::json!({ "device": device, "noise_floor_db": profile.noise_floor_db }));
+    Ok(profile)
+}
+
+#[tauri::command]
+pub fn get_noise_profiles(app_handle: tauri::AppHandle) -> Result<NoiseProfilesConfig, String> {
+    Ok(read_noise_profiles(&app_handle))
+}
+
+#[tauri::command]
+pub fn get_auto_stop(app_handle: tauri::AppHandle) -> Result<AutoStopConfig, String> {
+    Ok(read_auto_stop_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_auto_stop(app_handle: tauri::AppHandle, config: AutoStopConfig) -> Result<(), String> {
+    write_auto_stop_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_memory_usage(recorder: tauri::State<Arc<Mutex<RecorderState>>>) -> Result<RecorderMemoryUsage, String> {
+    let st = recorder.lock().map_err(|_| "recorder unavailable".to_string())?;
+    Ok(st.memory_usage())
+}
+
+#[tauri::command]
+pub fn get_audio_levels(app_handle: tauri::AppHandle) -> Result<AudioLevelsConfig, String> {
+    Ok(read_audio_levels_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_audio_levels(app_handle: tauri::AppHandle, config: AudioLevelsConfig) -> Result<(), String> {
+    if config.spectrum_bands > audio::MAX_SPECTRUM_BANDS {
+        return Err(format!("At most {} spectrum bands are supported", audio::MAX_SPECTRUM_BANDS));
+    }
+    write_audio_levels_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    audio::set_audio_level_options(config.interval_ms, config.spectrum_bands);
+    Ok(())
+}
+
+/// Largest boost or cut the gain stage accepts.
+const MAX_GAIN_DB: f32 = 24.0;
+/// High-pass cutoffs outside this range either do nothing or start eating into speech.
+const HIGH_PASS_CUTOFF_RANGE_HZ: std::ops::RangeInclusive<f32> = 20.0..=300.0;
+
+#[tauri::command]
+pub fn get_audio_pipeline(app_handle: tauri::AppHandle) -> Result<AudioPipelineConfig, String> {
+    Ok(read_audio_pipeline_config(&app_handle))
+}
+
+/// Save the stage order and settings; open streams switch to them on their next callback.
+#[tauri::command]
+pub fn save_audio_pipeline(app_handle: tauri::AppHandle, controller: tauri::State<AudioController>, config: AudioPipelineConfig) -> Result<(), String> {
+    for kind in AudioStageKind::ALL {
+        if config.stages.iter().filter(|s| s.kind == kind).count() > 1 {
+            return Err(format!("The {:?} stage is listed more than once", kind));
+        }
+    }
+    if config.stages.iter().any(|s| !s.gain_db.is_finite() || s.gain_db.abs() > MAX_GAIN_DB) {
+        return Err(format!("Gain must be between -{0} and {0} dB", MAX_GAIN_DB));
+    }
+    // Other stages ignore the cutoff, so whatever they carry is not an error.
+    if config.stages.iter().any(|s| s.kind == AudioStageKind::HighPass && !HIGH_PASS_CUTOFF_RANGE_HZ.contains(&s.cutoff_hz)) {
+        return Err(format!("High-pass cutoff must be between {} and {} Hz", HIGH_PASS_CUTOFF_RANGE_HZ.start(), HIGH_PASS_CUTOFF_RANGE_HZ.end()));
+    }
+    let denoise_was_enabled = processing::denoise_enabled();
+    write_audio_pipeline_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    processing::configure(&app_handle);
+    if processing::denoise_enabled() == denoise_was_enabled {
+        return Ok(());
+    }
+    // The preferred capture rate depends on noise suppression, so reopen the input.
+    controller.set_devices(config::input_device_candidates(&app_handle)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_recording_limit(app_handle: tauri::AppHandle) -> Result<RecordingLimitConfig, String> {
+    Ok(read_recording_limit_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_recording_limit(app_handle: tauri::AppHandle, config: RecordingLimitConfig) -> Result<(), String> {
+    if config.max_duration_secs == 0 {
+        return Err("Maximum recording duration must be at least one second".to_string());
+    }
+    write_recording_limit_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_paste_review_config(app_handle: tauri::AppHandle) -> Result<PasteReviewConfig, String> {
+    Ok(read_paste_review_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_paste_review_config(app_handle: tauri::AppHandle, config: PasteReviewConfig) -> Result<(), String> {
+    write_paste_review_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_pending_paste_review(app_handle: tauri::AppHandle) -> Result<Option<String>, String> {
+    Ok(sinks::pending_paste_review(&app_handle))
+}
+
+#[tauri::command]
+pub fn confirm_paste_review(app_handle: tauri::AppHandle, text: String) -> Result<(), String> {
+    sinks::finish_paste_review(&app_handle, Some(text)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn discard_paste_review(app_handle: tauri::AppHandle) -> Result<(), String> {
+    sinks::finish_paste_review(&app_handle, None).map_err(|e| e.to_string())
+}
+
+/// Longest retroactive window; a minute at 48 kHz is about 11 MB.
+const MAX_RETROACTIVE_SECS: u64 = 120;
+
+#[tauri::command]
+pub fn get_retroactive(app_handle: tauri::AppHandle) -> Result<RetroactiveConfig, String> {
+    Ok(read_retroactive_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_retroactive(
+    app_handle: tauri::AppHandle,
+    recorder: tauri::State<Arc<Mutex<RecorderState>>>,
+    controller: tauri::State<AudioController>,
+    config: RetroactiveConfig,
+) -> Result<(), String> {
+    if config.duration_secs == 0 || config.duration_secs > MAX_RETROACTIVE_SECS {
+        return Err(format!("The retroactive window must be between 1 and {} seconds", MAX_RETROACTIVE_SECS));
+    }
+    write_retroactive_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    recorder.lock().map_err(|_| "recorder unavailable".to_string())?.set_retroactive_ms(config.effective_ms());
+    // Like pre-roll, the buffer keeps the microphone open while idle.
+    controller.set_devices(config::input_device_candidates(&app_handle)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_pre_roll(app_handle: tauri::AppHandle) -> Result<PreRollConfig, String> {
+    Ok(read_pre_roll_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_pre_roll(
+    app_handle: tauri::AppHandle,
+    recorder: tauri::State<Arc<Mutex<RecorderState>>>,
+    controller: tauri::State<AudioController>,
+    config: PreRollConfig,
+) -> Result<(), String> {
+    write_pre_roll_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    recorder.lock().map_err(|_| "recorder unavailable".to_string())?.set_pre_roll_ms(config.effective_ms());
+    // Pre-roll keeps the microphone open while idle, so the input opens or closes with it.
+    controller.set_devices(config::input_device_candidates(&app_handle)).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_watch_folder(app_handle: tauri::AppHandle) -> Result<WatchFolderConfig, String> {
+    Ok(read_watch_folder_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_watch_folder(app_handle: tauri::AppHandle, config: WatchFolderConfig) -> Result<(), String> {
+    if let Some(dir) = config.directory.as_deref() {
+        if config.enabled && !std::path::Path::new(dir).is_dir() {
+            return Err(format!("Not a folder: {}", dir));
+        }
+    }
+    write_watch_folder_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+/// Turn the watch folder on or off without touching the rest of its settings.
+#[tauri::command]
+pub fn set_watch_folder_enabled(app_handle: tauri::AppHandle, enabled: bool) -> Result<WatchFolderConfig, String> {
+    let mut config = read_watch_folder_config(&app_handle);
+    if enabled && config.directory.is_none() {
+        return Err("Choose a folder to watch first".to_string());
+    }
+    config.enabled = enabled;
+    write_watch_folder_config(&app_handle, &config).map_err(|e| e.to_string())?;
+    Ok(config)
+}
+
+#[tauri::command]
+pub fn get_quality_guard(app_handle: tauri::AppHandle) -> Result<QualityGuardConfig, String> {
+    Ok(read_quality_guard_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_quality_guard(app_handle: tauri::AppHandle, config: QualityGuardConfig) -> Result<(), String> {
+    write_quality_guard_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn list_phrases(app_handle: tauri::AppHandle) -> Result<Vec<config::Phrase>, String> {
+    Ok(phrases::list_phrases(&app_handle))
+}
+
+#[tauri::command]
+pub fn add_phrase(app_handle: tauri::AppHandle, text: String) -> Result<Vec<config::Phrase>, String> {
+    phrases::add_phrase(&app_handle, &text).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn update_phrase(app_handle: tauri::AppHandle, old: String, new: String) -> Result<Vec<config::Phrase>, String> {
+    phrases::update_phrase(&app_handle, &old, &new).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn remove_phrase(app_handle: tauri::AppHandle, text: String) -> Result<Vec<config::Phrase>, String> {
+    phrases::remove_phrase(&app_handle, &text).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_fast_preview(app_handle: tauri::AppHandle) -> Result<FastPreviewConfig, String> {
+    Ok(read_fast_preview_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_fast_preview(app_handle: tauri::AppHandle, config: FastPreviewConfig) -> Result<(), String> {
+    write_fast_preview_config(&app_handle, &config).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn apply_improved_transcript(app_handle: tauri::AppHandle) -> Result<Option<transcription::ImprovedTranscript>, String> {
+    transcription::apply_improved_transcript(&app_handle).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn export_session(app_handle: tauri::AppHandle, job_id: u64, path: String) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(move || sessions::export_session(&app_handle, job_id, std::path::Path::new(&path)))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn get_streaming(app_handle: tauri::AppHandle) -> Result<StreamingConfig, String> {
+    Ok(read_streaming_config(&app_handle))
+}
+
+#[tauri::command]
+pub fn save_streaming(app_handle: tauri::AppHandle, config: StreamingConfig) -> Result<(), String> {
+    if config.chunk_secs < 5 {
+        return Err("Chunks must be at least 5 seconds long".to_string());
+    }
+    write_streaming_config(&app_handle, &config).map_err(|e| e.to_string())
+}
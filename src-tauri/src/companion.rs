@@ -0,0 +1,380 @@
+use crate::audio::read_upload_mono_bytes;
+use crate::config::{read_companion_config, write_companion_config};
+use crate::http_server::{is_model_loaded, query_bool, query_param};
+use crate::jobs::{complete_job, fail_job, start_job, JobSource};
+use crate::transcription::{transcribe_upload, TranscriptionOverrides};
+use anyhow::Result;
+use hyper::body::HttpBody;
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use log::{info, warn};
+use std::convert::Infallible;
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+use tokio::sync::{mpsc, oneshot};
+
+/// Upload page served to the phone; it keeps the pairing token in local storage. Browsers
+/// only allow microphone access on HTTPS, so the page uploads recordings made elsewhere, in
+/// the formats recorder apps save: WAV, m4a (AAC) or WebM (Opus).
+const PAGE: &str = include_str!("companion.html");
+
+/// Largest accepted upload, about 15 minutes of 16-bit 48 kHz stereo.
+const MAX_UPLOAD_BYTES: usize = 200 * 1024 * 1024;
+
+/// Longest compressed upload, matching what fits in `MAX_UPLOAD_BYTES` as WAV.
+const MAX_UPLOAD_SECS: u64 = 15 * 60;
+
+/// Comment sent on idle event streams so phones and proxies do not drop the connection.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Uploads waiting for the transcription worker; further ones are refused with 429.
+const MAX_QUEUED_UPLOADS: usize = 4;
+
+/// How long a restart waits for the previous listener to release its port.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A decoded upload waiting to be transcribed.
+struct QueuedUpload {
+    app: AppHandle,
+    job_id: u64,
+    samples: Vec<f32>,
+    sample_rate: u32,
+    overrides: TranscriptionOverrides,
+}
+
+struct RunningServer {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    /// Disconnects once the server thread has exited and the port is free again.
+    stopped: Receiver<()>,
+}
+
+static SERVER: Mutex<Option<RunningServer>> = Mutex::new(None);
+/// One sender per open `/events` stream, each fed preformatted SSE messages.
+static SUBSCRIBERS: Mutex<Vec<mpsc::UnboundedSender<String>>> = Mutex::new(Vec::new());
+/// Feeds the single transcription worker, so uploads never run model passes side by side.
+static UPLOAD_QUEUE: OnceLock<SyncSender<QueuedUpload>> = OnceLock::new();
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct CompanionStatus {
+    pub enabled: bool,
+    pub running: bool,
+    /// URL to open on the phone; it carries the pairing token in the fragment.
+    pub pairing_url: Option<String>,
+    /// `pairing_url` as an SVG QR code.
+    pub qr_svg: Option<String>,
+    /// Phones currently listening for results.
+    pub connected: usize,
+}
+
+/// Result of an upload, sent to the phone as a `transcript` event and to the UI as
+/// `companion-transcript`.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct CompanionTranscript {
+    pub job_id: u64,
+    pub text: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Start or stop the LAN listener to match the saved config, creating a pairing token on first use.
+pub fn apply_companion_config(app: &AppHandle) -> Result<()> {
+    stop_companion();
+    let mut cfg = read_companion_config(app);
+    if !cfg.enabled {
+        return Ok(());
+    }
+    let token = match cfg.token.clone() {
+        Some(token) => token,
+        None => {
+            let token = new_token()?;
+            cfg.token = Some(token.clone());
+            write_companion_config(app, &cfg)?;
+            token
+        }
+    };
+    start_companion(app.clone(), cfg.port, token)
+}
+
+/// Replace the pairing token, so every phone paired so far has to scan the new QR code.
+pub fn reset_pairing(app: &AppHandle) -> Result<()> {
+    let mut cfg = read_companion_config(app);
+    cfg.token = Some(new_token()?);
+    write_companion_config(app, &cfg)?;
+    apply_companion_config(app)
+}
+
+pub fn companion_status(app: &AppHandle) -> CompanionStatus {
+    let cfg = read_companion_config(app);
+    let port = SERVER.lock().ok().and_then(|s| s.as_ref().map(|s| s.addr.port()));
+    let pairing_url = match (port, cfg.token.as_deref(), lan_ip()) {
+        (Some(port), Some(token), Some(ip)) => Some(format!("http://{}/#token={}", SocketAddr::new(ip, port), token)),
+        _ => None,
+    };
+    CompanionStatus {
+        enabled: cfg.enabled,
+        running: port.is_some(),
+        qr_svg: pairing_url.as_deref().and_then(qr_svg),
+        pairing_url,
+        connected: SUBSCRIBERS.lock().map(|s| s.len()).unwrap_or(0),
+    }
+}
+
+fn stop_companion() {
+    // Event streams never end on their own, so they are closed before the graceful shutdown.
+    if let Ok(mut subs) = SUBSCRIBERS.lock() {
+        subs.clear();
+    }
+    if let Some(server) = SERVER.lock().ok().and_then(|mut s| s.take()) {
+        let _ = server.shutdown.send(());
+        // Wait for the listener to go away, or the restart after a pairing reset can race it for the port.
+        if let Err(std::sync::mpsc::RecvTimeoutError::Timeout) = server.stopped.recv_timeout(SHUTDOWN_TIMEOUT) {
+            warn!("Companion server on {} did not stop within {:?}", server.addr, SHUTDOWN_TIMEOUT);
+        }
+        info!("Companion server on {} stopped", server.addr);
+    }
+}
+
+fn start_companion(app: AppHandle, port: u16, token: String) -> Result<()> {
+    let (bound_tx, bound_rx) = std::sync::mpsc::channel::<std::result::Result<SocketAddr, String>>();
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let (stopped_tx, stopped_rx) = std::sync::mpsc::channel::<()>();
+    let token = Arc::new(token);
+
+    std::thread::spawn(move || {
+        // Dropped when this thread returns, after the runtime has closed the listener.
+        let _stopped = stopped_tx;
+        let rt = match tokio::runtime::Builder::new_multi_thread().enable_all().build() {
+            Ok(rt) => rt,
+            Err(e) => {
+                let _ = bound_tx.send(Err(format!("failed to build tokio runtime: {}", e)));
+                return;
+            }
+        };
+        rt.block_on(async move {
+            let addr = SocketAddr::from(([0, 0, 0, 0], port));
+            let make_svc = make_service_fn(move |_| {
+                let app = app.clone();
+                let token = token.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                        let app = app.clone();
+                        let token = token.clone();
+                        async move { Ok::<_, Infallible>(handle(app, &token, req).await) }
+                    }))
+                }
+            });
+            let builder = match Server::try_bind(&addr) {
+                Ok(b) => b,
+                Err(e) => {
+                    let _ = bound_tx.send(Err(format!("failed to bind {}: {}", addr, e)));
+                    return;
+                }
+            };
+            let _ = bound_tx.send(Ok(addr));
+            let server = builder.serve(make_svc).with_graceful_shutdown(async {
+                let _ = shutdown_rx.await;
+            });
+            if let Err(e) = server.await {
+                warn!("companion server error: {}", e);
+            }
+        });
+    });
+
+    let addr = bound_rx
+        .recv()
+        .unwrap_or_else(|_| Err("companion server thread exited before binding".to_string()))
+        .map_err(|e| anyhow::anyhow!(e))?;
+    info!("Companion server listening on {}", addr);
+    if let Ok(mut server) = SERVER.lock() {
+        *server = Some(RunningServer { addr, shutdown: shutdown_tx, stopped: stopped_rx });
+    }
+    Ok(())
+}
+
+async fn handle(app: AppHandle, token: &str, req: Request<Body>) -> Response<Body> {
+    let method = req.method().clone();
+    let path = req.uri().path().to_string();
+    match (method, path.as_str()) {
+        (Method::GET, "/") => with_type(Response::new(Body::from(PAGE)), "text/html; charset=utf-8"),
+        _ if !is_authorized(&req, token) => json_response(StatusCode::UNAUTHORIZED, serde_json::json!({
+            "error": "unauthorized",
+            "message": "Scan the pairing QR code in Commander again.",
+        })),
+        (Method::POST, "/upload") => upload(app, req).await,
+        (Method::GET, "/events") => events(),
+        _ => json_response(StatusCode::NOT_FOUND, serde_json::json!({ "error": "not_found" })),
+    }
+}
+
+/// Accept a WAV, m4a or WebM recording and queue it as a job; the text follows on `/events`.
+/// `language`, `prompt` and `translate` query parameters apply to this upload only.
+async fn upload(app: AppHandle, req: Request<Body>) -> Response<Body> {
+    if !is_model_loaded() {
+        return json_response(StatusCode::SERVICE_UNAVAILABLE, serde_json::json!({
+            "error": "no_model",
+            "message": "No model is loaded in Commander.",
+        }));
+    }
+    let query = req.uri().query();
+    let overrides = TranscriptionOverrides {
+        language: query_param(query, "language"),
+        prompt: query_param(query, "prompt"),
+        model: None,
+        translate: query_bool(query, "translate"),
+    };
+    let bytes = match read_body(req.into_body()).await {
+        Ok(bytes) => bytes,
+        Err(status) => return json_response(status, serde_json::json!({ "error": "invalid_body" })),
+    };
+    let (samples, sample_rate) = match read_upload_mono_bytes(&bytes, MAX_UPLOAD_SECS) {
+        Ok(v) => v,
+        Err(e) => {
+            return json_response(StatusCode::BAD_REQUEST, serde_json::json!({
+                "error": "invalid_audio",
+                "message": e.to_string(),
+            }))
+        }
+    };
+    let audio_ms = samples.len() as u64 * 1000 / sample_rate.max(1) as u64;
+    let job_id = start_job(JobSource::Companion, audio_ms, None, None);
+    let queued = QueuedUpload { app, job_id, samples, sample_rate, overrides };
+    match upload_queue().try_send(queued) {
+        Ok(()) => {
+            info!("companion: queued {} ms upload (job {})", audio_ms, job_id);
+            json_response(StatusCode::ACCEPTED, serde_json::json!({ "job_id": job_id }))
+        }
+        Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => {
+            fail_job(job_id, "companion upload queue is full".to_string());
+            json_response(StatusCode::TOO_MANY_REQUESTS, serde_json::json!({
+                "error": "busy",
+                "message": "Commander is still transcribing earlier uploads. Try again shortly.",
+            }))
+        }
+    }
+}
+
+fn upload_queue() -> &'static SyncSender<QueuedUpload> {
+    UPLOAD_QUEUE.get_or_init(|| {
+        let (tx, rx) = sync_channel::<QueuedUpload>(MAX_QUEUED_UPLOADS);
+        std::thread::spawn(move || {
+            for upload in rx {
+                transcribe_queued(upload);
+            }
+        });
+        tx
+    })
+}
+
+fn transcribe_queued(upload: QueuedUpload) {
+    let QueuedUpload { app, job_id, samples, sample_rate, overrides } = upload;
+    let event = match transcribe_upload(&app, job_id, &samples, sample_rate, &overrides) {
+        Ok(text) => {
+            complete_job(job_id);
+            CompanionTranscript { job_id, text: Some(text), error: None }
+        }
+        Err(e) => {
+            fail_job(job_id, e.to_string());
+            CompanionTranscript { job_id, text: None, error: Some(e.to_string()) }
+        }
+    };
+    let _ = app.emit("companion-transcript", &event);
+    broadcast("transcript", &event);
+}
+
+/// Server-sent event stream of upload results.
+fn events() -> Response<Body> {
+    let (mut body_tx, body) = Body::channel();
+    let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+    if let Ok(mut subs) = SUBSCRIBERS.lock() {
+        subs.push(tx);
+    }
+    tokio::spawn(async move {
+        let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+        loop {
+            let message = tokio::select! {
+                message = rx.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                _ = keepalive.tick() => ": keepalive\n\n".to_string(),
+            };
+            if body_tx.send_data(message.into()).await.is_err() {
+                break;
+            }
+        }
+    });
+    let mut resp = with_type(Response::new(body), "text/event-stream");
+    resp.headers_mut().insert(hyper::header::CACHE_CONTROL, hyper::header::HeaderValue::from_static("no-cache"));
+    resp
+}
+
+fn broadcast<T: serde::Serialize>(event: &str, payload: &T) {
+    let Ok(data) = serde_json::to_string(payload) else { return };
+    let message = format!("event: {}\ndata: {}\n\n", event, data);
+    if let Ok(mut subs) = SUBSCRIBERS.lock() {
+        // A closed stream's receiver is gone, so its sender drops out here.
+        subs.retain(|tx| tx.send(message.clone()).is_ok());
+    }
+}
+
+async fn read_body(mut body: Body) -> std::result::Result<Vec<u8>, StatusCode> {
+    let mut bytes = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if bytes.len() + chunk.len() > MAX_UPLOAD_BYTES {
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+/// The token only comes as `Authorization: Bearer <token>`, never in the URL, so it stays
+/// out of proxy logs and browser history.
+fn is_authorized(req: &Request<Body>, token: &str) -> bool {
+    req.headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|given| tokens_match(given.trim(), token))
+        .unwrap_or(false)
+}
+
+/// Constant-time comparison, so response timing does not leak how much of a guess was right.
+pub(crate) fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+pub(crate) fn new_token() -> Result<String> {
+    let mut bytes = [0u8; 16];
+    std::fs::File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Address other devices on the network reach this Mac at. Connecting a UDP socket sends
+/// nothing; it only picks the interface the default route goes through.
+fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok()?;
+    socket.connect("192.0.2.1:9").ok()?;
+    socket.local_addr().ok().map(|a| a.ip()).filter(|ip| !ip.is_loopback() && !ip.is_unspecified())
+}
+
+fn qr_svg(data: &str) -> Option<String> {
+    let code = qrcode::QrCode::new(data.as_bytes()).ok()?;
+    Some(code.render::<qrcode::render::svg::Color>().min_dimensions(240, 240).build())
+}
+
+fn with_type(mut resp: Response<Body>, content_type: &'static str) -> Response<Body> {
+    resp.headers_mut().insert(hyper::header::CONTENT_TYPE, hyper::header::HeaderValue::from_static(content_type));
+    resp
+}
+
+fn json_response(status: StatusCode, body: serde_json::Value) -> Response<Body> {
+    let mut resp = with_type(Response::new(Body::from(body.to_string())), "application/json");
+    *resp.status_mut() = status;
+    resp
+}
@@ -1,4 +1,5 @@
 use anyhow::{anyhow, Result};
+use std::path::PathBuf;
 use tauri::{AppHandle, Manager};
 use tauri_plugin_global_shortcut::{Code, Modifiers, Shortcut};
 
@@ -17,15 +18,80 @@ pub struct AutoPasteConfig {
     pub enabled: bool,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct HoldToRecordConfig {
     pub enabled: bool,
+    /// Presses released sooner than this are taps, not holds.
+    #[serde(default = "default_min_hold_ms")]
+    pub min_hold_ms: u64,
+    /// Treat a tap as "start recording until the next press" instead of discarding it.
+    #[serde(default)]
+    pub tap_toggles: bool,
+}
+
+fn default_min_hold_ms() -> u64 {
+    300
+}
+
+impl Default for HoldToRecordConfig {
+    fn default() -> Self {
+        Self { enabled: false, min_hold_ms: default_min_hold_ms(), tap_toggles: false }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct InlineTimestampsConfig {
+    pub enabled: bool,
+}
+
+/// What a global shortcut does when triggered.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShortcutAction {
+    /// Start/stop recording, following the hold-to-record setting.
+    #[default]
+    Record,
+    Toggle,
+    Hold,
+    /// Discard the current recording without transcribing.
+    Cancel,
+    PasteLast,
+    /// Activate `profile`, or the next profile when none is named.
+    SwitchProfile,
+    PauseResume,
+    /// Paste what append mode has collected so far.
+    FlushBuffer,
+    /// Put the fast-preview's improved transcript on the clipboard.
+    UseImproved,
+    ShowWindow,
+    OpenSettings,
+    OpenHistory,
+    /// Load the next installed model.
+    NextModel,
+    /// Select the next connected input device.
+    NextInputDevice,
+    ReplayLast,
+    /// Start a fresh time box for the running recording after its warning.
+    ContinueRecording,
+    /// Transcribe the retroactive buffer: what was said just now, without a recording.
+    TranscribeRecent,
+    /// Like `Toggle`, but the dictation it stops is translated into English.
+    Translate,
+    Quit,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct ShortcutConfig {
     pub modifiers: Vec<String>,
     pub key: String,
+    #[serde(default)]
+    pub action: ShortcutAction,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// Catalog id of the model that transcribes recordings this shortcut stops, instead of the
+    /// selected one. It is kept loaded next to the selected model.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
 }
 
 impl Default for ShortcutConfig {
@@ -33,10 +99,19 @@ impl Default for ShortcutConfig {
         Self {
             modifiers: vec!["Super".to_string(), "Shift".to_string()],
             key: "F9".to_string(),
+            action: ShortcutAction::Record,
+            profile: None,
+            model: None,
         }
     }
 }
 
+/// Additional shortcuts registered alongside the main one from `shortcut.json`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct ShortcutBindingsConfig {
+    pub bindings: Vec<ShortcutConfig>,
+}
+
 impl ShortcutConfig {
     pub fn to_shortcut(&self) -> Result<Shortcut> {
         let mut modifier_flags = Modifiers::empty();
@@ -111,6 +186,36 @@ impl ShortcutConfig {
     }
 }
 
+pub fn read_shortcut_config(app: &AppHandle) -> ShortcutConfig {
+    app.path()
+        .app_config_dir()
+        .ok()
+        .and_then(|dir| std::fs::read_to_string(dir.join("shortcut.json")).ok())
+        .and_then(|content| serde_json::from_str::<ShortcutConfig>(&content).ok())
+        .unwrap_or_default()
+}
+
+pub fn read_shortcut_bindings(app: &AppHandle) -> ShortcutBindingsConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("shortcut_bindings.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<ShortcutBindingsConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    ShortcutBindingsConfig::default()
+}
+
+pub fn write_shortcut_bindings(app: &AppHandle, cfg: &ShortcutBindingsConfig) -> Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("shortcut_bindings.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
 pub fn is_auto_paste_enabled(app: &AppHandle) -> bool {
     if let Ok(config_dir) = app.path().app_config_dir() {
         let path = config_dir.join("auto_paste.json");
@@ -126,11 +231,37 @@ pub fn is_auto_paste_enabled(app: &AppHandle) -> bool {
 }
 
 pub fn is_hold_to_record_enabled(app: &AppHandle) -> bool {
+    read_hold_to_record_config(app).enabled
+}
+
+pub fn read_hold_to_record_config(app: &AppHandle) -> HoldToRecordConfig {
     if let Ok(config_dir) = app.path().app_config_dir() {
         let path = config_dir.join("hold_to_record.json");
         if path.exists() {
             if let Ok(content) = std::fs::read_to_string(&path) {
                 if let Ok(cfg) = serde_json::from_str::<HoldToRecordConfig>(&content) {
+                    return cfg;
+                }
+            }
+        }
+    }
+    HoldToRecordConfig::default()
+}
+
+pub fn write_hold_to_record_config(app: &AppHandle, cfg: &HoldToRecordConfig) -> Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(dir.join("hold_to_record.json"), content)?;
+    Ok(())
+}
+
+pub fn is_inline_timestamps_enabled(app: &AppHandle) -> bool {
+    if let Ok(config_dir) = app.path().app_config_dir() {
+        let path = config_dir.join("inline_timestamps.json");
+        if path.exists() {
+            if let Ok(content) = std::fs::read_to_string(&path) {
+                if let Ok(cfg) = serde_json::from_str::<InlineTimestampsConfig>(&content) {
                     return cfg.enabled;
                 }
             }
@@ -169,6 +300,29 @@ pub fn write_model_config(app: &AppHandle, cfg: &ModelConfig) -> anyhow::Result<
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
 pub struct AudioInputConfig {
     pub selected_device_name: Option<String>,
+    /// Tried in order when `selected_device_name` is not connected, before the system default.
+    #[serde(default)]
+    pub fallback_device_names: Vec<String>,
+    /// Always record from the system default input, switching whenever it changes; the
+    /// selected and fallback devices are kept but ignored.
+    #[serde(default)]
+    pub follow_system_default: bool,
+}
+
+impl AudioInputConfig {
+    /// Preferred devices in priority order, without duplicates. Empty means the system default.
+    pub fn device_candidates(&self) -> Vec<String> {
+        if self.follow_system_default { return Vec::new(); }
+        let mut out: Vec<String> = Vec::new();
+        for name in self.selected_device_name.iter().chain(&self.fallback_device_names) {
+            if !name.is_empty() && !out.contains(name) { out.push(name.clone()); }
+        }
+        out
+    }
+}
+
+pub fn input_device_candidates(app: &AppHandle) -> Vec<String> {
+    read_audio_input_config(app).map(|c| c.device_candidates()).unwrap_or_default()
 }
 
 pub fn read_audio_input_config(app: &AppHandle) -> Option<AudioInputConfig> {
@@ -192,4 +346,1504 @@ pub fn write_audio_input_config(app: &AppHandle, cfg: &AudioInputConfig) -> anyh
     Ok(())
 }
 
+/// Stream format to open a device with, instead of its default.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct InputFormat {
+    pub sample_rate_hz: u32,
+    pub channels: u16,
+}
+
+/// Chosen stream formats keyed by input device name; devices without an entry prefer 16 kHz mono.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct InputFormatsConfig {
+    pub devices: std::collections::HashMap<String, InputFormat>,
+}
+
+pub fn read_input_formats_config(app: &AppHandle) -> InputFormatsConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("input_formats.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<InputFormatsConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    InputFormatsConfig::default()
+}
+
+pub fn write_input_formats_config(app: &AppHandle, cfg: &InputFormatsConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("input_formats.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+
+
+
+/// Per-application override of the global auto-paste setting. `app` matches the
+/// frontmost application's bundle identifier or display name.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AutoPasteAppRule {
+    pub app: String,
+    pub allow: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct AutoPasteRulesConfig {
+    pub rules: Vec<AutoPasteAppRule>,
+}
+
+pub fn read_auto_paste_rules(app: &AppHandle) -> AutoPasteRulesConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("auto_paste_rules.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<AutoPasteRulesConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    AutoPasteRulesConfig::default()
+}
+
+pub fn write_auto_paste_rules(app: &AppHandle, cfg: &AutoPasteRulesConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("auto_paste_rules.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Apps (bundle identifier or display name) in whose foreground recording refuses to start.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct RecordingBlocklistConfig {
+    pub apps: Vec<String>,
+}
+
+pub fn read_recording_blocklist(app: &AppHandle) -> RecordingBlocklistConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("recording_blocklist.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<RecordingBlocklistConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    RecordingBlocklistConfig::default()
+}
+
+pub fn write_recording_blocklist(app: &AppHandle, cfg: &RecordingBlocklistConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("recording_blocklist.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Optional overrides for where Commander keeps temp WAVs, saved recordings and models.
+/// `None` keeps the platform default.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct StorageConfig {
+    pub temp_dir: Option<String>,
+    pub recordings_dir: Option<String>,
+    pub models_dir: Option<String>,
+    /// Debugging aid: leave each job's temp WAV in `temp_dir` instead of deleting it.
+    #[serde(default)]
+    pub keep_temp_wavs: bool,
+}
+
+pub fn read_storage_config(app: &AppHandle) -> StorageConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("storage.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<StorageConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    StorageConfig::default()
+}
+
+pub fn write_storage_config(app: &AppHandle, cfg: &StorageConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("storage.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+fn configured_dir(value: &Option<String>) -> Option<PathBuf> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(PathBuf::from)
+}
+
+pub fn temp_dir(app: &AppHandle) -> PathBuf {
+    configured_dir(&read_storage_config(app).temp_dir).unwrap_or_else(std::env::temp_dir)
+}
+
+pub fn recordings_dir(app: &AppHandle) -> Result<PathBuf> {
+    match configured_dir(&read_storage_config(app).recordings_dir) {
+        Some(dir) => Ok(dir),
+        None => Ok(app.path().app_data_dir()?.join("recordings")),
+    }
+}
+
+pub fn models_dir(app: &AppHandle) -> Result<PathBuf> {
+    match configured_dir(&read_storage_config(app).models_dir) {
+        Some(dir) => Ok(dir),
+        None => Ok(app.path().app_data_dir()?.join("models")),
+    }
+}
+
+/// Token-bucket limits applied per client on `/transcribe`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub requests_per_minute: u32,
+    pub burst: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self { enabled: false, requests_per_minute: 30, burst: 5 }
+    }
+}
+
+pub fn read_rate_limit_config(app: &AppHandle) -> RateLimitConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("rate_limit.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<RateLimitConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    RateLimitConfig::default()
+}
+
+pub fn write_rate_limit_config(app: &AppHandle, cfg: &RateLimitConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("rate_limit.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Save this launch's HTTP client token to `client_token`, readable only by the current user,
+/// for editor plugins and scripts that call the privileged endpoints.
+pub fn write_client_token(app: &AppHandle, token: &str) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("client_token");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    std::io::Write::write_all(&mut options.open(&path)?, token.as_bytes())?;
+    Ok(())
+}
+
+/// Release the model after this long without a transcription; it is reloaded on the next one.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ModelIdleConfig {
+    pub enabled: bool,
+    pub idle_minutes: u64,
+}
+
+impl Default for ModelIdleConfig {
+    fn default() -> Self {
+        Self { enabled: false, idle_minutes: 15 }
+    }
+}
+
+pub fn read_model_idle_config(app: &AppHandle) -> ModelIdleConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("model_idle.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<ModelIdleConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    ModelIdleConfig::default()
+}
+
+pub fn write_model_idle_config(app: &AppHandle, cfg: &ModelIdleConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("model_idle.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// How whisper runs on this machine. Changes take effect when the model is reloaded.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct InferenceConfig {
+    /// Off forces CPU-only inference, for Macs where Metal fails to initialise or glitches.
+    pub use_gpu: bool,
+    /// Load models with flash attention, for faster decoding on Metal.
+    pub flash_attn: bool,
+    /// Align word timings with DTW for catalog models that have alignment heads. Has no
+    /// effect with `flash_attn`.
+    pub dtw_timestamps: bool,
+    /// CPU threads per transcription; `None` uses up to 4. Applies from the next request.
+    pub threads: Option<u32>,
+}
+
+impl Default for InferenceConfig {
+    fn default() -> Self {
+        Self { use_gpu: true, flash_attn: false, dtw_timestamps: false, threads: None }
+    }
+}
+
+pub fn read_inference_config(app: &AppHandle) -> InferenceConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("inference.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<InferenceConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    InferenceConfig::default()
+}
+
+pub fn write_inference_config(app: &AppHandle, cfg: &InferenceConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("inference.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Advanced whisper decoding knobs; `None` keeps whisper.cpp's default.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct WhisperParamsConfig {
+    /// Fallback to higher temperature when segment entropy exceeds this.
+    pub entropy_threshold: Option<f32>,
+    /// Fallback when the average token log-probability falls below this.
+    pub logprob_threshold: Option<f32>,
+    /// Keep the first sampled token from being blank (whisper.cpp default: on).
+    pub suppress_blank: Option<bool>,
+    /// Suppress non-speech tokens such as `(music)` or `[BLANK_AUDIO]` (default: off).
+    pub suppress_non_speech_tokens: Option<bool>,
+    /// whisper.cpp's `no_speech_thold`, e.g. `0.6`: a window above it that also decodes below
+    /// `logprob_threshold` is treated as silence.
+    #[serde(default)]
+    pub no_speech_threshold: Option<f32>,
+    /// Text that must never be produced, e.g. `♪` or `Subtitles by`. Only the exact token
+    /// sequence of each entry is blocked; its pieces can still appear in other words.
+    #[serde(default)]
+    pub suppressed_tokens: Vec<String>,
+    /// Translate every dictation into English instead of transcribing it.
+    #[serde(default)]
+    pub translate: bool,
+}
+
+pub fn read_whisper_params_config(app: &AppHandle) -> WhisperParamsConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("whisper_params.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<WhisperParamsConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    WhisperParamsConfig::default()
+}
+
+pub fn write_whisper_params_config(app: &AppHandle, cfg: &WhisperParamsConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("whisper_params.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// How whisper picks tokens.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DecodingStrategy {
+    /// Keep the `beam_size` best hypotheses; slower and usually more accurate.
+    #[default]
+    BeamSearch,
+    /// Sample one token at a time, keeping the best of `best_of` samples when temperature > 0.
+    Greedy,
+}
+
+impl DecodingStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DecodingStrategy::BeamSearch => "beam_search",
+            DecodingStrategy::Greedy => "greedy",
+        }
+    }
+}
+
+/// Largest beam or sample count offered; whisper.cpp slows down roughly linearly beyond this.
+pub const MAX_DECODING_CANDIDATES: u32 = 8;
+
+/// Decoding strategy for dictations. The defaults match what Commander always used.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct DecodingConfig {
+    pub strategy: DecodingStrategy,
+    pub beam_size: u32,
+    pub best_of: u32,
+    pub temperature: f32,
+    /// Beam search patience factor; `None` keeps whisper.cpp's default.
+    pub patience: Option<f32>,
+}
+
+impl Default for DecodingConfig {
+    fn default() -> Self {
+        Self { strategy: DecodingStrategy::BeamSearch, beam_size: 5, best_of: 5, temperature: 0.0, patience: None }
+    }
+}
 
+pub fn read_decoding_config(app: &AppHandle) -> DecodingConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("decoding.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<DecodingConfig>(&s).unwrap_or_default();
+            }
+        }
+        // `patience` lived in whisper_params.json before decoding.json existed. Save it here
+        // right away, since the next save of whisper_params.json drops the old key.
+        let legacy_patience = std::fs::read_to_string(dir.join("whisper_params.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<serde_json::Value>(&s).ok())
+            .and_then(|v| v.get("patience").and_then(|p| p.as_f64()));
+        if let Some(patience) = legacy_patience {
+            let cfg = DecodingConfig { patience: Some(patience as f32), ..DecodingConfig::default() };
+            if let Err(e) = write_decoding_config(app, &cfg) {
+                log::warn!("failed to carry patience over to decoding.json: {}", e);
+            }
+            return cfg;
+        }
+    }
+    DecodingConfig::default()
+}
+
+pub fn write_decoding_config(app: &AppHandle, cfg: &DecodingConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("decoding.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Use the keyboard input source's language for each dictation instead of the saved default.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct InputSourceLanguageConfig {
+    pub enabled: bool,
+}
+
+pub fn read_input_source_language_config(app: &AppHandle) -> InputSourceLanguageConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("input_source_language.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<InputSourceLanguageConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    InputSourceLanguageConfig::default()
+}
+
+pub fn write_input_source_language_config(app: &AppHandle, cfg: &InputSourceLanguageConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("input_source_language.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Collect successive dictations and only copy/paste them on an explicit flush.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AppendModeConfig {
+    pub enabled: bool,
+    /// Inserted between dictations when the buffer is flushed.
+    pub separator: String,
+}
+
+impl Default for AppendModeConfig {
+    fn default() -> Self {
+        Self { enabled: false, separator: " ".to_string() }
+    }
+}
+
+pub fn read_append_mode_config(app: &AppHandle) -> AppendModeConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("append_mode.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<AppendModeConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    AppendModeConfig::default()
+}
+
+pub fn write_append_mode_config(app: &AppHandle, cfg: &AppendModeConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("append_mode.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// When and how auto-paste fires after a transcription lands on the clipboard.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AutoPasteTimingConfig {
+    /// Pause before sending Cmd+V, giving slow apps time to settle focus.
+    pub delay_ms: u64,
+    /// Additionally wait until the frontmost app is stable (and not Commander) before pasting.
+    pub wait_for_frontmost: bool,
+    /// Give up on pasting if no stable target app appears within this window.
+    pub frontmost_timeout_ms: u64,
+}
+
+impl Default for AutoPasteTimingConfig {
+    fn default() -> Self {
+        Self { delay_ms: 150, wait_for_frontmost: false, frontmost_timeout_ms: 1000 }
+    }
+}
+
+pub fn read_auto_paste_timing_config(app: &AppHandle) -> AutoPasteTimingConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("auto_paste_timing.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<AutoPasteTimingConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    AutoPasteTimingConfig::default()
+}
+
+pub fn write_auto_paste_timing_config(app: &AppHandle, cfg: &AutoPasteTimingConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("auto_paste_timing.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Paste a quick transcript from a small model first, then re-run the audio on the
+/// selected model and offer the result as a replacement.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct FastPreviewConfig {
+    pub enabled: bool,
+    /// Catalog id of the installed model used for the instant preview.
+    pub preview_model_id: Option<String>,
+}
+
+pub fn read_fast_preview_config(app: &AppHandle) -> FastPreviewConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("fast_preview.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<FastPreviewConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    FastPreviewConfig::default()
+}
+
+pub fn write_fast_preview_config(app: &AppHandle, cfg: &FastPreviewConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("fast_preview.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Hold long transcripts in a review window before auto-paste fires.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct PasteReviewConfig {
+    pub enabled: bool,
+    /// Transcripts longer than this many characters are reviewed first.
+    pub min_chars: usize,
+}
+
+impl Default for PasteReviewConfig {
+    fn default() -> Self {
+        Self { enabled: false, min_chars: 500 }
+    }
+}
+
+pub fn read_paste_review_config(app: &AppHandle) -> PasteReviewConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("paste_review.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<PasteReviewConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    PasteReviewConfig::default()
+}
+
+pub fn write_paste_review_config(app: &AppHandle, cfg: &PasteReviewConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("paste_review.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// One destination for a finished transcript.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutputSinkConfig {
+    Clipboard,
+    /// Cmd+V into the frontmost app, subject to the auto-paste toggle and per-app rules.
+    AutoPaste,
+    FileAppend { path: String },
+    Webhook { url: String },
+    History,
+}
+
+impl OutputSinkConfig {
+    pub fn name(&self) -> &'static str {
+        match self {
+            OutputSinkConfig::Clipboard => "clipboard",
+            OutputSinkConfig::AutoPaste => "auto_paste",
+            OutputSinkConfig::FileAppend { .. } => "file_append",
+            OutputSinkConfig::Webhook { .. } => "webhook",
+            OutputSinkConfig::History => "history",
+        }
+    }
+}
+
+/// A named bundle of per-dictation behaviour the user can switch between.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ProfileConfig {
+    pub name: String,
+    /// Sinks run in order after each transcription.
+    pub output_sinks: Vec<OutputSinkConfig>,
+    /// Catalog model loaded when switching to this profile; `None` keeps the current model.
+    #[serde(default)]
+    pub model_id: Option<String>,
+}
+
+impl Default for ProfileConfig {
+    fn default() -> Self {
+        Self {
+            name: "Default".to_string(),
+            output_sinks: vec![OutputSinkConfig::Clipboard, OutputSinkConfig::AutoPaste],
+            model_id: None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ProfilesConfig {
+    pub active: String,
+    pub profiles: Vec<ProfileConfig>,
+}
+
+impl Default for ProfilesConfig {
+    fn default() -> Self {
+        let profile = ProfileConfig::default();
+        Self { active: profile.name.clone(), profiles: vec![profile] }
+    }
+}
+
+pub fn read_profiles_config(app: &AppHandle) -> ProfilesConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("profiles.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<ProfilesConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    ProfilesConfig::default()
+}
+
+pub fn write_profiles_config(app: &AppHandle, cfg: &ProfilesConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("profiles.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// The active profile, falling back to the first one (or the built-in default).
+pub fn active_profile(app: &AppHandle) -> ProfileConfig {
+    let cfg = read_profiles_config(app);
+    cfg.profiles
+        .iter()
+        .find(|p| p.name == cfg.active)
+        .or_else(|| cfg.profiles.first())
+        .cloned()
+        .unwrap_or_default()
+}
+
+/// Make `name` the active profile, or advance to the next profile when `name` is `None`.
+/// Returns the newly active profile name.
+pub fn switch_profile(app: &AppHandle, name: Option<&str>) -> anyhow::Result<String> {
+    let mut cfg = read_profiles_config(app);
+    let next = match name {
+        Some(name) => cfg
+            .profiles
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.name.clone())
+            .ok_or_else(|| anyhow!("Unknown profile: {}", name))?,
+        None => {
+            let idx = cfg.profiles.iter().position(|p| p.name == cfg.active).map(|i| i + 1).unwrap_or(0);
+            cfg.profiles
+                .get(idx % cfg.profiles.len().max(1))
+                .map(|p| p.name.clone())
+                .ok_or_else(|| anyhow!("No profiles configured"))?
+        }
+    };
+    cfg.active = next.clone();
+    write_profiles_config(app, &cfg)?;
+    Ok(next)
+}
+
+/// Meeting mode records a second input (a loopback device carrying system audio) as a
+/// separate track, so each side is transcribed on its own and labelled.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct MeetingModeConfig {
+    pub enabled: bool,
+    /// Input device that receives system output, e.g. a BlackHole loopback device.
+    pub loopback_device: Option<String>,
+    pub me_label: String,
+    pub them_label: String,
+    #[serde(default)]
+    pub track_mode: MeetingTrackMode,
+}
+
+/// How the mic and system-audio tracks of a meeting recording are combined.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MeetingTrackMode {
+    /// Transcribe each side on its own and label the lines; saved as a two-channel WAV.
+    #[default]
+    Separate,
+    /// Sum both sides into one track and transcribe it as a normal dictation.
+    Mixed,
+}
+
+impl Default for MeetingModeConfig {
+    fn default() -> Self {
+        Self { enabled: false, loopback_device: None, me_label: "Me".to_string(), them_label: "Them".to_string(), track_mode: MeetingTrackMode::Separate }
+    }
+}
+
+impl MeetingModeConfig {
+    /// The loopback device to capture, or `None` when meeting mode is off.
+    pub fn active_loopback_device(&self) -> Option<String> {
+        if self.enabled { self.loopback_device.clone() } else { None }
+    }
+}
+
+pub fn read_meeting_mode_config(app: &AppHandle) -> MeetingModeConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("meeting_mode.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<MeetingModeConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    MeetingModeConfig::default()
+}
+
+pub fn write_meeting_mode_config(app: &AppHandle, cfg: &MeetingModeConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("meeting_mode.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Ambient level measured by `calibrate_noise_floor` for one input device.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct NoiseProfile {
+    pub noise_floor_db: f32,
+    pub calibrated_at_ms: u128,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct NoiseProfilesConfig {
+    /// Keyed by input device name.
+    pub devices: std::collections::HashMap<String, NoiseProfile>,
+}
+
+pub fn read_noise_profiles(app: &AppHandle) -> NoiseProfilesConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("noise_profiles.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<NoiseProfilesConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    NoiseProfilesConfig::default()
+}
+
+pub fn write_noise_profiles(app: &AppHandle, cfg: &NoiseProfilesConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("noise_profiles.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Name of the input device recordings come from: the selected one, else the system default.
+pub fn current_input_device_name(app: &AppHandle) -> Option<String> {
+    let open = app
+        .try_state::<std::sync::Arc<std::sync::Mutex<crate::audio::RecorderState>>>()
+        .and_then(|r| r.lock().ok().and_then(|st| st.input_device_name.clone()));
+    open.or_else(|| input_device_candidates(app).into_iter().next())
+        .or_else(crate::audio::default_input_device_name)
+}
+
+/// Calibrated noise floor of the current input device, if it has been measured.
+pub fn current_noise_floor_db(app: &AppHandle) -> Option<f32> {
+    let device = current_input_device_name(app)?;
+    read_noise_profiles(app).devices.get(&device).map(|p| p.noise_floor_db)
+}
+
+/// Transcribe long recordings in chunks while they run instead of all at once on stop.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct StreamingConfig {
+    pub enabled: bool,
+    /// Nominal chunk length; each chunk ends at a quiet point shortly before this.
+    pub chunk_secs: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self { enabled: false, chunk_secs: 30 }
+    }
+}
+
+pub fn read_streaming_config(app: &AppHandle) -> StreamingConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("streaming.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<StreamingConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    StreamingConfig::default()
+}
+
+pub fn write_streaming_config(app: &AppHandle, cfg: &StreamingConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("streaming.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Warn, then stop, a toggle-mode recording that has run for a while, so one left running by
+/// accident ends on its own. `continue_recording` after the warning starts a fresh box.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct TimeBoxConfig {
+    pub enabled: bool,
+    pub warn_after_secs: u64,
+    pub stop_after_secs: u64,
+}
+
+impl Default for TimeBoxConfig {
+    fn default() -> Self {
+        Self { enabled: false, warn_after_secs: 4 * 60, stop_after_secs: 5 * 60 }
+    }
+}
+
+pub fn read_time_box_config(app: &AppHandle) -> TimeBoxConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("time_box.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<TimeBoxConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    TimeBoxConfig::default()
+}
+
+pub fn write_time_box_config(app: &AppHandle, cfg: &TimeBoxConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("time_box.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Short pause between the shortcut and the start of capture, so the key click is not recorded
+/// and there is time to bring the target app forward. `tick` plays a click when it begins.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct ArmingDelayConfig {
+    pub enabled: bool,
+    pub delay_ms: u64,
+    pub tick: bool,
+}
+
+impl Default for ArmingDelayConfig {
+    fn default() -> Self {
+        Self { enabled: false, delay_ms: 500, tick: true }
+    }
+}
+
+pub fn read_arming_delay_config(app: &AppHandle) -> ArmingDelayConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("arming_delay.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<ArmingDelayConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    ArmingDelayConfig::default()
+}
+
+pub fn write_arming_delay_config(app: &AppHandle, cfg: &ArmingDelayConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("arming_delay.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// LAN listener that lets a paired phone upload recordings. `token` is the pairing secret carried
+/// by the QR code; resetting it unpairs every phone.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct CompanionConfig {
+    pub enabled: bool,
+    pub port: u16,
+    #[serde(default)]
+    pub token: Option<String>,
+}
+
+impl Default for CompanionConfig {
+    fn default() -> Self {
+        Self { enabled: false, port: 9010, token: None }
+    }
+}
+
+pub fn read_companion_config(app: &AppHandle) -> CompanionConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("companion.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<CompanionConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    CompanionConfig::default()
+}
+
+pub fn write_companion_config(app: &AppHandle, cfg: &CompanionConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("companion.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Pause whatever is playing while recording, so music or video does not bleed into the mic,
+/// and resume it afterwards.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct MediaPauseConfig {
+    pub enabled: bool,
+}
+
+pub fn read_media_pause_config(app: &AppHandle) -> MediaPauseConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("media_pause.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<MediaPauseConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    MediaPauseConfig::default()
+}
+
+pub fn write_media_pause_config(app: &AppHandle, cfg: &MediaPauseConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("media_pause.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Stop-to-text target for dictations; a slower one raises `latency-budget-exceeded`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct LatencyBudgetConfig {
+    pub enabled: bool,
+    pub target_ms: u64,
+}
+
+impl Default for LatencyBudgetConfig {
+    fn default() -> Self {
+        Self { enabled: false, target_ms: 2_000 }
+    }
+}
+
+pub fn read_latency_budget_config(app: &AppHandle) -> LatencyBudgetConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("latency_budget.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<LatencyBudgetConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    LatencyBudgetConfig::default()
+}
+
+pub fn write_latency_budget_config(app: &AppHandle, cfg: &LatencyBudgetConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("latency_budget.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Whether each recording is saved in the recordings directory, in which format, and how long
+/// saved recordings are kept. Limits of `None` keep recordings forever.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct RecordingRetentionConfig {
+    pub save_recordings: bool,
+    #[serde(default)]
+    pub format: RecordingFormat,
+    pub max_count: Option<usize>,
+    pub max_age_days: Option<u64>,
+}
+
+impl Default for RecordingRetentionConfig {
+    fn default() -> Self {
+        Self { save_recordings: true, format: RecordingFormat::default(), max_count: None, max_age_days: None }
+    }
+}
+
+/// File format of saved recordings. Both are lossless; FLAC takes a fraction of the space.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingFormat {
+    Wav,
+    #[default]
+    Flac,
+}
+
+impl RecordingFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            RecordingFormat::Wav => "wav",
+            RecordingFormat::Flac => "flac",
+        }
+    }
+}
+
+pub fn read_recording_retention_config(app: &AppHandle) -> RecordingRetentionConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("recording_retention.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<RecordingRetentionConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    RecordingRetentionConfig::default()
+}
+
+pub fn write_recording_retention_config(app: &AppHandle, cfg: &RecordingRetentionConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("recording_retention.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Rate and content of `audio-level` events for the level meter and spectrum visualizer.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AudioLevelsConfig {
+    /// Minimum time between events (16 ms at the fastest).
+    pub interval_ms: u64,
+    /// Spectrum bands to include in each event; 0 sends levels only.
+    pub spectrum_bands: usize,
+}
+
+impl Default for AudioLevelsConfig {
+    fn default() -> Self {
+        Self { interval_ms: 50, spectrum_bands: 0 }
+    }
+}
+
+pub fn read_audio_levels_config(app: &AppHandle) -> AudioLevelsConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("audio_levels.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<AudioLevelsConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    AudioLevelsConfig::default()
+}
+
+pub fn write_audio_levels_config(app: &AppHandle, cfg: &AudioLevelsConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("audio_levels.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Speak state changes (recording, transcribed, model switched…) through the macOS `say` voice.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct SpokenFeedbackConfig {
+    pub enabled: bool,
+    /// `say -v` voice name; the system voice when `None`.
+    #[serde(default)]
+    pub voice: Option<String>,
+}
+
+pub fn read_spoken_feedback_config(app: &AppHandle) -> SpokenFeedbackConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("spoken_feedback.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<SpokenFeedbackConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    SpokenFeedbackConfig::default()
+}
+
+pub fn write_spoken_feedback_config(app: &AppHandle, cfg: &SpokenFeedbackConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("spoken_feedback.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Stop recording automatically once the speaker has been silent for `silence_ms`.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AutoStopConfig {
+    pub enabled: bool,
+    pub silence_ms: u64,
+}
+
+impl Default for AutoStopConfig {
+    fn default() -> Self {
+        Self { enabled: false, silence_ms: 2000 }
+    }
+}
+
+pub fn read_auto_stop_config(app: &AppHandle) -> AutoStopConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("auto_stop.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<AutoStopConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    AutoStopConfig::default()
+}
+
+pub fn write_auto_stop_config(app: &AppHandle, cfg: &AutoStopConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("auto_stop.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AudioStageKind {
+    /// DC offset removal plus a high-pass at `cutoff_hz`, against rumble from cheap mics.
+    HighPass,
+    Gain,
+    /// RNNoise suppression; makes 48 kHz the preferred capture rate.
+    Denoise,
+    /// Marks speech against the calibrated noise floor at this point in the chain. Without
+    /// it speech is judged after the last stage, so auto-stop works whatever the chain.
+    Vad,
+}
+
+impl AudioStageKind {
+    /// Default order, which is also where a stage missing from a saved list is inserted.
+    pub const ALL: [AudioStageKind; 4] = [AudioStageKind::HighPass, AudioStageKind::Gain, AudioStageKind::Denoise, AudioStageKind::Vad];
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AudioStageConfig {
+    pub kind: AudioStageKind,
+    pub enabled: bool,
+    /// Only used by `gain`.
+    #[serde(default)]
+    pub gain_db: f32,
+    /// Only used by `high-pass`.
+    #[serde(default = "default_high_pass_cutoff_hz")]
+    pub cutoff_hz: f32,
+}
+
+fn default_high_pass_cutoff_hz() -> f32 {
+    80.0
+}
+
+impl AudioStageConfig {
+    fn default_for(kind: AudioStageKind) -> Self {
+        let enabled = matches!(kind, AudioStageKind::HighPass | AudioStageKind::Vad);
+        Self { kind, enabled, gain_db: 0.0, cutoff_hz: default_high_pass_cutoff_hz() }
+    }
+}
+
+/// Stages applied to captured audio, in order, before it is buffered.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct AudioPipelineConfig {
+    pub stages: Vec<AudioStageConfig>,
+}
+
+impl Default for AudioPipelineConfig {
+    fn default() -> Self {
+        Self { stages: AudioStageKind::ALL.iter().map(|&kind| AudioStageConfig::default_for(kind)).collect() }
+    }
+}
+
+impl AudioPipelineConfig {
+    /// Stages missing from a saved list are added with their defaults at their default
+    /// position, so stages added in a later version show up after an upgrade.
+    fn with_missing_stages(mut self) -> Self {
+        for (index, kind) in AudioStageKind::ALL.into_iter().enumerate() {
+            if !self.stages.iter().any(|s| s.kind == kind) {
+                self.stages.insert(index.min(self.stages.len()), AudioStageConfig::default_for(kind));
+            }
+        }
+        self
+    }
+}
+
+/// `noise_suppression.json`, which switched denoising on before the stage chain existed.
+#[derive(serde::Deserialize, Default)]
+struct LegacyNoiseSuppressionConfig {
+    enabled: bool,
+}
+
+pub fn read_audio_pipeline_config(app: &AppHandle) -> AudioPipelineConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("audio_pipeline.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<AudioPipelineConfig>(&s).unwrap_or_default().with_missing_stages();
+            }
+        }
+        // No chain saved yet: carry over noise suppression turned on in an earlier version.
+        let legacy = std::fs::read_to_string(dir.join("noise_suppression.json"))
+            .ok()
+            .and_then(|s| serde_json::from_str::<LegacyNoiseSuppressionConfig>(&s).ok())
+            .unwrap_or_default();
+        let mut cfg = AudioPipelineConfig::default();
+        if let Some(denoise) = cfg.stages.iter_mut().find(|s| s.kind == AudioStageKind::Denoise) {
+            denoise.enabled = legacy.enabled;
+        }
+        return cfg;
+    }
+    AudioPipelineConfig::default()
+}
+
+pub fn write_audio_pipeline_config(app: &AppHandle, cfg: &AudioPipelineConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("audio_pipeline.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Upper bound on one recording. At the limit the recording either stops and transcribes,
+/// or keeps going and overwrites its oldest audio, which it did before stopping was added.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct RecordingLimitConfig {
+    pub max_duration_secs: u64,
+    #[serde(default)]
+    pub stop_at_limit: bool,
+    /// The tray warns this long before the limit; 0 disables the warning.
+    #[serde(default = "default_limit_warning_secs")]
+    pub warning_secs: u64,
+}
+
+fn default_limit_warning_secs() -> u64 {
+    30
+}
+
+impl Default for RecordingLimitConfig {
+    fn default() -> Self {
+        Self { max_duration_secs: 30 * 60, stop_at_limit: false, warning_secs: default_limit_warning_secs() }
+    }
+}
+
+pub fn read_recording_limit_config(app: &AppHandle) -> RecordingLimitConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("recording_limit.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<RecordingLimitConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    RecordingLimitConfig::default()
+}
+
+pub fn write_recording_limit_config(app: &AppHandle, cfg: &RecordingLimitConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("recording_limit.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Audio kept from just before recording starts, so speech that begins a moment early is not cut.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct PreRollConfig {
+    pub enabled: bool,
+    pub duration_ms: u64,
+}
+
+impl Default for PreRollConfig {
+    fn default() -> Self {
+        Self { enabled: false, duration_ms: 1500 }
+    }
+}
+
+impl PreRollConfig {
+    pub fn effective_ms(&self) -> u64 {
+        if self.enabled { self.duration_ms } else { 0 }
+    }
+}
+
+pub fn read_pre_roll_config(app: &AppHandle) -> PreRollConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("pre_roll.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<PreRollConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    PreRollConfig::default()
+}
+
+pub fn write_pre_roll_config(app: &AppHandle, cfg: &PreRollConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("pre_roll.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Audio kept from the last `duration_secs` while not recording, so something said before
+/// anyone thought to press record can still be transcribed.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct RetroactiveConfig {
+    pub enabled: bool,
+    pub duration_secs: u64,
+}
+
+impl Default for RetroactiveConfig {
+    fn default() -> Self {
+        Self { enabled: false, duration_secs: 30 }
+    }
+}
+
+impl RetroactiveConfig {
+    pub fn effective_ms(&self) -> u64 {
+        if self.enabled { self.duration_secs * 1000 } else { 0 }
+    }
+}
+
+pub fn read_retroactive_config(app: &AppHandle) -> RetroactiveConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("retroactive.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<RetroactiveConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    RetroactiveConfig::default()
+}
+
+pub fn write_retroactive_config(app: &AppHandle, cfg: &RetroactiveConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("retroactive.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Folder polled for WAV files to transcribe into `.txt`/`.srt` sidecars.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct WatchFolderConfig {
+    pub enabled: bool,
+    pub directory: Option<String>,
+    #[serde(default = "default_true")]
+    pub write_srt: bool,
+    #[serde(default = "default_watch_poll_secs")]
+    pub poll_secs: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_watch_poll_secs() -> u64 {
+    10
+}
+
+impl Default for WatchFolderConfig {
+    fn default() -> Self {
+        Self { enabled: false, directory: None, write_srt: true, poll_secs: default_watch_poll_secs() }
+    }
+}
+
+pub fn read_watch_folder_config(app: &AppHandle) -> WatchFolderConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("watch_folder.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<WatchFolderConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    WatchFolderConfig::default()
+}
+
+pub fn write_watch_folder_config(app: &AppHandle, cfg: &WatchFolderConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("watch_folder.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// Reject transcripts that look like hallucinations and retry them once with fallback decoding.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct QualityGuardConfig {
+    pub enabled: bool,
+    /// Share of repeated three-word phrases above which output counts as a loop.
+    pub max_repetition_ratio: f32,
+    /// Temperature for the greedy retry; some randomness helps escape a loop.
+    pub retry_temperature: f32,
+    /// Transcripts with a lower confidence are flagged in the tray, even with the guard off.
+    /// `None` turns the flag off.
+    #[serde(default = "default_low_confidence_threshold")]
+    pub low_confidence_threshold: Option<f32>,
+}
+
+fn default_low_confidence_threshold() -> Option<f32> {
+    Some(0.5)
+}
+
+impl Default for QualityGuardConfig {
+    fn default() -> Self {
+        Self { enabled: false, max_repetition_ratio: 0.5, retry_temperature: 0.4, low_confidence_threshold: default_low_confidence_threshold() }
+    }
+}
+
+pub fn read_quality_guard_config(app: &AppHandle) -> QualityGuardConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("quality_guard.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<QualityGuardConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    QualityGuardConfig::default()
+}
+
+pub fn write_quality_guard_config(app: &AppHandle, cfg: &QualityGuardConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("quality_guard.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
+
+/// A vocabulary entry for the initial prompt, with usage stats for prioritising.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Phrase {
+    pub text: String,
+    pub added_ms: u128,
+    /// Last time the phrase appeared in a transcript; 0 if never.
+    #[serde(default)]
+    pub last_used_ms: u128,
+    #[serde(default)]
+    pub use_count: u64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct PhraseListConfig {
+    pub phrases: Vec<Phrase>,
+}
+
+pub fn read_phrase_list(app: &AppHandle) -> PhraseListConfig {
+    if let Ok(dir) = app.path().app_config_dir() {
+        let path = dir.join("phrases.json");
+        if path.exists() {
+            if let Ok(s) = std::fs::read_to_string(path) {
+                return serde_json::from_str::<PhraseListConfig>(&s).unwrap_or_default();
+            }
+        }
+    }
+    PhraseListConfig::default()
+}
+
+pub fn write_phrase_list(app: &AppHandle, cfg: &PhraseListConfig) -> anyhow::Result<()> {
+    let dir = app.path().app_config_dir()?;
+    std::fs::create_dir_all(&dir)?;
+    let path = dir.join("phrases.json");
+    let content = serde_json::to_string_pretty(cfg)?;
+    std::fs::write(path, content)?;
+    Ok(())
+}
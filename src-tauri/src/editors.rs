@@ -0,0 +1,141 @@
+use crate::platform::frontmost_application;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+/// How long a plugin gets to accept a transcript before we fall back to the clipboard.
+const DELIVERY_TIMEOUT_MS: u64 = 2000;
+
+/// Per-editor tweaks applied to the transcript before it is handed to the plugin.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct EditorFormat {
+    pub prefix: String,
+    pub suffix: String,
+    /// Drop a single trailing `.`, which rarely belongs in code or commit messages.
+    pub strip_trailing_period: bool,
+    pub lowercase_first: bool,
+}
+
+impl EditorFormat {
+    pub fn apply(&self, text: &str) -> String {
+        let mut body = text.trim().to_string();
+        if self.strip_trailing_period && body.ends_with('.') && !body.ends_with("..") {
+            body.pop();
+        }
+        if self.lowercase_first {
+            let mut chars = body.chars();
+            if let Some(first) = chars.next() {
+                body = first.to_lowercase().chain(chars).collect();
+            }
+        }
+        format!("{}{}{}", self.prefix, body, self.suffix)
+    }
+}
+
+/// Sent by an editor plugin to `POST /editors/register`.
+#[derive(serde::Deserialize, Clone, Debug)]
+pub struct EditorRegistrationRequest {
+    pub name: String,
+    /// Bundle ids or app names the plugin lives in, e.g. `com.microsoft.VSCode`.
+    pub apps: Vec<String>,
+    /// Plugin endpoint that inserts `{job_id, text}` at the cursor of the active editor.
+    pub callback_url: String,
+    #[serde(default)]
+    pub format: EditorFormat,
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct EditorRegistration {
+    pub id: u64,
+    pub name: String,
+    pub apps: Vec<String>,
+    pub callback_url: String,
+    pub format: EditorFormat,
+}
+
+static NEXT_EDITOR_ID: AtomicU64 = AtomicU64::new(1);
+static EDITORS: OnceLock<Mutex<Vec<EditorRegistration>>> = OnceLock::new();
+
+fn editors() -> &'static Mutex<Vec<EditorRegistration>> {
+    EDITORS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+/// Register a plugin. A plugin re-registering with the same callback replaces its old entry,
+/// so restarts of the editor do not pile up stale registrations.
+pub fn register_editor(req: EditorRegistrationRequest) -> Result<EditorRegistration> {
+    if req.apps.iter().all(|a| a.trim().is_empty()) {
+        return Err(anyhow!("at least one app is required"));
+    }
+    let url = reqwest::Url::parse(&req.callback_url).map_err(|e| anyhow!("invalid callback_url: {}", e))?;
+    if !matches!(url.host_str(), Some("127.0.0.1" | "localhost" | "::1" | "[::1]")) {
+        return Err(anyhow!("callback_url must point at localhost"));
+    }
+    let registration = EditorRegistration {
+        id: NEXT_EDITOR_ID.fetch_add(1, Ordering::Relaxed),
+        name: req.name,
+        apps: req.apps,
+        callback_url: req.callback_url,
+        format: req.format,
+    };
+    let mut list = editors().lock().map_err(|_| anyhow!("editor registry unavailable"))?;
+    list.retain(|e| e.callback_url != registration.callback_url);
+    list.push(registration.clone());
+    info!("editor plugin registered: {} ({:?})", registration.name, registration.apps);
+    Ok(registration)
+}
+
+pub fn unregister_editor(id: u64) -> bool {
+    let Ok(mut list) = editors().lock() else { return false };
+    let before = list.len();
+    list.retain(|e| e.id != id);
+    list.len() != before
+}
+
+pub fn registered_editors() -> Vec<EditorRegistration> {
+    editors().lock().map(|l| l.clone()).unwrap_or_default()
+}
+
+/// The plugin registered for the frontmost app, if any; the newest registration wins.
+fn active_editor() -> Option<EditorRegistration> {
+    let front = frontmost_application()?;
+    let list = editors().lock().ok()?;
+    list.iter().rev().find(|e| e.apps.iter().any(|a| front.matches(a))).cloned()
+}
+
+/// Hand the transcript to the plugin of the frontmost editor. Returns false when no plugin
+/// covers that app or the plugin did not accept it, so the caller falls back to pasting.
+pub fn deliver_to_active_editor(job_id: u64, text: &str) -> bool {
+    let Some(editor) = active_editor() else { return false };
+    let body = serde_json::json!({ "job_id": job_id, "text": editor.format.apply(text) });
+    let url = editor.callback_url.clone();
+    let result = std::thread::spawn(move || -> Result<()> {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build()?;
+        rt.block_on(async move {
+            let resp = reqwest::Client::new()
+                .post(&url)
+                .timeout(Duration::from_millis(DELIVERY_TIMEOUT_MS))
+                .json(&body)
+                .send()
+                .await?;
+            if !resp.status().is_success() {
+                return Err(anyhow!("plugin returned {}", resp.status()));
+            }
+            Ok(())
+        })
+    })
+    .join()
+    .unwrap_or_else(|_| Err(anyhow!("delivery thread panicked")));
+    match result {
+        Ok(()) => {
+            info!("transcript {} sent to editor plugin {}", job_id, editor.name);
+            true
+        }
+        Err(e) => {
+            warn!("editor plugin {} failed, falling back to paste: {}", editor.name, e);
+            false
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use crate::audio::default_input_device_name;
+use crate::config::read_model_config;
+use crate::http_server::{is_model_loaded, server_bind_status};
+use crate::platform::{self, MicrophoneAuthorization};
+use log::warn;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct HealthCheck {
+    pub name: &'static str,
+    pub ok: bool,
+    pub detail: String,
+}
+
+/// Result of the startup self-check; `ok` is false when any capability is degraded.
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct StartupHealth {
+    pub ok: bool,
+    pub checks: Vec<HealthCheck>,
+}
+
+fn check(name: &'static str, ok: bool, detail: impl Into<String>) -> HealthCheck {
+    HealthCheck { name, ok, detail: detail.into() }
+}
+
+/// Probe microphone, permissions, model, Metal resources and the local server.
+pub fn run_health_checks(app: &AppHandle) -> StartupHealth {
+    let mut checks = Vec::new();
+
+    checks.push(match default_input_device_name() {
+        Some(name) => check("microphone", true, name),
+        None => check("microphone", false, "No input device found"),
+    });
+
+    let mic = platform::microphone_authorization();
+    checks.push(check(
+        "microphone_permission",
+        matches!(mic, MicrophoneAuthorization::Authorized | MicrophoneAuthorization::NotDetermined),
+        match mic {
+            MicrophoneAuthorization::Authorized => "Granted",
+            MicrophoneAuthorization::NotDetermined => "Will be requested on first recording",
+            MicrophoneAuthorization::Denied => "Denied in System Settings → Privacy & Security → Microphone",
+            MicrophoneAuthorization::Restricted => "Restricted by system policy",
+        },
+    ));
+
+    let trusted = platform::is_accessibility_trusted();
+    checks.push(check(
+        "accessibility_permission",
+        trusted,
+        if trusted { "Granted" } else { "Needed for global shortcuts and auto-paste" },
+    ));
+
+    let selected = read_model_config(app).and_then(|c| c.selected_id);
+    checks.push(match (is_model_loaded(), selected) {
+        (true, Some(id)) => check("model", true, id),
+        (true, None) => check("model", true, "Loaded"),
+        (false, Some(id)) => check("model", false, format!("Selected model {} is missing or failed to load", id)),
+        (false, None) => check("model", false, "No model downloaded"),
+    });
+
+    let metal_dir = std::env::var("GGML_METAL_PATH_RESOURCES").unwrap_or_default();
+    let metallib = std::path::Path::new(&metal_dir).join("default.metallib");
+    checks.push(if metallib.exists() {
+        check("metallib", true, metallib.to_string_lossy())
+    } else {
+        check("metallib", false, format!("default.metallib not found in {:?}; inference falls back to CPU", metal_dir))
+    });
+
+    checks.push(match server_bind_status() {
+        Some(Ok(addr)) => check("server", true, addr.to_string()),
+        Some(Err(e)) => check("server", false, e),
+        None => check("server", false, "Not started"),
+    });
+
+    for c in checks.iter().filter(|c| !c.ok) {
+        warn!("startup check {} failed: {}", c.name, c.detail);
+    }
+    StartupHealth { ok: checks.iter().all(|c| c.ok), checks }
+}
+
+/// Run the checks and broadcast them as `startup-health`.
+pub fn report_health(app: &AppHandle) -> StartupHealth {
+    let health = run_health_checks(app);
+    let _ = app.emit("startup-health", &health);
+    health
+}
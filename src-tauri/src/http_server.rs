@@ -1,45 +1,655 @@
-use crate::whisper_service::WhisperService;
+use crate::whisper_service::{set_inference_threads, ContextOptions, TranscribeOptions, WhisperService};
+use whisper_rs::DtwModelPreset;
 use anyhow::{Context, Result};
 use hyper::body::to_bytes;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use hyper::server::conn::AddrStream;
+use log::{info, warn};
+use crate::companion::{new_token, tokens_match};
+use crate::config::{InferenceConfig, ModelIdleConfig, RateLimitConfig, MAX_DECODING_CANDIDATES};
+use crate::editors::{register_editor, registered_editors, unregister_editor, EditorRegistrationRequest};
+use crate::jobs::{complete_job, fail_job, start_job, JobSource, RecordingSpan};
+use std::collections::{HashMap, VecDeque};
 use std::convert::Infallible;
 use std::net::SocketAddr;
-use std::sync::{Arc, RwLock, OnceLock};
-use std::time::Instant;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, RwLock, OnceLock};
+use std::time::{Instant, SystemTime};
 
 static WHISPER_SVC: OnceLock<Arc<RwLock<Option<Arc<WhisperService>>>>> = OnceLock::new();
 
+/// Number of recent requests kept in memory for `recent_requests`.
+const ACCESS_LOG_CAPACITY: usize = 200;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+static ACCESS_LOG: OnceLock<Mutex<VecDeque<AccessLogEntry>>> = OnceLock::new();
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct AccessLogEntry {
+    pub request_id: u64,
+    pub timestamp_ms: u128,
+    pub method: String,
+    pub path: String,
+    pub peer: Option<String>,
+    pub status: u16,
+    pub duration_ms: u128,
+}
+
+fn access_log() -> &'static Mutex<VecDeque<AccessLogEntry>> {
+    ACCESS_LOG.get_or_init(|| Mutex::new(VecDeque::with_capacity(ACCESS_LOG_CAPACITY)))
+}
+
+fn record_access(entry: AccessLogEntry) {
+    info!(
+        "[req {}] {} {} peer={} status={} {}ms",
+        entry.request_id,
+        entry.method,
+        entry.path,
+        entry.peer.as_deref().unwrap_or("-"),
+        entry.status,
+        entry.duration_ms
+    );
+    if let Ok(mut log) = access_log().lock() {
+        if log.len() == ACCESS_LOG_CAPACITY {
+            log.pop_front();
+        }
+        log.push_back(entry);
+    }
+}
+
+/// The most recent dictation delivered by the app, served on `GET /last`.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LastTranscription {
+    pub job_id: u64,
+    pub text: String,
+    pub timestamp_ms: u128,
+    pub audio_ms: u64,
+    pub total_ms: u128,
+    pub model_id: Option<String>,
+    pub warning: Option<String>,
+    pub recording: Option<RecordingSpan>,
+}
+
+static LAST_TRANSCRIPTION: OnceLock<Mutex<Option<LastTranscription>>> = OnceLock::new();
+
+pub fn set_last_transcription(last: LastTranscription) {
+    if let Ok(mut slot) = LAST_TRANSCRIPTION.get_or_init(|| Mutex::new(None)).lock() {
+        *slot = Some(last);
+    }
+}
+
+fn last_transcription() -> Option<LastTranscription> {
+    LAST_TRANSCRIPTION.get()?.lock().ok()?.clone()
+}
+
+static CLIENT_TOKEN: OnceLock<String> = OnceLock::new();
+
+/// Secret for the endpoints a web page must not reach, created once per launch. Commander's
+/// own pipeline sends it, and `write_client_token` saves it for plugins and scripts.
+pub fn client_token() -> &'static str {
+    CLIENT_TOKEN.get_or_init(|| {
+        new_token().unwrap_or_else(|e| {
+            warn!("failed to read random bytes for the client token: {}", e);
+            let state = std::collections::hash_map::RandomState::new();
+            format!("{:016x}{:016x}", std::hash::BuildHasher::hash_one(&state, 1u8), std::hash::BuildHasher::hash_one(&state, 2u8))
+        })
+    })
+}
+
+/// Whether the request carries `Authorization: Bearer <client token>`.
+fn is_app_client(req: &Request<Body>) -> bool {
+    req.headers()
+        .get(hyper::header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|given| tokens_match(given.trim(), client_token()))
+        .unwrap_or(false)
+}
+
+/// Gate for endpoints that change app state. Browsers attach `Origin` to cross-site POSTs and
+/// DELETEs, so any request with one is refused even before the token is checked.
+fn require_app_client(req: &Request<Body>, request_id: u64) -> Result<(), Response<Body>> {
+    if req.headers().contains_key(hyper::header::ORIGIN) {
+        return json_response(StatusCode::FORBIDDEN, serde_json::json!({
+            "request_id": request_id,
+            "error": "browser_origin",
+            "message": "This endpoint cannot be called from a web page.",
+        }))
+        .map(|_| ());
+    }
+    if !is_app_client(req) {
+        return json_response(StatusCode::UNAUTHORIZED, serde_json::json!({
+            "request_id": request_id,
+            "error": "unauthorized",
+            "message": "Send the token from Commander's client_token file as a Bearer token.",
+        }))
+        .map(|_| ());
+    }
+    Ok(())
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+struct RateLimiter {
+    config: RateLimitConfig,
+    buckets: HashMap<String, TokenBucket>,
+}
+
+static RATE_LIMITER: OnceLock<Mutex<RateLimiter>> = OnceLock::new();
+
+fn rate_limiter() -> &'static Mutex<RateLimiter> {
+    RATE_LIMITER.get_or_init(|| Mutex::new(RateLimiter { config: RateLimitConfig::default(), buckets: HashMap::new() }))
+}
+
+/// Replace the active rate limit settings; existing buckets are reset.
+pub fn set_rate_limit(config: RateLimitConfig) {
+    if let Ok(mut limiter) = rate_limiter().lock() {
+        limiter.config = config;
+        limiter.buckets.clear();
+    }
+}
+
+/// Bucket for a caller that is not exempt. Headers are chosen by the caller, so rotating
+/// them must not buy a fresh bucket; only the peer address is trusted. Holders of the client
+/// token are exempt and never reach a bucket.
+fn client_key(peer: &SocketAddr) -> String {
+    format!("ip:{}", peer.ip())
+}
+
+/// Take one token for `key`; on refusal returns the seconds until a token is available.
+/// Commander's own pipeline, which sends the client token, is never limited.
+fn check_rate_limit(key: &str, exempt: bool) -> Result<(), u64> {
+    if exempt { return Ok(()); }
+    let mut limiter = match rate_limiter().lock() { Ok(l) => l, Err(_) => return Ok(()) };
+    if !limiter.config.enabled || limiter.config.requests_per_minute == 0 { return Ok(()); }
+    let capacity = limiter.config.burst.max(1) as f64;
+    let per_sec = limiter.config.requests_per_minute as f64 / 60.0;
+    let now = Instant::now();
+    if !limiter.buckets.contains_key(key) {
+        // A bucket idle long enough to refill is the same as a new one, so drop those before
+        // adding another; otherwise every client id ever sent would stay in memory.
+        let full_after = capacity / per_sec;
+        limiter.buckets.retain(|_, b| now.duration_since(b.last_refill).as_secs_f64() < full_after);
+    }
+    let bucket = limiter
+        .buckets
+        .entry(key.to_string())
+        .or_insert(TokenBucket { tokens: capacity, last_refill: now });
+    bucket.take(capacity, per_sec, now)
+}
+
+impl TokenBucket {
+    /// Refill for the time since the last call, then take one token if there is one.
+    fn take(&mut self, capacity: f64, per_sec: f64, now: Instant) -> Result<(), u64> {
+        let elapsed = now.duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_sec).min(capacity);
+        self.last_refill = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(((1.0 - self.tokens) / per_sec).ceil() as u64)
+        }
+    }
+}
+
+/// Most recent HTTP requests, newest first.
+pub fn recent_requests() -> Vec<AccessLogEntry> {
+    access_log()
+        .lock()
+        .map(|log| log.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
+
+static CONTEXT_OPTIONS: OnceLock<Mutex<ContextOptions>> = OnceLock::new();
+static DTW_TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+
+type DtwPresetLookup = Box<dyn Fn(&str) -> Option<DtwModelPreset> + Send + Sync>;
+static DTW_PRESETS: OnceLock<DtwPresetLookup> = OnceLock::new();
+
+/// Register how a model file maps to its DTW alignment-head preset (set once at startup).
+pub fn set_dtw_preset_lookup<F>(lookup: F)
+where
+    F: Fn(&str) -> Option<DtwModelPreset> + Send + Sync + 'static,
+{
+    let _ = DTW_PRESETS.set(Box::new(lookup));
+}
+
+/// Apply the thread count to the next request and the context options to every model loaded
+/// from now on; see `reload_model` to apply those to the loaded one.
+pub fn set_inference(config: &InferenceConfig) {
+    if let Ok(mut options) = CONTEXT_OPTIONS.get_or_init(|| Mutex::new(ContextOptions::default())).lock() {
+        options.use_gpu = config.use_gpu;
+        options.flash_attn = config.flash_attn;
+    }
+    DTW_TIMESTAMPS.store(config.dtw_timestamps, Ordering::Relaxed);
+    set_inference_threads(config.threads);
+}
+
+/// Options for loading `model_path`, with its DTW preset when DTW timestamps are on.
+fn context_options(model_path: &str) -> ContextOptions {
+    let mut options: ContextOptions = CONTEXT_OPTIONS.get().and_then(|o| o.lock().ok().map(|o| o.clone())).unwrap_or_default();
+    if DTW_TIMESTAMPS.load(Ordering::Relaxed) {
+        options.dtw_preset = DTW_PRESETS.get().and_then(|lookup| lookup(model_path));
+    }
+    options
+}
+
 pub fn service_holder() -> Arc<RwLock<Option<Arc<WhisperService>>>> {
     WHISPER_SVC
         .get_or_init(|| Arc::new(RwLock::new(None)))
         .clone()
 }
 
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ModelChangedEvent {
+    pub model_path: String,
+    pub previous_model_path: Option<String>,
+}
+
+type ModelChangeListener = Box<dyn Fn(ModelChangedEvent) + Send + Sync>;
+static MODEL_CHANGE_LISTENER: OnceLock<ModelChangeListener> = OnceLock::new();
+
+/// Register the process-wide receiver of completed model swaps (set once at startup).
+pub fn set_model_change_listener<F>(listener: F)
+where
+    F: Fn(ModelChangedEvent) + Send + Sync + 'static,
+{
+    let _ = MODEL_CHANGE_LISTENER.set(Box::new(listener));
+}
+
+#[derive(Clone, Debug, serde::Serialize)]
+pub struct ModelUnloadedEvent {
+    pub model_path: String,
+}
+
+type ModelUnloadListener = Box<dyn Fn(ModelUnloadedEvent) + Send + Sync>;
+static MODEL_UNLOAD_LISTENER: OnceLock<ModelUnloadListener> = OnceLock::new();
+
+/// Register the process-wide receiver of completed model unloads (set once at startup).
+pub fn set_model_unload_listener<F>(listener: F)
+where
+    F: Fn(ModelUnloadedEvent) + Send + Sync + 'static,
+{
+    let _ = MODEL_UNLOAD_LISTENER.set(Box::new(listener));
+}
+
+type ModelResolver = Box<dyn Fn(&str) -> Option<std::path::PathBuf> + Send + Sync>;
+static MODEL_RESOLVER: OnceLock<ModelResolver> = OnceLock::new();
+/// The most recent per-request override model, kept loaded for follow-up requests.
+static OVERRIDE_SVC: OnceLock<Mutex<Option<(String, Arc<WhisperService>)>>> = OnceLock::new();
+
+/// Register how `model=<id>` query overrides map to model files (set once at startup).
+pub fn set_model_resolver<F>(resolver: F)
+where
+    F: Fn(&str) -> Option<std::path::PathBuf> + Send + Sync + 'static,
+{
+    let _ = MODEL_RESOLVER.set(Box::new(resolver));
+}
+
+/// Service for a one-off model override. Reuses the current service when it already runs
+/// that model; otherwise loads it, replacing any previously cached override.
+fn override_service(id: &str) -> std::result::Result<Arc<WhisperService>, String> {
+    let path = MODEL_RESOLVER
+        .get()
+        .and_then(|resolve| resolve(id))
+        .ok_or_else(|| format!("Model {} is not installed", id))?
+        .to_string_lossy()
+        .to_string();
+    if let Some(current) = current_service().filter(|s| s.backend_info().model_path == path) {
+        return Ok(current);
+    }
+    let cache = OVERRIDE_SVC.get_or_init(|| Mutex::new(None));
+    let mut cached = cache.lock().map_err(|_| "override model lock poisoned".to_string())?;
+    if let Some((cached_path, svc)) = cached.as_ref() {
+        if *cached_path == path { return Ok(svc.clone()); }
+    }
+    info!("Loading override model {} from {}", id, path);
+    let svc = Arc::new(WhisperService::from_model_path(&path, &context_options(&path)).map_err(|e| e.to_string())?);
+    *cached = Some((path, svc.clone()));
+    Ok(svc)
+}
+
+type ModelLoadingListener = Box<dyn Fn(String) + Send + Sync>;
+static MODEL_LOADING_LISTENER: OnceLock<ModelLoadingListener> = OnceLock::new();
+
+/// Register the receiver told when an idle-unloaded model is being reloaded (set once at startup).
+pub fn set_model_loading_listener<F>(listener: F)
+where
+    F: Fn(String) + Send + Sync + 'static,
+{
+    let _ = MODEL_LOADING_LISTENER.set(Box::new(listener));
+}
+
+/// How often the idle watcher checks when the model was last used.
+const IDLE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+static IDLE_CONFIG: OnceLock<Mutex<ModelIdleConfig>> = OnceLock::new();
+static LAST_MODEL_USE: OnceLock<Mutex<Instant>> = OnceLock::new();
+/// Path of the model the idle watcher released, reloaded by the next transcription.
+static IDLE_UNLOADED: OnceLock<Mutex<Option<String>>> = OnceLock::new();
+static IDLE_WATCHER: OnceLock<()> = OnceLock::new();
+static RELOAD_LOCK: Mutex<()> = Mutex::new(());
+
+fn idle_unloaded() -> &'static Mutex<Option<String>> {
+    IDLE_UNLOADED.get_or_init(|| Mutex::new(None))
+}
+
+/// Restart the idle clock, e.g. when a recording starts, so the model is not unloaded just
+/// before it is needed.
+pub fn touch_model() {
+    if let Ok(mut last) = LAST_MODEL_USE.get_or_init(|| Mutex::new(Instant::now())).lock() {
+        *last = Instant::now();
+    }
+}
+
+/// Replace the idle auto-unload settings, starting the watcher on first use.
+pub fn set_idle_unload(config: ModelIdleConfig) {
+    if let Ok(mut current) = IDLE_CONFIG.get_or_init(|| Mutex::new(ModelIdleConfig::default())).lock() {
+        *current = config;
+    }
+    touch_model();
+    IDLE_WATCHER.get_or_init(|| {
+        std::thread::spawn(|| loop {
+            std::thread::sleep(IDLE_CHECK_INTERVAL);
+            unload_if_idle();
+        });
+    });
+}
+
+fn unload_if_idle() {
+    let Some(Ok(config)) = IDLE_CONFIG.get().map(|c| c.lock().map(|c| c.clone())) else { return };
+    if !config.enabled || config.idle_minutes == 0 { return; }
+    let idle = LAST_MODEL_USE.get().and_then(|l| l.lock().ok().map(|l| l.elapsed())).unwrap_or_default();
+    if idle.as_secs() < config.idle_minutes * 60 || current_service().is_none() { return; }
+    // Held until the slot is filled, so a request arriving meanwhile waits in
+    // `reload_after_idle` instead of finding neither a model nor one to reload.
+    let Ok(_reloading) = RELOAD_LOCK.lock() else { return };
+    match unload_model() {
+        Ok(Some(path)) => {
+            info!("Model idle for {} min; unloaded until the next transcription", idle.as_secs() / 60);
+            if let Ok(mut slot) = idle_unloaded().lock() { *slot = Some(path); }
+        }
+        Ok(None) => {}
+        Err(e) => warn!("Failed to unload idle model: {}", e),
+    }
+}
+
+/// The current service, first reloading the model the idle watcher released if there is one.
+fn reload_after_idle() -> Option<Arc<WhisperService>> {
+    let _reloading = RELOAD_LOCK.lock().ok()?;
+    if let Some(svc) = current_service() { return Some(svc); }
+    let path = idle_unloaded().lock().ok()?.take()?;
+    info!("Reloading idle-unloaded model {}", path);
+    if let Some(listener) = MODEL_LOADING_LISTENER.get() {
+        listener(path.clone());
+    }
+    if let Err(e) = load_model(path.clone()) {
+        warn!("Failed to reload model {}: {}", path, e);
+        if let Ok(mut slot) = idle_unloaded().lock() { *slot = Some(path); }
+        return None;
+    }
+    current_service()
+}
+
+/// Load model `id` next to the selected one in the background, so the first request routed
+/// to it does not wait for the load.
+pub fn preload_override_model(id: String) {
+    std::thread::spawn(move || {
+        if let Err(e) = override_service(&id) {
+            warn!("Failed to preload model {}: {}", id, e);
+        }
+    });
+}
+
+/// Load `model_path` and make it the current service. Requests that already cloned the
+/// previous service keep running on it; the swap is announced once the last of them has
+/// released it, so the old model is never freed mid-inference.
 pub fn load_model(model_path: String) -> Result<()> {
     let holder = service_holder();
-    let svc = Arc::new(WhisperService::from_model_path(&model_path).context("load model")?);
-    if let Ok(mut w) = holder.write() {
-        *w = Some(svc);
-    }
+    if let Ok(mut slot) = idle_unloaded().lock() { slot.take(); }
+    touch_model();
+    let svc = Arc::new(WhisperService::from_model_path(&model_path, &context_options(&model_path)).context("load model")?);
+    let previous = match holder.write() {
+        Ok(mut w) => w.replace(svc),
+        Err(_) => return Err(anyhow::anyhow!("model lock poisoned")),
+    };
+    let previous_model_path = previous.as_ref().map(|p| p.backend_info().model_path);
+    release_when_idle(previous, move || {
+        info!("Model switched to {}", model_path);
+        if let Some(listener) = MODEL_CHANGE_LISTENER.get() {
+            listener(ModelChangedEvent { model_path, previous_model_path });
+        }
+    });
     Ok(())
 }
 
+/// Load the current model again with the current inference options, dropping the cached
+/// override model so it is reloaded with them too. Returns the reloaded model's path.
+pub fn reload_model() -> Result<Option<String>> {
+    if let Some(Ok(mut cached)) = OVERRIDE_SVC.get().map(|c| c.lock()) {
+        cached.take();
+    }
+    let Some(path) = current_service().map(|s| s.backend_info().model_path) else { return Ok(None) };
+    load_model(path.clone())?;
+    Ok(Some(path))
+}
+
+/// Follow model files moved from `from` to `to`: the idle-unloaded slot is pointed at the new
+/// location, and a model loaded from `from` is loaded again from `to` in the background so
+/// `reload_model` and model comparisons see the path it now lives at.
+pub fn models_moved(from: &std::path::Path, to: &std::path::Path) {
+    let from = from.to_path_buf();
+    let to = to.to_path_buf();
+    std::thread::spawn(move || {
+        let Ok(_reloading) = RELOAD_LOCK.lock() else { return };
+        if let Ok(mut slot) = idle_unloaded().lock() {
+            if let Some(path) = slot.as_deref().and_then(|p| moved_path(p, &from, &to)) {
+                *slot = Some(path);
+            }
+        }
+        let Some(path) = current_service().and_then(|s| moved_path(&s.backend_info().model_path, &from, &to)) else { return };
+        if let Err(e) = load_model(path.clone()) {
+            warn!("Failed to load moved model {}: {}", path, e);
+        }
+    });
+}
+
+/// `path` re-rooted from `from` to `to`, or None when it is not under `from`.
+fn moved_path(path: &str, from: &std::path::Path, to: &std::path::Path) -> Option<String> {
+    let rest = std::path::Path::new(path).strip_prefix(from).ok()?;
+    Some(to.join(rest).to_string_lossy().to_string())
+}
+
+/// Drop the current model and any cached override model to free their memory. Requests that
+/// already cloned a service keep it until they finish; `model-unloaded` follows once it is freed.
+/// Returns the path of the model that was loaded, if any.
+pub fn unload_model() -> Result<Option<String>> {
+    if let Ok(mut slot) = idle_unloaded().lock() { slot.take(); }
+    let previous = match service_holder().write() {
+        Ok(mut w) => w.take(),
+        Err(_) => return Err(anyhow::anyhow!("model lock poisoned")),
+    };
+    if let Some(Ok(mut cached)) = OVERRIDE_SVC.get().map(|c| c.lock()) {
+        cached.take();
+    }
+    let Some(previous) = previous else { return Ok(None) };
+    let model_path = previous.backend_info().model_path;
+    let unloaded_path = model_path.clone();
+    release_when_idle(Some(previous), move || {
+        info!("Model {} unloaded", unloaded_path);
+        if let Some(listener) = MODEL_UNLOAD_LISTENER.get() {
+            listener(ModelUnloadedEvent { model_path: unloaded_path });
+        }
+    });
+    Ok(Some(model_path))
+}
+
+/// On a background thread, wait until no request holds `old` any more, drop it, then run `then`.
+fn release_when_idle<F>(old: Option<Arc<WhisperService>>, then: F)
+where
+    F: FnOnce() + Send + 'static,
+{
+    std::thread::spawn(move || {
+        if let Some(old) = old {
+            while Arc::strong_count(&old) > 1 {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+            drop(old);
+        }
+        then();
+    });
+}
+
+/// The currently loaded service, if any.
+pub fn current_service() -> Option<Arc<WhisperService>> {
+    service_holder().read().ok().and_then(|r| r.clone())
+}
+
 pub fn is_model_loaded() -> bool {
+    #[cfg(feature = "mock-transcription")]
+    if mock_backend::from_env().is_some() {
+        return true;
+    }
     let holder = service_holder();
     {
         if let Ok(r) = holder.read() {
-            return r.is_some();
+            // An idle-unloaded model counts: the next transcription reloads it.
+            return r.is_some() || idle_unloaded().lock().map(|s| s.is_some()).unwrap_or(false);
         }
     }
     false
 }
 
+/// Fake backend for UI development and CI: answers every request with fixed text
+/// after a delay, so no model has to be downloaded.
+#[cfg(feature = "mock-transcription")]
+mod mock_backend {
+    use std::time::Duration;
+
+    const DEFAULT_TEXT: &str = "This is a mock transcription.";
+    const DEFAULT_DELAY_MS: u64 = 300;
+
+    pub struct MockBackend {
+        pub text: String,
+        pub delay: Duration,
+    }
+
+    /// Enabled when `COMMANDER_MOCK_TRANSCRIPTION` is set; its value is the canned text.
+    pub fn from_env() -> Option<MockBackend> {
+        let text = std::env::var("COMMANDER_MOCK_TRANSCRIPTION").ok()?;
+        let text = if text.trim().is_empty() { DEFAULT_TEXT.to_string() } else { text };
+        let delay_ms = std::env::var("COMMANDER_MOCK_TRANSCRIPTION_DELAY_MS")
+            .ok()
+            .and_then(|v| v.parse::<u64>().ok())
+            .unwrap_or(DEFAULT_DELAY_MS);
+        Some(MockBackend { text, delay: Duration::from_millis(delay_ms) })
+    }
+}
+
+/// Look up a non-empty query parameter and percent-decode its value.
+pub(crate) fn query_param(query: Option<&str>, key: &str) -> Option<String> {
+    query?.split('&').find_map(|pair| {
+        let mut it = pair.splitn(2, '=');
+        match (it.next(), it.next()) {
+            (Some(k), Some(val)) if k == key && !val.is_empty() => {
+                Some(percent_encoding::percent_decode_str(val).decode_utf8_lossy().to_string())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Boolean query flag: `1`/`true`/`yes` enable it, anything else (or absence) disables.
+fn query_flag(query: Option<&str>, key: &str) -> bool {
+    query_param(query, key)
+        .map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+        .unwrap_or(false)
+}
+
+/// Duration of a WAV payload from its header, 0 when unparseable.
+fn wav_duration_ms(bytes: &[u8]) -> u64 {
+    hound::WavReader::new(std::io::Cursor::new(bytes))
+        .map(|r| r.duration() as u64 * 1000 / r.spec().sample_rate.max(1) as u64)
+        .unwrap_or(0)
+}
+
+/// Every value of a repeated query parameter, in order.
+fn query_params(query: Option<&str>, key: &str) -> Vec<String> {
+    let Some(query) = query else { return Vec::new() };
+    query
+        .split('&')
+        .filter_map(|pair| {
+            let mut it = pair.splitn(2, '=');
+            match (it.next(), it.next()) {
+                (Some(k), Some(val)) if k == key && !val.is_empty() => {
+                    Some(percent_encoding::percent_decode_str(val).decode_utf8_lossy().to_string())
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Tri-state boolean query parameter: `None` when absent, so the model default applies.
+pub(crate) fn query_bool(query: Option<&str>, key: &str) -> Option<bool> {
+    query_param(query, key).map(|v| matches!(v.to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+}
+
+fn query_u32(query: Option<&str>, key: &str) -> Option<u32> {
+    query_param(query, key).and_then(|v| v.parse::<u32>().ok()).filter(|v| *v > 0)
+}
+
+fn query_f32(query: Option<&str>, key: &str) -> Option<f32> {
+    query_param(query, key).and_then(|v| v.parse::<f32>().ok()).filter(|v| v.is_finite())
+}
+
 async fn handle(
     holder: Arc<RwLock<Option<Arc<WhisperService>>>>,
     req: Request<Body>,
+    request_id: u64,
+    peer: SocketAddr,
 ) -> Result<Response<Body>, Response<Body>> {
     if req.method() == Method::POST && req.uri().path() == "/transcribe" {
+        if let Err(retry_after) = check_rate_limit(&client_key(&peer), is_app_client(&req)) {
+            let body = serde_json::json!({
+                "request_id": request_id,
+                "error": "rate_limited",
+                "message": "Too many transcription requests from this client.",
+                "retry_after_secs": retry_after,
+            }).to_string();
+            let mut resp = Response::new(Body::from(body));
+            *resp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+            resp.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("application/json"),
+            );
+            if let Ok(v) = hyper::header::HeaderValue::from_str(&retry_after.to_string()) {
+                resp.headers_mut().insert(hyper::header::RETRY_AFTER, v);
+            }
+            return Err(resp);
+        }
+        #[cfg(feature = "mock-transcription")]
+        if let Some(mock) = mock_backend::from_env() {
+            let t_start = Instant::now();
+            let _ = to_bytes(req.into_body()).await;
+            tokio::time::sleep(mock.delay).await;
+            let body = serde_json::json!({
+                "request_id": request_id,
+                "text": mock.text,
+                "profile": { "server": { "mock": true, "total_ms": t_start.elapsed().as_millis() } }
+            }).to_string();
+            let mut resp = Response::new(Body::from(body));
+            resp.headers_mut().insert(
+                hyper::header::CONTENT_TYPE,
+                hyper::header::HeaderValue::from_static("application/json"),
+            );
+            return Ok(resp);
+        }
         // Require model to be loaded
         let svc = {
             let guard = holder.read().map_err(|_| {
@@ -49,10 +659,16 @@ async fn handle(
             })?;
             guard.clone()
         };
+        let svc = match svc {
+            Some(s) => Some(s),
+            None => tokio::task::spawn_blocking(reload_after_idle).await.ok().flatten(),
+        };
+        touch_model();
         let svc = match svc {
             Some(s) => s,
             None => {
                 let body = serde_json::json!({
+                    "request_id": request_id,
                     "error": "model_not_loaded",
                     "message": "No Whisper model is loaded. Please download and select a model.",
                 }).to_string();
@@ -78,38 +694,65 @@ async fn handle(
             return Err(resp);
         }
 
-        // Read optional language from query string: /transcribe?lang=en
-        let language: Option<String> = req
-            .uri()
-            .query()
-            .and_then(|q| {
-                // simple parse for lang=...
-                q.split('&')
-                    .find_map(|pair| {
-                        let mut it = pair.splitn(2, '=');
-                        match (it.next(), it.next()) {
-                            (Some("lang"), Some(val)) if !val.is_empty() => Some(val.to_string()),
-                            _ => None,
-                        }
-                    })
-            });
-        // Read optional initial prompt from query string: /transcribe?prompt=...
-        let initial_prompt: Option<String> = req
-            .uri()
-            .query()
-            .and_then(|q| {
-                q.split('&')
-                    .find_map(|pair| {
-                        let mut it = pair.splitn(2, '=');
-                        match (it.next(), it.next()) {
-                            (Some("prompt"), Some(val)) if !val.is_empty() => {
-                                // Keep as-is (already percent-encoded by client)
-                                Some(percent_encoding::percent_decode_str(val).decode_utf8_lossy().to_string())
-                            }
-                            _ => None,
-                        }
-                    })
-            });
+        // Optional query params: /transcribe?lang=en&prompt=...&timestamps=1&patience=1.0&model=<id>&translate=1
+        let query = req.uri().query();
+        let svc = match query_param(query, "model") {
+            Some(id) => {
+                // Loading another model costs seconds and gigabytes, so only local tools holding the token may ask.
+                require_app_client(&req, request_id)?;
+                let loaded = tokio::task::spawn_blocking(move || override_service(&id))
+                    .await
+                    .unwrap_or_else(|e| Err(e.to_string()));
+                match loaded {
+                    Ok(s) => s,
+                    Err(e) => {
+                        let body = serde_json::json!({ "request_id": request_id, "error": "model_unavailable", "message": e }).to_string();
+                        let mut resp = Response::new(Body::from(body));
+                        *resp.status_mut() = StatusCode::BAD_REQUEST;
+                        resp.headers_mut().insert(
+                            hyper::header::CONTENT_TYPE,
+                            hyper::header::HeaderValue::from_static("application/json"),
+                        );
+                        return Err(resp);
+                    }
+                }
+            }
+            None => svc,
+        };
+        // Commander's own pipeline registers its job and passes the id; other clients get one here,
+        // so they cannot attach progress to, or finish, a dictation's job.
+        let client_job_id = req
+            .headers()
+            .get("x-job-id")
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<u64>().ok())
+            .filter(|_| is_app_client(&req));
+        let mut opts = TranscribeOptions {
+            job_id: client_job_id,
+            language: query_param(query, "lang"),
+            initial_prompt: query_param(query, "prompt"),
+            inline_timestamps: query_flag(query, "timestamps"),
+            patience: query_f32(query, "patience"),
+            entropy_threshold: query_f32(query, "entropy_thold"),
+            logprob_threshold: query_f32(query, "logprob_thold"),
+            greedy: query_param(query, "strategy").as_deref() == Some("greedy"),
+            beam_size: query_u32(query, "beam_size"),
+            best_of: query_u32(query, "best_of"),
+            temperature: query_f32(query, "temperature"),
+            suppress_blank: query_bool(query, "suppress_blank"),
+            suppress_non_speech_tokens: query_bool(query, "suppress_nst"),
+            no_speech_threshold: query_f32(query, "no_speech_thold"),
+            suppressed_tokens: query_params(query, "suppress"),
+            translate: query_flag(query, "translate"),
+        };
+        // whisper.cpp allocates a decoder per candidate, so an unbounded count can exhaust memory.
+        if opts.beam_size.into_iter().chain(opts.best_of).any(|n| n > MAX_DECODING_CANDIDATES) {
+            return json_response(StatusCode::BAD_REQUEST, serde_json::json!({
+                "request_id": request_id,
+                "error": "invalid_decoding",
+                "message": format!("beam_size and best_of must be between 1 and {}", MAX_DECODING_CANDIDATES),
+            }));
+        }
         let t_req_total = Instant::now();
         let t_read_start = Instant::now();
         let body_bytes = to_bytes(req.into_body()).await.map_err(|e| {
@@ -118,22 +761,39 @@ async fn handle(
             resp
         })?;
         let read_body_ms = t_read_start.elapsed().as_millis();
+        let server_job_id = match client_job_id {
+            Some(_) => None,
+            None => Some(start_job(JobSource::Http, wav_duration_ms(&body_bytes), None, None)),
+        };
+        if server_job_id.is_some() {
+            opts.job_id = server_job_id;
+        }
 
         let t_transcribe_start = Instant::now();
-        let (text, timings) = svc
-            .transcribe_wav_bytes_profiled(&body_bytes, language.as_deref(), initial_prompt.as_deref())
+        let (transcript, timings) = svc
+            .transcribe_wav_bytes_profiled(&body_bytes, &opts)
             .map_err(|e| {
+                warn!("[req {}] transcription error: {}", request_id, e);
+                if let Some(id) = server_job_id { fail_job(id, e.to_string()); }
                 let mut resp = Response::new(Body::from(format!("transcription error: {}", e)));
                 *resp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
                 resp
             })?;
         let transcribe_ms = t_transcribe_start.elapsed().as_millis();
+        touch_model();
+        if let Some(id) = server_job_id { complete_job(id); }
 
         let total_req_ms = t_req_total.elapsed().as_millis();
 
         let backend = svc.backend_info();
         let body = serde_json::json!({
-            "text": text,
+            "request_id": request_id,
+            "job_id": opts.job_id,
+            "text": transcript.text,
+            "words": transcript.words,
+            "segments": transcript.segments,
+            "detected_language": transcript.detected_language,
+            "confidence": transcript.confidence,
             "profile": {
                 "server": {
                     "read_body_ms": read_body_ms,
@@ -152,13 +812,101 @@ async fn handle(
         return Ok(resp);
     }
 
+    if req.method() == Method::POST && req.uri().path() == "/models/unload" {
+        require_app_client(&req, request_id)?;
+        return match tokio::task::spawn_blocking(unload_model).await.map_err(anyhow::Error::from).and_then(|r| r) {
+            Ok(unloaded) => json_response(StatusCode::OK, serde_json::json!({ "request_id": request_id, "unloaded": unloaded })),
+            Err(e) => json_response(StatusCode::INTERNAL_SERVER_ERROR, serde_json::json!({
+                "request_id": request_id,
+                "error": "unload_failed",
+                "message": e.to_string(),
+            })),
+        };
+    }
+
+    if req.uri().path().starts_with("/editors") {
+        // A registration decides where dictated text is sent, so only local tools holding the token may change it.
+        require_app_client(&req, request_id)?;
+        return handle_editors(req, request_id).await;
+    }
+
+    if req.method() == Method::GET && req.uri().path() == "/last" {
+        // A DNS-rebound page reads same-origin GETs without an Origin header, so the token is what guards dictated text.
+        require_app_client(&req, request_id)?;
+        return match last_transcription() {
+            Some(last) => json_response(StatusCode::OK, serde_json::json!({ "request_id": request_id, "last": last })),
+            None => json_response(StatusCode::NOT_FOUND, serde_json::json!({
+                "request_id": request_id,
+                "error": "no_transcription",
+                "message": "Nothing has been transcribed yet.",
+            })),
+        };
+    }
+
     let mut resp = Response::new(Body::from("Not Found"));
     *resp.status_mut() = StatusCode::NOT_FOUND;
     Err(resp)
 }
 
+fn json_response(status: StatusCode, body: serde_json::Value) -> Result<Response<Body>, Response<Body>> {
+    let mut resp = Response::new(Body::from(body.to_string()));
+    *resp.status_mut() = status;
+    resp.headers_mut().insert(
+        hyper::header::CONTENT_TYPE,
+        hyper::header::HeaderValue::from_static("application/json"),
+    );
+    if status.is_success() { Ok(resp) } else { Err(resp) }
+}
+
+/// Editor plugin registry: `POST /editors/register`, `GET /editors`, `DELETE /editors/<id>`.
+async fn handle_editors(req: Request<Body>, request_id: u64) -> Result<Response<Body>, Response<Body>> {
+    let path = req.uri().path().trim_end_matches('/').to_string();
+    match (req.method().clone(), path.as_str()) {
+        (Method::POST, "/editors/register") => {
+            let bytes = to_bytes(req.into_body()).await.map_err(|e| {
+                let mut resp = Response::new(Body::from(format!("failed to read body: {}", e)));
+                *resp.status_mut() = StatusCode::BAD_REQUEST;
+                resp
+            })?;
+            let registration = serde_json::from_slice::<EditorRegistrationRequest>(&bytes)
+                .map_err(|e| anyhow::anyhow!("invalid registration: {}", e))
+                .and_then(register_editor);
+            match registration {
+                Ok(editor) => json_response(StatusCode::OK, serde_json::json!({ "request_id": request_id, "editor": editor })),
+                Err(e) => json_response(StatusCode::BAD_REQUEST, serde_json::json!({
+                    "request_id": request_id,
+                    "error": "invalid_registration",
+                    "message": e.to_string(),
+                })),
+            }
+        }
+        (Method::GET, "/editors") => {
+            json_response(StatusCode::OK, serde_json::json!({ "request_id": request_id, "editors": registered_editors() }))
+        }
+        (Method::DELETE, p) => match p.strip_prefix("/editors/").and_then(|id| id.parse::<u64>().ok()) {
+            Some(id) if unregister_editor(id) => json_response(StatusCode::OK, serde_json::json!({ "request_id": request_id, "removed": id })),
+            _ => json_response(StatusCode::NOT_FOUND, serde_json::json!({ "request_id": request_id, "error": "unknown_editor" })),
+        },
+        _ => {
+            let mut resp = Response::new(Body::from("Not Found"));
+            *resp.status_mut() = StatusCode::NOT_FOUND;
+            Err(resp)
+        }
+    }
+}
+
+static SERVER_BIND: OnceLock<std::result::Result<SocketAddr, String>> = OnceLock::new();
+
+/// Outcome of binding the local server: the address it listens on, or why it could not start.
+pub fn server_bind_status() -> Option<std::result::Result<SocketAddr, String>> {
+    SERVER_BIND.get().cloned()
+}
+
+/// Start the local server on a background thread. Returns once the socket is bound,
+/// so a taken port is reported to the caller instead of only being logged.
 pub fn start_whisper_server(bind_addr: String) -> Result<()> {
     let holder = service_holder();
+    let (bound_tx, bound_rx) = std::sync::mpsc::channel::<std::result::Result<SocketAddr, String>>();
 
     std::thread::spawn(move || {
         let rt = match tokio::runtime::Builder::new_multi_thread()
@@ -167,7 +915,7 @@ pub fn start_whisper_server(bind_addr: String) -> Result<()> {
         {
             Ok(rt) => rt,
             Err(e) => {
-                eprintln!("failed to build tokio runtime: {}", e);
+                let _ = bound_tx.send(Err(format!("failed to build tokio runtime: {}", e)));
                 return;
             }
         };
@@ -176,37 +924,102 @@ pub fn start_whisper_server(bind_addr: String) -> Result<()> {
             let addr: SocketAddr = match bind_addr.parse() {
                 Ok(a) => a,
                 Err(e) => {
-                    eprintln!("invalid bind addr {}: {}", bind_addr, e);
+                    let _ = bound_tx.send(Err(format!("invalid bind addr {}: {}", bind_addr, e)));
                     return;
                 }
             };
 
             let make_svc = {
                 let holder = holder.clone();
-                make_service_fn(move |_conn| {
+                make_service_fn(move |conn: &AddrStream| {
                     let holder = holder.clone();
+                    let peer = conn.remote_addr();
                     async move {
-                        Ok::<_, Infallible>(service_fn(move |req| {
+                        Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
                             let holder = holder.clone();
                             async move {
-                                match handle(holder, req).await {
-                                    Ok(resp) => Ok::<_, Infallible>(resp),
-                                    Err(resp) => Ok::<_, Infallible>(resp),
+                                let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+                                let started = Instant::now();
+                                let timestamp_ms = SystemTime::now()
+                                    .duration_since(SystemTime::UNIX_EPOCH)
+                                    .unwrap_or_default()
+                                    .as_millis();
+                                let method = req.method().to_string();
+                                let path = req.uri().path().to_string();
+                                let mut resp = match handle(holder, req, request_id, peer).await {
+                                    Ok(resp) => resp,
+                                    Err(resp) => resp,
+                                };
+                                if let Ok(v) = hyper::header::HeaderValue::from_str(&request_id.to_string()) {
+                                    resp.headers_mut().insert("x-request-id", v);
                                 }
+                                record_access(AccessLogEntry {
+                                    request_id,
+                                    timestamp_ms,
+                                    method,
+                                    path,
+                                    peer: Some(peer.to_string()),
+                                    status: resp.status().as_u16(),
+                                    duration_ms: started.elapsed().as_millis(),
+                                });
+                                Ok::<_, Infallible>(resp)
                             }
                         }))
                     }
                 })
             };
 
-            let server = Server::bind(&addr).serve(make_svc);
-            if let Err(e) = server.await {
+            let builder = match Server::try_bind(&addr) {
+                Ok(b) => b,
+                Err(e) => {
+                    let _ = bound_tx.send(Err(format!("failed to bind {}: {}", addr, e)));
+                    return;
+                }
+            };
+            let _ = bound_tx.send(Ok(addr));
+            if let Err(e) = builder.serve(make_svc).await {
                 eprintln!("hyper server error: {}", e);
             }
         });
     });
 
-    Ok(())
+    let status = bound_rx.recv().unwrap_or_else(|_| Err("server thread exited before binding".to_string()));
+    let _ = SERVER_BIND.set(status.clone());
+    status.map(|addr| info!("Whisper server listening on {}", addr)).map_err(|e| anyhow::anyhow!(e))
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn token_bucket_refuses_when_empty_and_refills_over_time() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket { tokens: 2.0, last_refill: start };
+        assert_eq!(bucket.take(2.0, 0.5, start), Ok(()));
+        assert_eq!(bucket.take(2.0, 0.5, start), Ok(()));
+        assert_eq!(bucket.take(2.0, 0.5, start), Err(2));
+        assert_eq!(bucket.take(2.0, 0.5, start + Duration::from_secs(1)), Err(1));
+        assert_eq!(bucket.take(2.0, 0.5, start + Duration::from_secs(2)), Ok(()));
+    }
+
+    #[test]
+    fn token_bucket_never_exceeds_capacity() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket { tokens: 0.0, last_refill: start };
+        let later = start + Duration::from_secs(3600);
+        assert_eq!(bucket.take(3.0, 1.0, later), Ok(()));
+        assert!((bucket.tokens - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn moved_path_rebases_only_paths_under_the_old_dir() {
+        let from = std::path::Path::new("/old/models");
+        let to = std::path::Path::new("/new/models");
+        assert_eq!(moved_path("/old/models/ggml-base.bin", from, to).as_deref(), Some("/new/models/ggml-base.bin"));
+        assert_eq!(moved_path("/elsewhere/ggml-base.bin", from, to), None);
+    }
+}
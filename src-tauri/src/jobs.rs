@@ -0,0 +1,201 @@
+use crate::whisper_service::LanguageProbability;
+use std::collections::VecDeque;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, SystemTime};
+
+/// Finished jobs kept for `recent_jobs`.
+const JOB_HISTORY_CAPACITY: usize = 100;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobSource {
+    Shortcut,
+    Tray,
+    Command,
+    SavedRecording,
+    Http,
+    /// Recording ended by voice-activity auto-stop.
+    AutoStop,
+    /// Recording stopped at its maximum duration.
+    RecordingLimit,
+    /// Toggle-mode recording stopped at the end of its time box.
+    TimeBox,
+    /// A file picked up from the watch folder.
+    WatchFolder,
+    /// A recording uploaded by a paired phone.
+    Companion,
+}
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobState {
+    Running,
+    Completed,
+    Failed,
+}
+
+/// Wall-clock time covered by a job's audio, in ms since the Unix epoch, so segment offsets
+/// can be anchored to real time. Time spent paused is not in the audio but is in the span.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordingSpan {
+    pub started_at_ms: u64,
+    pub ended_at_ms: u64,
+}
+
+impl RecordingSpan {
+    /// From `started` until now.
+    pub fn since(started: SystemTime) -> Self {
+        Self { started_at_ms: epoch_ms(started), ended_at_ms: epoch_ms(SystemTime::now()) }
+    }
+
+    /// The `audio_ms` that ended just now.
+    pub fn ending_now(audio_ms: u64) -> Self {
+        Self::since(SystemTime::now() - Duration::from_millis(audio_ms))
+    }
+}
+
+fn epoch_ms(t: SystemTime) -> u64 {
+    t.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TranscriptionJob {
+    pub id: u64,
+    pub source: JobSource,
+    pub state: JobState,
+    pub audio_ms: u64,
+    pub eta_ms: Option<u64>,
+    pub started_at_ms: u128,
+    pub finished_at_ms: Option<u128>,
+    pub error: Option<String>,
+    /// When the audio was recorded; `None` for files and uploads of unknown origin.
+    pub recording: Option<RecordingSpan>,
+    /// Language whisper detected in the audio, for jobs transcribed with `auto`.
+    pub detected_language: Option<LanguageProbability>,
+    /// Text of a job that failed the quality check, so it is not lost with the failure.
+    pub rejected_text: Option<String>,
+}
+
+struct JobRegistry {
+    next_id: u64,
+    jobs: VecDeque<TranscriptionJob>,
+}
+
+type JobListener = Box<dyn Fn(&'static str, &TranscriptionJob) + Send + Sync>;
+
+static REGISTRY: OnceLock<Mutex<JobRegistry>> = OnceLock::new();
+static JOB_LISTENER: OnceLock<JobListener> = OnceLock::new();
+
+fn registry() -> &'static Mutex<JobRegistry> {
+    REGISTRY.get_or_init(|| Mutex::new(JobRegistry { next_id: 1, jobs: VecDeque::new() }))
+}
+
+fn now_ms() -> u128 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis()
+}
+
+/// Register the receiver of job lifecycle events (set once at startup).
+pub fn set_job_listener<F>(listener: F)
+where
+    F: Fn(&'static str, &TranscriptionJob) + Send + Sync + 'static,
+{
+    let _ = JOB_LISTENER.set(Box::new(listener));
+}
+
+fn notify(event: &'static str, job: &TranscriptionJob) {
+    if let Some(listener) = JOB_LISTENER.get() {
+        listener(event, job);
+    }
+}
+
+/// Register a new running job and announce it as `transcription-start`.
+pub fn start_job(source: JobSource, audio_ms: u64, eta_ms: Option<u64>, recording: Option<RecordingSpan>) -> u64 {
+    start_reserved_job(reserve_job_id(), source, audio_ms, eta_ms, recording)
+}
+
+/// Take the next job id without registering a job, for work that runs before its job
+/// starts, such as the chunks of a streamed recording.
+pub fn reserve_job_id() -> u64 {
+    let Ok(mut reg) = registry().lock() else { return 0 };
+    let id = reg.next_id;
+    reg.next_id += 1;
+    id
+}
+
+/// `start_job` for an id taken earlier with `reserve_job_id`.
+pub fn start_reserved_job(id: u64, source: JobSource, audio_ms: u64, eta_ms: Option<u64>, recording: Option<RecordingSpan>) -> u64 {
+    let job = {
+        let Ok(mut reg) = registry().lock() else { return 0 };
+        let job = TranscriptionJob {
+            id,
+            source,
+            state: JobState::Running,
+            audio_ms,
+            eta_ms,
+            started_at_ms: now_ms(),
+            finished_at_ms: None,
+            error: None,
+            recording,
+            detected_language: None,
+            rejected_text: None,
+        };
+        if reg.jobs.len() == JOB_HISTORY_CAPACITY {
+            reg.jobs.pop_front();
+        }
+        reg.jobs.push_back(job.clone());
+        job
+    };
+    notify("transcription-start", &job);
+    job.id
+}
+
+fn finish_job(id: u64, state: JobState, error: Option<String>) -> Option<TranscriptionJob> {
+    let mut reg = registry().lock().ok()?;
+    let job = reg.jobs.iter_mut().find(|j| j.id == id)?;
+    job.state = state;
+    job.error = error;
+    job.finished_at_ms = Some(now_ms());
+    Some(job.clone())
+}
+
+/// Mark a job done and announce `transcription-complete`.
+pub fn complete_job(id: u64) {
+    if let Some(job) = finish_job(id, JobState::Completed, None) {
+        notify("transcription-complete", &job);
+    }
+}
+
+/// Mark a job failed and announce `transcription-failed`.
+pub fn fail_job(id: u64, error: String) {
+    if let Some(job) = finish_job(id, JobState::Failed, Some(error)) {
+        notify("transcription-failed", &job);
+    }
+}
+
+/// Record the language detected while transcribing job `id`.
+pub fn set_job_language(id: u64, language: LanguageProbability) {
+    let Ok(mut reg) = registry().lock() else { return };
+    if let Some(job) = reg.jobs.iter_mut().find(|j| j.id == id) {
+        job.detected_language = Some(language);
+    }
+}
+
+/// Keep the transcript job `id` is about to fail with, for the `transcription-failed` event.
+pub fn set_job_rejected_text(id: u64, text: String) {
+    let Ok(mut reg) = registry().lock() else { return };
+    if let Some(job) = reg.jobs.iter_mut().find(|j| j.id == id) {
+        job.rejected_text = Some(text);
+    }
+}
+
+pub fn get_job(id: u64) -> Option<TranscriptionJob> {
+    registry().lock().ok()?.jobs.iter().find(|j| j.id == id).cloned()
+}
+
+/// Known jobs, newest first.
+pub fn recent_jobs() -> Vec<TranscriptionJob> {
+    registry()
+        .lock()
+        .map(|reg| reg.jobs.iter().rev().cloned().collect())
+        .unwrap_or_default()
+}
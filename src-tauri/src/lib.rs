@@ -1,26 +1,49 @@
 // use anyhow::Context;
 use log::{error, info, warn};
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
 use tauri::path::BaseDirectory;
 use tauri::{Emitter, Manager};
-use tauri::image::Image;
-use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
 // use cpal::traits::StreamTrait;
+mod actions;
+mod announce;
+mod companion;
 mod http_server;
 mod whisper_service;
 mod audio;
+mod system_audio;
 mod config;
 mod tray;
 mod transcription;
 mod platform;
 mod commands;
-use crate::audio::{start_audio_controller, RecorderState};
-use crate::config::{is_hold_to_record_enabled, ShortcutConfig};
-use crate::tray::{build_tray, make_recording_icon, make_transcribing_icon};
-use crate::transcription::transcribe_and_copy;
-use crate::config::{read_model_config, read_audio_input_config};
-use crate::http_server::{start_whisper_server, load_model};
+mod recording;
+mod perf_stats;
+mod jobs;
+mod sinks;
+mod editors;
+mod shortcuts;
+mod vad;
+mod quality;
+mod phrases;
+mod processing;
+mod sessions;
+mod streaming;
+mod watch_folder;
+mod health;
+use crate::audio::{set_audio_frames_subscribed, set_audio_level_options, start_audio_controller, sweep_recordings, RecorderState};
+use crate::announce::announce;
+use crate::companion::apply_companion_config;
+use crate::tray::build_tray;
+use crate::recording::toggle_recording;
+use crate::shortcuts::{dispatch, register_shortcuts, ShortcutBindings};
+use crate::sinks::{LastTranscript, PendingPasteReview};
+use crate::watch_folder::start_watch_folder;
+use crate::health::report_health;
+use crate::config::{read_model_config, input_device_candidates, read_meeting_mode_config, read_audio_levels_config, read_recording_retention_config, read_pre_roll_config, read_retroactive_config, read_rate_limit_config, read_model_idle_config, read_inference_config, models_dir, write_client_token};
+use crate::http_server::{client_token, start_whisper_server, load_model, set_model_change_listener, set_model_resolver, set_dtw_preset_lookup, set_model_unload_listener, set_model_loading_listener, set_idle_unload, set_inference, set_rate_limit};
+use crate::whisper_service::set_progress_listener;
+use crate::transcription::{AppendBuffer, PendingImprovedTranscript};
+use crate::jobs::{set_job_listener, JobSource};
 
 #[allow(dead_code)]
 // commands moved to `commands` module
@@ -69,20 +92,31 @@ use crate::http_server::{start_whisper_server, load_model};
 pub fn run() {
     let recorder = Arc::new(Mutex::new(RecorderState::new()));
     
-    // Default shortcuts as fallback when custom shortcut fails
-    let shortcuts = if cfg!(target_os = "macos") {
-        vec![
-            Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::F9),
-        ]
-    } else {
-        vec![
-            Shortcut::new(Some(Modifiers::CONTROL | Modifiers::SHIFT), Code::F9),
-        ]
-    };
     let recorder_for_stream = recorder.clone();
 
     tauri::Builder::default()
+        // Must be the first plugin: a second launch hands its arguments to this
+        // instance and exits before registering shortcuts or a tray icon.
+        .plugin(tauri_plugin_single_instance::init({
+            let recorder = recorder.clone();
+            move |app, argv, _cwd| {
+                info!("Second instance launched with args: {:?}", argv);
+                if argv.iter().any(|a| a == "--toggle-recording") {
+                    // Off the main thread: starting a recording waits for the microphone.
+                    let (app, recorder) = (app.clone(), recorder.clone());
+                    std::thread::spawn(move || { toggle_recording(&app, &recorder, JobSource::Command); });
+                } else if let Some(win) = app.get_webview_window("main") {
+                    let _ = win.show();
+                    let _ = win.set_focus();
+                }
+            }
+        }))
         .manage(recorder.clone())
+        .manage(AppendBuffer::default())
+        .manage(LastTranscript::default())
+        .manage(PendingPasteReview::default())
+        .manage(PendingImprovedTranscript::default())
+        .manage(ShortcutBindings::default())
         .plugin(
             tauri_plugin_log::Builder::new()
                 .clear_targets()
@@ -100,33 +134,159 @@ pub fn run() {
         
         .invoke_handler(tauri::generate_handler![
             commands::toggle_recording,
+            commands::toggle_pause,
+            commands::stop_and_transcribe,
+            commands::get_recording_blocklist,
+            commands::save_recording_blocklist,
             commands::get_current_shortcut,
             commands::save_custom_shortcut,
             commands::get_default_language,
             commands::save_default_language,
+            commands::get_input_source_language_enabled,
+            commands::save_input_source_language_enabled,
             commands::get_default_prompt,
             commands::save_default_prompt,
+            commands::validate_prompt,
             commands::get_auto_paste_enabled,
             commands::save_auto_paste_enabled,
+            commands::get_auto_paste_timing,
+            commands::save_auto_paste_timing,
+            commands::get_auto_paste_rules,
+            commands::set_auto_paste_rule,
+            commands::remove_auto_paste_rule,
+            commands::get_frontmost_app,
             commands::get_hold_to_record_enabled,
             commands::save_hold_to_record_enabled,
+            commands::get_hold_to_record_config,
+            commands::save_hold_to_record_config,
+            commands::get_inline_timestamps_enabled,
+            commands::save_inline_timestamps_enabled,
             commands::get_models_status,
             commands::download_model,
             commands::select_model,
+            commands::unload_model,
             commands::list_audio_input_devices,
+            commands::list_input_stream_formats,
+            commands::list_input_device_capabilities,
+            commands::get_input_formats,
+            commands::save_input_format,
             commands::get_selected_audio_input_device,
             commands::save_selected_audio_input_device,
+            commands::get_fallback_audio_input_devices,
+            commands::save_fallback_audio_input_devices,
+            commands::get_follow_system_default,
+            commands::save_follow_system_default,
             commands::apply_selected_audio_input_device,
+            commands::get_storage_locations,
+            commands::save_storage_locations,
+            commands::get_recent_http_requests,
+            commands::transcribe_saved_recording,
+            commands::list_recordings,
+            commands::delete_recording,
+            commands::play_recording,
+            commands::get_recording_retention,
+            commands::save_recording_retention,
+            commands::open_recordings_dir,
+            commands::clear_recordings,
+            commands::replay_last_recording,
+            commands::get_append_mode,
+            commands::save_append_mode,
+            commands::flush_append_buffer,
+            commands::list_transcription_jobs,
+            commands::get_transcription_job,
+            commands::get_profiles,
+            commands::save_profiles,
+            commands::set_active_profile,
+            commands::run_action,
+            commands::get_spoken_feedback,
+            commands::save_spoken_feedback,
+            commands::get_shortcut_bindings,
+            commands::save_shortcut_bindings,
+            commands::detect_language_from_last_recording,
+            commands::run_startup_health_check,
+            commands::get_model_perf_stats,
+            commands::reset_model_perf_stats,
+            commands::get_latency_budget,
+            commands::save_latency_budget,
+            commands::get_meeting_mode,
+            commands::save_meeting_mode,
+            commands::calibrate_noise_floor,
+            commands::test_microphone,
+            commands::get_noise_profiles,
+            commands::get_auto_stop,
+            commands::save_auto_stop,
+            commands::get_memory_usage,
+            commands::get_audio_levels,
+            commands::save_audio_levels,
+            commands::get_audio_pipeline,
+            commands::save_audio_pipeline,
+            commands::get_recording_limit,
+            commands::save_recording_limit,
+            commands::get_paste_review_config,
+            commands::save_paste_review_config,
+            commands::get_pending_paste_review,
+            commands::confirm_paste_review,
+            commands::discard_paste_review,
+            commands::get_pre_roll,
+            commands::save_pre_roll,
+            commands::get_retroactive,
+            commands::save_retroactive,
+            commands::get_watch_folder,
+            commands::save_watch_folder,
+            commands::set_watch_folder_enabled,
+            commands::get_quality_guard,
+            commands::save_quality_guard,
+            commands::list_phrases,
+            commands::add_phrase,
+            commands::update_phrase,
+            commands::remove_phrase,
+            commands::get_fast_preview,
+            commands::save_fast_preview,
+            commands::apply_improved_transcript,
+            commands::export_session,
+            commands::get_streaming,
+            commands::get_recording_state,
+            commands::continue_recording,
+            commands::get_time_box,
+            commands::save_time_box,
+            commands::get_arming_delay,
+            commands::save_arming_delay,
+            commands::get_media_pause,
+            commands::save_media_pause,
+            commands::get_companion_status,
+            commands::set_companion_enabled,
+            commands::reset_companion_pairing,
+            commands::subscribe_audio_frames,
+            commands::save_streaming,
+            commands::get_rate_limit_config,
+            commands::get_model_idle_config,
+            commands::save_model_idle_config,
+            commands::get_inference_config,
+            commands::save_inference_config,
+            commands::get_cpu_count,
+            commands::save_rate_limit_config,
+            commands::get_whisper_params,
+            commands::save_whisper_params,
+            commands::get_decoding_config,
+            commands::save_decoding_config,
             #[cfg(target_os = "macos")]
             commands::is_accessibility_trusted,
             #[cfg(target_os = "macos")]
-            commands::open_accessibility_settings
+            commands::open_accessibility_settings,
+            #[cfg(target_os = "macos")]
+            commands::is_microphone_authorized,
+            #[cfg(target_os = "macos")]
+            commands::get_microphone_status,
+            #[cfg(target_os = "macos")]
+            commands::request_microphone_access
         ])
         .on_window_event(|window, event| {
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
                 // Hide window instead of closing; keep app running in tray
                 api.prevent_close();
                 let _ = window.hide();
+                // Nobody draws the waveform while hidden; the window subscribes again when shown.
+                set_audio_frames_subscribed(false);
             }
         })
         .plugin(tauri_plugin_opener::init())
@@ -135,140 +295,7 @@ pub fn run() {
             tauri_plugin_global_shortcut::Builder::new()
                 .with_handler({
                     let recorder = recorder.clone();
-                    move |app, _shortcut, event| {
-                        let hold_mode = is_hold_to_record_enabled(&app);
-                        match (hold_mode, event.state()) {
-                            (true, ShortcutState::Pressed) => {
-                                // Start recording on press (if not already recording)
-                                let mut started = false;
-                                if let Ok(mut st) = recorder.lock() {
-                                    if !st.is_recording {
-                                        st.is_recording = true;
-                                        st.buffer.clear();
-                                        st.start_instant = Some(Instant::now());
-                                        started = true;
-                                    }
-                                }
-                                if started {
-                                    if let Some(tray) = app.tray_by_id("main-tray") {
-                                        let _ = tray.set_tooltip(Some("Recording…"));
-                                        let _ = tray.set_icon(Some(make_recording_icon()));
-                                    }
-                                    let _ = app.emit("recording-start", true);
-                                }
-                            }
-                            (true, ShortcutState::Released) => {
-                                // Stop recording on release and transcribe
-                                let mut data = Vec::<i16>::new();
-                                let mut should_transcribe = false;
-                                if let Ok(mut st) = recorder.lock() {
-                                    if st.is_recording {
-                                        st.is_recording = false;
-                                        std::mem::swap(&mut data, &mut st.buffer);
-                                        st.start_instant = None;
-                                        should_transcribe = true;
-                                    }
-                                }
-                                if should_transcribe {
-                                    if let Some(tray) = app.tray_by_id("main-tray") {
-                                        let _ = tray.set_tooltip(Some("Transcribing…"));
-                                        let _ = tray.set_icon(Some(make_transcribing_icon()));
-                                    }
-                                    let _ = app.emit("transcription-start", true);
-                                    let _ = app.emit("recording-stop", true);
-                                    let app2 = app.clone();
-                                    let default_icon_bytes = include_bytes!("../icons/commander-icon.png");
-                                    let default_icon = Image::from_bytes(default_icon_bytes).ok();
-                                    let sample_rate = recorder
-                                        .lock()
-                                        .ok()
-                                        .map(|s| s.sample_rate_hz)
-                                        .unwrap_or(16_000);
-                                    // Save WAV for debugging
-                                    let _ = crate::audio::save_recording_wav(&app2, &data, sample_rate)
-                                        .map(|p| println!("Saved recording: {}", p.display()));
-                                    std::thread::spawn(move || {
-                                        if let Err(err) = transcribe_and_copy(&app2, data, sample_rate) {
-                                            error!("transcription error: {err:?}");
-                                            if let Some(tray) = app2.tray_by_id("main-tray") {
-                                                let _ = tray.set_tooltip(Some("Transcription failed"));
-                                                if let Some(icon) = default_icon.clone() { let _ = tray.set_icon(Some(icon)); }
-                                            }
-                                            let _ = app2.emit("transcription-failed", true);
-                                            let app3 = app2.clone();
-                                            std::thread::spawn(move || {
-                                                std::thread::sleep(std::time::Duration::from_secs(2));
-                                                if let Some(tray) = app3.tray_by_id("main-tray") {
-                                                    let _ = tray.set_tooltip(Some("Commander"));
-                                                }
-                                            });
-                                        }
-                                    });
-                                }
-                            }
-                            (false, ShortcutState::Pressed) => {
-                                // Toggle behavior on press
-                                let maybe_wav = {
-                                    let mut data = Vec::<i16>::new();
-                                    if let Ok(mut st) = recorder.lock() {
-                                        if st.is_recording {
-                                            st.is_recording = false;
-                                            std::mem::swap(&mut data, &mut st.buffer);
-                                            st.start_instant = None;
-                                        } else {
-                                            st.is_recording = true;
-                                            st.buffer.clear();
-                                            st.start_instant = Some(Instant::now());
-                                        }
-                                    }
-                                    data
-                                };
-                                if maybe_wav.is_empty() {
-                                    if let Some(tray) = app.tray_by_id("main-tray") {
-                                        let _ = tray.set_tooltip(Some("Recording…"));
-                                        let _ = tray.set_icon(Some(make_recording_icon()));
-                                    }
-                                    let _ = app.emit("recording-start", true);
-                                } else {
-                                    if let Some(tray) = app.tray_by_id("main-tray") {
-                                        let _ = tray.set_tooltip(Some("Transcribing…"));
-                                        let _ = tray.set_icon(Some(make_transcribing_icon()));
-                                    }
-                                    let _ = app.emit("transcription-start", true);
-                                    let app2 = app.clone();
-                                    let default_icon_bytes = include_bytes!("../icons/commander-icon.png");
-                                    let default_icon = Image::from_bytes(default_icon_bytes).ok();
-                                    let sample_rate = recorder
-                                        .lock()
-                                        .ok()
-                                        .map(|s| s.sample_rate_hz)
-                                        .unwrap_or(16_000);
-                                    // Save WAV for debugging
-                                    let _ = crate::audio::save_recording_wav(&app2, &maybe_wav, sample_rate)
-                                        .map(|p| println!("Saved recording: {}", p.display()));
-                                    std::thread::spawn(move || {
-                                        if let Err(err) = transcribe_and_copy(&app2, maybe_wav, sample_rate) {
-                                            error!("transcription error: {err:?}");
-                                            if let Some(tray) = app2.tray_by_id("main-tray") {
-                                                let _ = tray.set_tooltip(Some("Transcription failed"));
-                                                if let Some(icon) = default_icon.clone() { let _ = tray.set_icon(Some(icon)); }
-                                            }
-                                            let _ = app2.emit("transcription-failed", true);
-                                            let app3 = app2.clone();
-                                            std::thread::spawn(move || {
-                                                std::thread::sleep(std::time::Duration::from_secs(2));
-                                                if let Some(tray) = app3.tray_by_id("main-tray") {
-                                                    let _ = tray.set_tooltip(Some("Commander"));
-                                                }
-                                            });
-                                        }
-                                    });
-                                    let _ = app.emit("recording-stop", true);
-                                }
-                            }
-                            (false, ShortcutState::Released) => {}
-                        }
-                    }
+                    move |app, shortcut, event| dispatch(app, &recorder, shortcut, event.state())
                 })
                 .build(),
         )
@@ -284,17 +311,12 @@ pub fn run() {
             {
                 let trusted = platform::is_accessibility_trusted();
                 let _ = app.emit("accessibility-status", serde_json::json!({"trusted": trusted}));
+                // The microphone now opens only when recording, so a missing grant would otherwise
+                // first show up as a silent or refused recording.
+                let mic = platform::microphone_authorization();
+                let _ = app.emit("microphone-status", serde_json::json!({"status": mic, "authorized": mic == platform::MicrophoneAuthorization::Authorized}));
             }
             
-            // Load saved shortcut configuration
-            let config_dir = app.path().app_config_dir().ok();
-            let saved_config = config_dir
-                .and_then(|dir| std::fs::read_to_string(dir.join("shortcut.json")).ok())
-                .and_then(|content| serde_json::from_str::<ShortcutConfig>(&content).ok())
-                .unwrap_or_default();
-            
-            info!("Loading shortcut configuration: {:?} + {}", saved_config.modifiers, saved_config.key);
-            
             // Force ggml to load metallib from our app resources dir to avoid mismatches
             // between crate-generated paths (e.g. target/debug) and the bundled metallib.
             let resources_dir = if cfg!(debug_assertions) {
@@ -356,22 +378,80 @@ pub fn run() {
                 });
                 let _ = app.emit("backend-status", backend);
             }
+            // Forward whisper inference progress to the UI
+            {
+                let app_handle = app.handle().clone();
+                set_progress_listener(move |progress| {
+                    let _ = app_handle.emit("transcription-progress", progress);
+                });
+            }
+            // Announce model swaps once in-flight requests on the old model have finished
+            {
+                let app_handle = app.handle().clone();
+                set_model_change_listener(move |event| {
+                    let _ = app_handle.emit("model-changed", event);
+                });
+            }
+            // Announce unloads once the model's memory has actually been released
+            {
+                let app_handle = app.handle().clone();
+                set_model_unload_listener(move |event| {
+                    let _ = app_handle.emit("model-unloaded", event);
+                });
+            }
+            // Show when a transcription has to wait for an idle-unloaded model to come back
+            {
+                let app_handle = app.handle().clone();
+                set_model_loading_listener(move |model_path| {
+                    if let Some(tray) = app_handle.tray_by_id("main-tray") {
+                        let _ = tray.set_tooltip(Some("Loading model…"));
+                    }
+                    let _ = app_handle.emit("model-loading", serde_json::json!({ "model_path": model_path }));
+                });
+            }
+            // Resolve per-request `model=<id>` overrides to installed model files
+            {
+                let app_handle = app.handle().clone();
+                set_model_resolver(move |id| commands::installed_model_path(&app_handle, id));
+            }
+            set_dtw_preset_lookup(commands::dtw_preset_for_path);
+            // Announce job lifecycle (transcription-start/complete/failed) to the UI
+            {
+                let app_handle = app.handle().clone();
+                set_job_listener(move |event, job| {
+                    // Background jobs (HTTP, watch folder, phone uploads) are not announced.
+                    if !matches!(job.source, JobSource::Http | JobSource::WatchFolder | JobSource::Companion) {
+                        match event {
+                            "transcription-complete" => announce(&app_handle, "Transcribed"),
+                            "transcription-failed" => announce(&app_handle, "Transcription failed"),
+                            _ => {}
+                        }
+                    }
+                    let _ = app_handle.emit(event, job);
+                });
+            }
             build_tray(app, recorder.clone())?;
             // Start local whisper server without model; load selected if present
             let bind_addr =
                 std::env::var("WHISPER_BIND_ADDR").unwrap_or_else(|_| "127.0.0.1:9000".to_string());
+            set_rate_limit(read_rate_limit_config(&app.app_handle()));
+            set_idle_unload(read_model_idle_config(&app.app_handle()));
+            set_inference(&read_inference_config(&app.app_handle()));
             if let Err(e) = start_whisper_server(bind_addr) {
                 error!("failed to start whisper server: {}", e);
             }
+            if let Err(e) = write_client_token(&app.app_handle(), client_token()) {
+                warn!("failed to save the HTTP client token: {}", e);
+            }
             // Attempt to load previously selected model from user data directory
             let app_handle = app.app_handle();
             if let Some(cfg) = read_model_config(&app_handle) {
                 if let Some(id) = cfg.selected_id {
                     // Map id -> filename
-                    if let Ok(models_dir) = app_handle.path().app_data_dir() {
+                    if let Ok(models_dir) = models_dir(&app_handle) {
                         let path = match id.as_str() {
-                            "large-v3-turbo" => models_dir.join("models").join("ggml-large-v3-turbo.bin"),
-                            "large-v3-turbo-q5_0" => models_dir.join("models").join("ggml-large-v3-turbo-q5_0.bin"),
+                            "large-v3-turbo" => models_dir.join("ggml-large-v3-turbo.bin"),
+                            "large-v3-turbo-q5_0" => models_dir.join("ggml-large-v3-turbo-q5_0.bin"),
                             _ => std::path::PathBuf::new(),
                         };
                         if path.exists() {
@@ -383,62 +463,33 @@ pub fn run() {
                 }
             }
             // Start audio controller thread that owns the CPAL stream
-            let preferred_device_name = read_audio_input_config(&app.app_handle()).and_then(|c| c.selected_device_name);
-            let controller = start_audio_controller(recorder_for_stream, app.app_handle().clone(), preferred_device_name);
+            let preferred_devices = input_device_candidates(&app.app_handle());
+            let levels = read_audio_levels_config(&app.app_handle());
+            set_audio_level_options(levels.interval_ms, levels.spectrum_bands);
+            if let Ok(mut st) = recorder_for_stream.lock() {
+                st.set_pre_roll_ms(read_pre_roll_config(&app.app_handle()).effective_ms());
+                st.set_retroactive_ms(read_retroactive_config(&app.app_handle()).effective_ms());
+            }
+            let loopback_device = read_meeting_mode_config(&app.app_handle()).active_loopback_device();
+            let controller = start_audio_controller(recorder_for_stream, app.app_handle().clone(), preferred_devices, loopback_device);
             app.manage(controller);
-            // Ensure only our desired shortcuts are registered
-            app.global_shortcut().unregister_all().ok();
-            
-            // Try to register the user's saved shortcut
-            match saved_config.to_shortcut() {
-                Ok(user_shortcut) => {
-                    match app.global_shortcut().register(user_shortcut.clone()) {
-                        Ok(_) => {
-                            info!("✅ User's custom shortcut registered successfully!");
-                            // Store the shortcut in app state for the handler
-                            app.manage(vec![user_shortcut]);
-                        }
-                        Err(e) => {
-                            error!("❌ Failed to register custom shortcut: {}", e);
-                            warn!("Falling back to default shortcuts...");
-                            
-                            // Fallback to default shortcuts
-                            let mut registered = false;
-                            for (idx, shortcut) in shortcuts.iter().enumerate() {
-                                if let Ok(_) = app.global_shortcut().register(shortcut.clone()) {
-                                    registered = true;
-                                    info!("✅ Fallback shortcut {} registered", idx + 1);
-                                    break;
-                                }
-                            }
-                            
-                            if !registered {
-                                error!("❌ Failed to register any shortcuts!");
-                                warn!("On macOS, ensure accessibility permissions are granted.");
-                            }
-                            app.manage(shortcuts);
-                        }
-                    }
-                }
-                Err(e) => {
-                    error!("❌ Invalid shortcut configuration: {}", e);
-                    warn!("Using default shortcuts...");
-                    
-                    // Use default shortcuts
-                    let mut registered_count = 0;
-                    for (idx, shortcut) in shortcuts.iter().enumerate() {
-                        if let Ok(_) = app.global_shortcut().register(shortcut.clone()) {
-                            registered_count += 1;
-                            info!("✅ Default shortcut {} registered", idx + 1);
-                        }
-                    }
-                    
-                    if registered_count == 0 {
-                        error!("❌ Failed to register any shortcuts!");
+            register_shortcuts(app.handle());
+            start_watch_folder(app.handle().clone());
+            if let Err(e) = apply_companion_config(app.handle()) {
+                warn!("failed to start companion server: {}", e);
+            }
+            // Apply the recording retention limits once per launch
+            {
+                let app_handle = app.handle().clone();
+                std::thread::spawn(move || {
+                    match sweep_recordings(&app_handle, &read_recording_retention_config(&app_handle)) {
+                        Ok(0) => {}
+                        Ok(n) => info!("Deleted {} old recordings", n),
+                        Err(e) => warn!("Recording retention sweep failed: {}", e),
                     }
-                    app.manage(shortcuts);
-                }
+                });
             }
+            report_health(app.handle());
             Ok(())
         })
         .run(tauri::generate_context!())
@@ -0,0 +1,210 @@
+use crate::whisper_service::ServiceTimings;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+use tauri::{AppHandle, Manager};
+
+/// Weight of the newest run in the moving averages.
+const RTF_SMOOTHING: f64 = 0.2;
+
+/// Moving averages of the server-side stages, in milliseconds.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct TimingBreakdown {
+    pub sample_to_mono_ms: f64,
+    pub resample_ms: f64,
+    pub create_state_ms: f64,
+    pub inference_ms: f64,
+    pub collect_segments_ms: f64,
+    pub total_ms: f64,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct ModelPerfStats {
+    pub runs: u64,
+    /// Processing time divided by audio duration (lower is faster).
+    pub avg_rtf: f64,
+    #[serde(default)]
+    pub total_audio_ms: u64,
+    #[serde(default)]
+    pub avg_timings: TimingBreakdown,
+    /// Stop-to-text latency as seen by the app, including HTTP and file I/O.
+    #[serde(default)]
+    pub avg_end_to_end_ms: f64,
+    #[serde(default)]
+    pub last_run_at_ms: Option<u128>,
+}
+
+fn smooth(avg: f64, value: f64, first: bool) -> f64 {
+    if first { value } else { avg * (1.0 - RTF_SMOOTHING) + value * RTF_SMOOTHING }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct PerfStatsStore {
+    pub models: HashMap<String, ModelPerfStats>,
+}
+
+fn stats_path(app: &AppHandle) -> Result<PathBuf> {
+    Ok(app.path().app_data_dir()?.join("perf_stats.json"))
+}
+
+pub fn read_perf_stats(app: &AppHandle) -> PerfStatsStore {
+    stats_path(app)
+        .ok()
+        .and_then(|p| std::fs::read_to_string(p).ok())
+        .and_then(|s| serde_json::from_str::<PerfStatsStore>(&s).ok())
+        .unwrap_or_default()
+}
+
+fn write_perf_stats(app: &AppHandle, store: &PerfStatsStore) -> Result<()> {
+    let path = stats_path(app)?;
+    if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+    std::fs::write(path, serde_json::to_string_pretty(store)?)?;
+    Ok(())
+}
+
+/// Fold a completed transcription into the model's moving averages.
+pub fn record_run(app: &AppHandle, model_id: &str, audio_ms: u64, timings: &ServiceTimings, end_to_end_ms: u64) -> Result<()> {
+    if audio_ms == 0 { return Ok(()); }
+    let rtf = timings.total_ms as f64 / audio_ms as f64;
+    let mut store = read_perf_stats(app);
+    let entry = store.models.entry(model_id.to_string()).or_default();
+    let first = entry.runs == 0;
+    entry.avg_rtf = smooth(entry.avg_rtf, rtf, first);
+    let avg = &mut entry.avg_timings;
+    avg.sample_to_mono_ms = smooth(avg.sample_to_mono_ms, timings.sample_to_mono_ms as f64, first);
+    avg.resample_ms = smooth(avg.resample_ms, timings.resample_ms as f64, first);
+    avg.create_state_ms = smooth(avg.create_state_ms, timings.create_state_ms as f64, first);
+    avg.inference_ms = smooth(avg.inference_ms, timings.inference_ms as f64, first);
+    avg.collect_segments_ms = smooth(avg.collect_segments_ms, timings.collect_segments_ms as f64, first);
+    avg.total_ms = smooth(avg.total_ms, timings.total_ms as f64, first);
+    entry.avg_end_to_end_ms = smooth(entry.avg_end_to_end_ms, end_to_end_ms as f64, first);
+    entry.total_audio_ms += audio_ms;
+    entry.last_run_at_ms = Some(SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis());
+    entry.runs += 1;
+    write_perf_stats(app, &store)
+}
+
+/// Forget the history of one model, or of all models when `model_id` is `None`.
+pub fn reset_perf_stats(app: &AppHandle, model_id: Option<&str>) -> Result<()> {
+    let mut store = read_perf_stats(app);
+    match model_id {
+        Some(id) => { store.models.remove(id); }
+        None => store.models.clear(),
+    }
+    write_perf_stats(app, &store)
+}
+
+/// Predict processing time for `audio_ms` of audio from the model's history, if any.
+pub fn estimate_processing_ms(app: &AppHandle, model_id: &str, audio_ms: u64) -> Option<u64> {
+    let store = read_perf_stats(app);
+    let stats = store.models.get(model_id)?;
+    if stats.runs == 0 { return None; }
+    Some((stats.avg_rtf * audio_ms as f64).round() as u64)
+}
+
+/// Time one stage of a dictation took, in milliseconds.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LatencyStage {
+    pub name: &'static str,
+    pub ms: u64,
+}
+
+/// Where the stop-to-text time of one dictation went, built from its timing profile.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LatencyReport {
+    pub job_id: u64,
+    pub total_ms: u64,
+    pub audio_ms: u64,
+    /// Stages in pipeline order. `other` is whatever the measured stages do not cover.
+    pub stages: Vec<LatencyStage>,
+    pub dominant_stage: &'static str,
+}
+
+/// Emitted as `latency-budget-exceeded` when a dictation takes longer than the target.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct LatencyBudgetExceeded {
+    #[serde(flatten)]
+    pub report: LatencyReport,
+    pub target_ms: u64,
+    /// What usually fixes a slow `dominant_stage`.
+    pub hint: Option<&'static str>,
+}
+
+fn profile_ms(value: &serde_json::Value) -> u64 {
+    value.as_u64().unwrap_or(0)
+}
+
+/// Split `total_ms` into stages using the client, server and whisper timings of `profile`.
+pub fn latency_report(job_id: u64, profile: &serde_json::Value, audio_ms: u64, total_ms: u64) -> LatencyReport {
+    let client = &profile["client"];
+    let server = &profile["server"]["server"];
+    let whisper = &profile["server"]["whisper"];
+    let retry = &profile["quality"]["retry"]["client"];
+    let http_ms = profile_ms(&client["http_ms"]);
+    let transcribe_ms = profile_ms(&server["transcribe_ms"]);
+    let mut stages = vec![
+        LatencyStage { name: "encoding", ms: profile_ms(&client["wav_write_ms"]) + profile_ms(&client["read_file_ms"]) },
+        LatencyStage { name: "transport", ms: http_ms.saturating_sub(transcribe_ms) },
+        // Time the request spent waiting for the model, e.g. behind another job.
+        LatencyStage { name: "queue", ms: transcribe_ms.saturating_sub(profile_ms(&whisper["total_ms"])) },
+        LatencyStage { name: "audio_prep", ms: profile_ms(&whisper["sample_to_mono_ms"]) + profile_ms(&whisper["resample_ms"]) },
+        LatencyStage { name: "model_setup", ms: profile_ms(&whisper["create_state_ms"]) },
+        LatencyStage { name: "inference", ms: profile_ms(&whisper["language_detect_ms"]) + profile_ms(&whisper["inference_ms"]) + profile_ms(&whisper["collect_segments_ms"]) },
+        LatencyStage { name: "retry", ms: profile_ms(&retry["wav_write_ms"]) + profile_ms(&retry["read_file_ms"]) + profile_ms(&retry["http_ms"]) },
+    ];
+    let measured: u64 = stages.iter().map(|s| s.ms).sum();
+    stages.push(LatencyStage { name: "other", ms: total_ms.saturating_sub(measured) });
+    let dominant_stage = stages.iter().max_by_key(|s| s.ms).map(|s| s.name).unwrap_or("other");
+    LatencyReport { job_id, total_ms, audio_ms, stages, dominant_stage }
+}
+
+pub fn latency_hint(stage: &str) -> Option<&'static str> {
+    match stage {
+        "inference" | "model_setup" | "audio_prep" => Some("Switch to a smaller or quantized model, or keep dictations shorter."),
+        "queue" => Some("Another transcription was running; wait for background jobs or turn off fast preview."),
+        "encoding" | "transport" => Some("Check free disk space and that nothing else is slowing down the local server."),
+        "retry" => Some("The quality guard retried the transcript; check the microphone level."),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stage_ms(report: &LatencyReport, name: &str) -> u64 {
+        report.stages.iter().find(|s| s.name == name).map(|s| s.ms).unwrap()
+    }
+
+    #[test]
+    fn latency_report_splits_the_profile_into_stages() {
+        let profile = serde_json::json!({
+            "client": { "wav_write_ms": 30, "read_file_ms": 10, "http_ms": 1200 },
+            "server": {
+                "server": { "transcribe_ms": 1100 },
+                "whisper": {
+                    "total_ms": 1000, "sample_to_mono_ms": 5, "resample_ms": 40, "create_state_ms": 60,
+                    "language_detect_ms": 50, "inference_ms": 800, "collect_segments_ms": 20
+                }
+            }
+        });
+        let report = latency_report(7, &profile, 5000, 1400);
+        assert_eq!(stage_ms(&report, "encoding"), 40);
+        assert_eq!(stage_ms(&report, "transport"), 100);
+        assert_eq!(stage_ms(&report, "queue"), 100);
+        assert_eq!(stage_ms(&report, "audio_prep"), 45);
+        assert_eq!(stage_ms(&report, "model_setup"), 60);
+        assert_eq!(stage_ms(&report, "inference"), 870);
+        assert_eq!(stage_ms(&report, "retry"), 0);
+        assert_eq!(stage_ms(&report, "other"), 185);
+        assert_eq!(report.dominant_stage, "inference");
+    }
+
+    #[test]
+    fn latency_report_puts_unmeasured_time_in_other() {
+        let report = latency_report(1, &serde_json::Value::Null, 1000, 900);
+        assert_eq!(stage_ms(&report, "other"), 900);
+        assert_eq!(report.dominant_stage, "other");
+    }
+}
@@ -0,0 +1,148 @@
+//! User phrase list (names, product terms) woven into the initial prompt so whisper
+//! spells domain vocabulary the way the user does.
+
+use crate::config::{read_phrase_list, write_phrase_list, Phrase};
+use crate::http_server::current_service;
+use crate::whisper_service::MAX_PROMPT_TOKENS;
+use anyhow::{anyhow, Result};
+use std::time::SystemTime;
+use tauri::AppHandle;
+
+fn now_ms() -> u128 {
+    SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis()
+}
+
+fn token_count(text: &str) -> usize {
+    current_service()
+        .and_then(|svc| svc.count_tokens(text).ok())
+        .unwrap_or_else(|| text.chars().count().div_ceil(4))
+}
+
+/// Phrases most recently heard in a transcript first, then the most used, then oldest added.
+fn by_priority(mut phrases: Vec<Phrase>) -> Vec<Phrase> {
+    phrases.sort_by(|a, b| {
+        b.last_used_ms.cmp(&a.last_used_ms).then(b.use_count.cmp(&a.use_count)).then(a.added_ms.cmp(&b.added_ms))
+    });
+    phrases
+}
+
+/// Prepend as many phrases as fit next to `prompt` within the prompt token budget, highest
+/// priority first. The user's own prompt is never shortened to make room.
+pub fn weave_into_prompt(app: &AppHandle, prompt: Option<String>) -> Option<String> {
+    let phrases = by_priority(read_phrase_list(app).phrases);
+    if phrases.is_empty() {
+        return prompt;
+    }
+    let base = prompt.unwrap_or_default();
+    let budget = MAX_PROMPT_TOKENS.saturating_sub(token_count(&base));
+    let mut chosen: Vec<&str> = Vec::new();
+    for phrase in &phrases {
+        chosen.push(&phrase.text);
+        if token_count(&format!("{}.", chosen.join(", "))) > budget {
+            chosen.pop();
+            break;
+        }
+    }
+    if chosen.is_empty() {
+        return (!base.is_empty()).then_some(base);
+    }
+    let glossary = format!("{}.", chosen.join(", "));
+    Some(if base.is_empty() { glossary } else { format!("{} {}", glossary, base) })
+}
+
+/// Whether `phrase` occurs in `text` as whole words, ignoring case, so "Al" does not match
+/// inside "also".
+fn mentions(text: &str, phrase: &str) -> bool {
+    let text = text.to_lowercase();
+    let phrase = phrase.to_lowercase();
+    if phrase.is_empty() { return false; }
+    text.match_indices(&phrase).any(|(start, _)| {
+        let before = text[..start].chars().next_back();
+        let after = text[start + phrase.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Mark every phrase that occurs in `text` as just used.
+pub fn record_usage(app: &AppHandle, text: &str) -> Result<()> {
+    let mut list = read_phrase_list(app);
+    let now = now_ms();
+    let mut changed = false;
+    for phrase in list.phrases.iter_mut().filter(|p| mentions(text, &p.text)) {
+        phrase.last_used_ms = now;
+        phrase.use_count += 1;
+        changed = true;
+    }
+    if changed { write_phrase_list(app, &list)?; }
+    Ok(())
+}
+
+pub fn list_phrases(app: &AppHandle) -> Vec<Phrase> {
+    by_priority(read_phrase_list(app).phrases)
+}
+
+pub fn add_phrase(app: &AppHandle, text: &str) -> Result<Vec<Phrase>> {
+    let text = text.trim();
+    if text.is_empty() { return Err(anyhow!("phrase is empty")); }
+    let mut list = read_phrase_list(app);
+    if list.phrases.iter().any(|p| p.text.eq_ignore_ascii_case(text)) {
+        return Err(anyhow!("\"{}\" is already in the list", text));
+    }
+    list.phrases.push(Phrase { text: text.to_string(), added_ms: now_ms(), last_used_ms: 0, use_count: 0 });
+    write_phrase_list(app, &list)?;
+    Ok(by_priority(list.phrases))
+}
+
+pub fn update_phrase(app: &AppHandle, old: &str, new: &str) -> Result<Vec<Phrase>> {
+    let new = new.trim();
+    if new.is_empty() { return Err(anyhow!("phrase is empty")); }
+    let mut list = read_phrase_list(app);
+    if list.phrases.iter().any(|p| p.text != old && p.text.eq_ignore_ascii_case(new)) {
+        return Err(anyhow!("\"{}\" is already in the list", new));
+    }
+    let phrase = list.phrases.iter_mut().find(|p| p.text == old).ok_or_else(|| anyhow!("no phrase \"{}\"", old))?;
+    phrase.text = new.to_string();
+    write_phrase_list(app, &list)?;
+    Ok(by_priority(list.phrases))
+}
+
+pub fn remove_phrase(app: &AppHandle, text: &str) -> Result<Vec<Phrase>> {
+    let mut list = read_phrase_list(app);
+    let before = list.phrases.len();
+    list.phrases.retain(|p| p.text != text);
+    if list.phrases.len() == before { return Err(anyhow!("no phrase \"{}\"", text)); }
+    write_phrase_list(app, &list)?;
+    Ok(by_priority(list.phrases))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn phrase(text: &str, added_ms: u128, last_used_ms: u128, use_count: u64) -> Phrase {
+        Phrase { text: text.to_string(), added_ms, last_used_ms, use_count }
+    }
+
+    #[test]
+    fn mentions_matches_whole_words_ignoring_case() {
+        assert!(mentions("Ask al about it", "Al"));
+        assert!(mentions("ship Tauri 2, then rest", "tauri 2"));
+        assert!(mentions("(Commander)", "commander"));
+        assert!(!mentions("also", "Al"));
+        assert!(!mentions("totally", "Al"));
+        assert!(!mentions("anything", ""));
+    }
+
+    #[test]
+    fn by_priority_orders_by_recent_use_then_count_then_age() {
+        let sorted = by_priority(vec![
+            phrase("old unused", 1, 0, 0),
+            phrase("new unused", 2, 0, 0),
+            phrase("frequent", 3, 10, 5),
+            phrase("rare", 4, 10, 1),
+            phrase("recent", 5, 20, 1),
+        ]);
+        let texts: Vec<&str> = sorted.iter().map(|p| p.text.as_str()).collect();
+        assert_eq!(texts, ["recent", "frequent", "rare", "old unused", "new unused"]);
+    }
+}
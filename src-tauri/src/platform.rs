@@ -31,4 +31,340 @@ pub fn open_accessibility_settings() {
         .spawn();
 }
 
+/// Open a folder in Finder.
+pub fn open_in_finder(path: &std::path::Path) -> std::io::Result<()> {
+    std::process::Command::new("open").arg(path).spawn().map(|_| ())
+}
+
+/// Identity of the application currently in the foreground.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct FrontmostApp {
+    pub bundle_id: Option<String>,
+    pub name: Option<String>,
+}
+
+impl FrontmostApp {
+    /// Case-insensitive match against either the bundle identifier or the display name.
+    pub fn matches(&self, app: &str) -> bool {
+        let app = app.trim();
+        if app.is_empty() { return false; }
+        self.bundle_id.as_deref().map(|b| b.eq_ignore_ascii_case(app)).unwrap_or(false)
+            || self.name.as_deref().map(|n| n.eq_ignore_ascii_case(app)).unwrap_or(false)
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod objc_ffi {
+    use std::ffi::{c_char, c_void, CStr};
+
+    #[link(name = "AppKit", kind = "framework")]
+    extern "C" {}
+
+    #[link(name = "objc")]
+    extern "C" {
+        fn objc_getClass(name: *const c_char) -> *mut c_void;
+        fn sel_registerName(name: *const c_char) -> *mut c_void;
+        fn objc_msgSend();
+    }
+
+    /// Send a zero-argument message returning an object pointer.
+    pub unsafe fn send(receiver: *mut c_void, selector: &CStr) -> *mut c_void {
+        if receiver.is_null() { return std::ptr::null_mut(); }
+        let f: unsafe extern "C" fn(*mut c_void, *mut c_void) -> *mut c_void =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        f(receiver, sel_registerName(selector.as_ptr()))
+    }
+
+    /// Send a one-argument message returning an `NSInteger`.
+    pub unsafe fn send_isize(receiver: *mut c_void, selector: &CStr, arg: *mut c_void) -> isize {
+        if receiver.is_null() { return 0; }
+        let f: unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void) -> isize =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        f(receiver, sel_registerName(selector.as_ptr()), arg)
+    }
+
+    /// Send a two-argument message returning nothing.
+    pub unsafe fn send_void2(receiver: *mut c_void, selector: &CStr, a: *mut c_void, b: *const c_void) {
+        if receiver.is_null() { return; }
+        let f: unsafe extern "C" fn(*mut c_void, *mut c_void, *mut c_void, *const c_void) =
+            std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        f(receiver, sel_registerName(selector.as_ptr()), a, b)
+    }
+
+    #[repr(C)]
+    struct NSPoint {
+        x: f64,
+        y: f64,
+    }
+
+    /// `+[NSEvent otherEventWithType:location:modifierFlags:timestamp:windowNumber:context:subtype:data1:data2:]`
+    /// for an `NSEventTypeSystemDefined` event, which is how media keys are delivered.
+    pub unsafe fn system_defined_event(flags: usize, subtype: i16, data1: isize) -> *mut c_void {
+        type Ctor = unsafe extern "C" fn(*mut c_void, *mut c_void, usize, NSPoint, usize, f64, isize, *mut c_void, i16, isize, isize) -> *mut c_void;
+        let f: Ctor = std::mem::transmute(objc_msgSend as unsafe extern "C" fn());
+        let selector = c"otherEventWithType:location:modifierFlags:timestamp:windowNumber:context:subtype:data1:data2:";
+        f(class(c"NSEvent"), sel_registerName(selector.as_ptr()), 14, NSPoint { x: 0.0, y: 0.0 }, flags, 0.0, 0, std::ptr::null_mut(), subtype, data1, -1)
+    }
+
+    pub unsafe fn class(name: &CStr) -> *mut c_void {
+        objc_getClass(name.as_ptr())
+    }
+
+    /// Convert an `NSString*` into an owned Rust string.
+    pub unsafe fn nsstring_to_string(ns: *mut c_void) -> Option<String> {
+        let utf8 = send(ns, c"UTF8String") as *const c_char;
+        if utf8.is_null() { return None; }
+        Some(CStr::from_ptr(utf8).to_string_lossy().into_owned())
+    }
+}
+
+#[cfg(target_os = "macos")]
+pub fn frontmost_application() -> Option<FrontmostApp> {
+    unsafe {
+        let workspace = objc_ffi::send(objc_ffi::class(c"NSWorkspace"), c"sharedWorkspace");
+        let app = objc_ffi::send(workspace, c"frontmostApplication");
+        if app.is_null() { return None; }
+        let bundle_id = objc_ffi::nsstring_to_string(objc_ffi::send(app, c"bundleIdentifier"));
+        let name = objc_ffi::nsstring_to_string(objc_ffi::send(app, c"localizedName"));
+        Some(FrontmostApp { bundle_id, name })
+    }
+}
+
+/// Microphone (TCC) authorization state, mirroring `AVAuthorizationStatus`.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum MicrophoneAuthorization {
+    NotDetermined,
+    Restricted,
+    Denied,
+    Authorized,
+}
+
+#[cfg(target_os = "macos")]
+#[link(name = "AVFoundation", kind = "framework")]
+extern "C" {
+    static AVMediaTypeAudio: *mut std::ffi::c_void;
+}
 
+#[cfg(target_os = "macos")]
+pub fn microphone_authorization() -> MicrophoneAuthorization {
+    let status = unsafe {
+        objc_ffi::send_isize(objc_ffi::class(c"AVCaptureDevice"), c"authorizationStatusForMediaType:", AVMediaTypeAudio)
+    };
+    match status {
+        1 => MicrophoneAuthorization::Restricted,
+        2 => MicrophoneAuthorization::Denied,
+        3 => MicrophoneAuthorization::Authorized,
+        _ => MicrophoneAuthorization::NotDetermined,
+    }
+}
+
+/// A hand-built Objective-C block taking a `BOOL`, for APIs that report back through a
+/// completion handler. It is global: it captures nothing and the runtime never copies or frees it.
+#[cfg(target_os = "macos")]
+mod block_ffi {
+    use std::ffi::c_void;
+
+    const BLOCK_IS_GLOBAL: i32 = 1 << 28;
+
+    extern "C" {
+        static _NSConcreteGlobalBlock: u8;
+    }
+
+    #[repr(C)]
+    struct BlockDescriptor {
+        reserved: usize,
+        size: usize,
+    }
+
+    #[repr(C)]
+    pub struct BoolBlock {
+        isa: *const u8,
+        flags: i32,
+        reserved: i32,
+        invoke: unsafe extern "C" fn(*const BoolBlock, bool),
+        descriptor: *const BlockDescriptor,
+    }
+
+    // Immutable after construction and only ever read by the Objective-C runtime.
+    unsafe impl Send for BoolBlock {}
+    unsafe impl Sync for BoolBlock {}
+
+    static DESCRIPTOR: BlockDescriptor = BlockDescriptor { reserved: 0, size: std::mem::size_of::<BoolBlock>() };
+
+    impl BoolBlock {
+        pub fn new(invoke: unsafe extern "C" fn(*const BoolBlock, bool)) -> Self {
+            Self {
+                isa: std::ptr::addr_of!(_NSConcreteGlobalBlock),
+                flags: BLOCK_IS_GLOBAL,
+                reserved: 0,
+                invoke,
+                descriptor: &DESCRIPTOR,
+            }
+        }
+
+        pub fn as_ptr(&'static self) -> *const c_void {
+            self as *const Self as *const c_void
+        }
+    }
+}
+
+/// Callers of `request_microphone_access` waiting for the user's answer.
+#[cfg(target_os = "macos")]
+static MICROPHONE_ACCESS_WAITERS: std::sync::Mutex<Vec<std::sync::mpsc::Sender<bool>>> = std::sync::Mutex::new(Vec::new());
+
+#[cfg(target_os = "macos")]
+unsafe extern "C" fn on_microphone_access(_block: *const block_ffi::BoolBlock, granted: bool) {
+    if let Ok(mut waiters) = MICROPHONE_ACCESS_WAITERS.lock() {
+        for waiter in waiters.drain(..) {
+            let _ = waiter.send(granted);
+        }
+    }
+}
+
+/// Ask for microphone access, showing the system prompt if the user has not answered it yet.
+/// Once they have (immediately, if they already did), the receiver yields whether it was granted.
+#[cfg(target_os = "macos")]
+pub fn request_microphone_access() -> std::sync::mpsc::Receiver<bool> {
+    static BLOCK: std::sync::OnceLock<block_ffi::BoolBlock> = std::sync::OnceLock::new();
+    let (tx, rx) = std::sync::mpsc::channel();
+    if let Ok(mut waiters) = MICROPHONE_ACCESS_WAITERS.lock() {
+        waiters.push(tx);
+    }
+    let block = BLOCK.get_or_init(|| block_ffi::BoolBlock::new(on_microphone_access));
+    unsafe {
+        objc_ffi::send_void2(objc_ffi::class(c"AVCaptureDevice"), c"requestAccessForMediaType:completionHandler:", AVMediaTypeAudio, block.as_ptr());
+    }
+    rx
+}
+
+#[cfg(target_os = "macos")]
+mod tis_ffi {
+    use std::ffi::{c_char, c_void};
+
+    pub const K_CF_STRING_ENCODING_UTF8: u32 = 0x0800_0100;
+
+    #[link(name = "Carbon", kind = "framework")]
+    extern "C" {
+        pub fn TISCopyCurrentKeyboardInputSource() -> *mut c_void;
+        pub fn TISGetInputSourceProperty(source: *mut c_void, key: *const c_void) -> *const c_void;
+        pub static kTISPropertyInputSourceLanguages: *const c_void;
+    }
+
+    #[link(name = "CoreFoundation", kind = "framework")]
+    extern "C" {
+        pub fn CFArrayGetCount(array: *const c_void) -> isize;
+        pub fn CFArrayGetValueAtIndex(array: *const c_void, idx: isize) -> *const c_void;
+        pub fn CFStringGetCString(s: *const c_void, buf: *mut c_char, size: isize, encoding: u32) -> bool;
+        pub fn CFRelease(cf: *const c_void);
+    }
+}
+
+/// Primary language of the current keyboard input source (e.g. "de" for German QWERTZ).
+/// TIS must be queried on the main thread, so this blocks briefly on a main-thread hop and
+/// must not be called from the main thread itself.
+#[cfg(target_os = "macos")]
+pub fn input_source_language(app: &tauri::AppHandle) -> Option<String> {
+    let (tx, rx) = std::sync::mpsc::channel();
+    app.run_on_main_thread(move || {
+        let lang = unsafe {
+            let source = tis_ffi::TISCopyCurrentKeyboardInputSource();
+            if source.is_null() {
+                None
+            } else {
+                let langs = tis_ffi::TISGetInputSourceProperty(source, tis_ffi::kTISPropertyInputSourceLanguages);
+                let mut out = None;
+                if !langs.is_null() && tis_ffi::CFArrayGetCount(langs) > 0 {
+                    let first = tis_ffi::CFArrayGetValueAtIndex(langs, 0);
+                    let mut buf = [0 as std::ffi::c_char; 64];
+                    if tis_ffi::CFStringGetCString(first, buf.as_mut_ptr(), buf.len() as isize, tis_ffi::K_CF_STRING_ENCODING_UTF8) {
+                        out = Some(std::ffi::CStr::from_ptr(buf.as_ptr()).to_string_lossy().into_owned());
+                    }
+                }
+                tis_ffi::CFRelease(source);
+                out
+            }
+        };
+        let _ = tx.send(lang);
+    }).ok()?;
+    let tag = rx.recv_timeout(std::time::Duration::from_millis(500)).ok()??;
+    // BCP-47 tag ("zh-Hans", "pt-BR") → whisper's ISO 639-1 code
+    let primary = tag.split(['-', '_']).next()?.to_ascii_lowercase();
+    if primary.is_empty() { None } else { Some(primary) }
+}
+
+#[cfg(target_os = "macos")]
+mod media_ffi {
+    use std::ffi::c_void;
+
+    #[repr(C)]
+    pub struct AudioObjectPropertyAddress {
+        pub selector: u32,
+        pub scope: u32,
+        pub element: u32,
+    }
+
+    pub const K_AUDIO_OBJECT_SYSTEM_OBJECT: u32 = 1;
+    pub const K_AUDIO_HARDWARE_PROPERTY_DEFAULT_OUTPUT_DEVICE: u32 = u32::from_be_bytes(*b"dOut");
+    pub const K_AUDIO_DEVICE_PROPERTY_DEVICE_IS_RUNNING_SOMEWHERE: u32 = u32::from_be_bytes(*b"gone");
+    pub const K_AUDIO_OBJECT_PROPERTY_SCOPE_GLOBAL: u32 = u32::from_be_bytes(*b"glob");
+    pub const K_AUDIO_OBJECT_PROPERTY_ELEMENT_MAIN: u32 = 0;
+
+    #[link(name = "CoreAudio", kind = "framework")]
+    extern "C" {
+        pub fn AudioObjectGetPropertyData(
+            object: u32,
+            address: *const AudioObjectPropertyAddress,
+            qualifier_size: u32,
+            qualifier: *const c_void,
+            data_size: *mut u32,
+            data: *mut c_void,
+        ) -> i32;
+    }
+
+    #[link(name = "CoreGraphics", kind = "framework")]
+    extern "C" {
+        pub fn CGEventPost(tap: u32, event: *mut c_void);
+    }
+
+    pub unsafe fn get_u32(object: u32, selector: u32) -> Option<u32> {
+        let address = AudioObjectPropertyAddress {
+            selector,
+            scope: K_AUDIO_OBJECT_PROPERTY_SCOPE_GLOBAL,
+            element: K_AUDIO_OBJECT_PROPERTY_ELEMENT_MAIN,
+        };
+        let mut value = 0u32;
+        let mut size = std::mem::size_of::<u32>() as u32;
+        let status = AudioObjectGetPropertyData(object, &address, 0, std::ptr::null(), &mut size, &mut value as *mut u32 as *mut c_void);
+        (status == 0).then_some(value)
+    }
+}
+
+/// Whether some process is playing through the default output device. Players usually
+/// release the device shortly after pausing, so this tells "playing" from "paused".
+#[cfg(target_os = "macos")]
+pub fn output_audio_playing() -> bool {
+    unsafe {
+        media_ffi::get_u32(media_ffi::K_AUDIO_OBJECT_SYSTEM_OBJECT, media_ffi::K_AUDIO_HARDWARE_PROPERTY_DEFAULT_OUTPUT_DEVICE)
+            .filter(|&device| device != 0)
+            .and_then(|device| media_ffi::get_u32(device, media_ffi::K_AUDIO_DEVICE_PROPERTY_DEVICE_IS_RUNNING_SOMEWHERE))
+            .is_some_and(|running| running != 0)
+    }
+}
+
+/// Press and release the play/pause media key, which goes to whichever app owns Now Playing
+/// (Music, Spotify, a browser tab). Posting events needs the Accessibility permission.
+#[cfg(target_os = "macos")]
+pub fn send_media_play_pause(app: &tauri::AppHandle) {
+    const NX_KEYTYPE_PLAY: isize = 16;
+    const NX_SUBTYPE_AUX_CONTROL_BUTTONS: i16 = 8;
+    let _ = app.run_on_main_thread(|| unsafe {
+        for (flags, state) in [(0xa00, 0xa), (0xb00, 0xb)] {
+            let event = objc_ffi::system_defined_event(flags, NX_SUBTYPE_AUX_CONTROL_BUTTONS, (NX_KEYTYPE_PLAY << 16) | (state << 8));
+            let cg_event = objc_ffi::send(event, c"CGEvent");
+            if !cg_event.is_null() {
+                media_ffi::CGEventPost(0, cg_event);
+            }
+        }
+    });
+}
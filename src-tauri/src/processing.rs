@@ -0,0 +1,254 @@
+//! The capture chain: stages that clean up or annotate microphone audio on the audio
+//! thread, before it is buffered. Which stages run, and in what order, is configured in
+//! `audio_pipeline.json` and can change while a stream is open. Stages are built off the
+//! audio thread and handed to each open stream, so the callback never locks or allocates
+//! for them.
+
+use crate::config::{current_noise_floor_db, read_audio_pipeline_config, AudioPipelineConfig, AudioStageKind};
+use crate::vad::speech_threshold_db;
+use nnnoiseless::DenoiseState;
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+use tauri::AppHandle;
+
+/// One callback's worth of mono samples at the device rate, on its way through the chain.
+#[derive(Debug, Default)]
+pub struct AudioBlock {
+    pub samples: Vec<f32>,
+    /// Set by the VAD stage; `None` when no stage judged the block.
+    pub speech: Option<bool>,
+}
+
+/// A stage of the capture chain. Stages may change the number of samples in a block.
+pub trait AudioProcessor: Send {
+    fn process(&mut self, block: &mut AudioBlock);
+}
+
+/// One-pole DC blocker followed by a second-order Butterworth high-pass (RBJ biquad).
+struct HighPass {
+    dc_x1: f32,
+    dc_y1: f32,
+    b: [f32; 3],
+    a: [f32; 2],
+    x: [f32; 2],
+    y: [f32; 2],
+}
+
+/// Pole of the DC blocker; its corner sits at a few hertz whatever the cutoff.
+const DC_BLOCKER_POLE: f32 = 0.995;
+
+impl HighPass {
+    fn new(cutoff_hz: f32, sample_rate_hz: u32) -> Self {
+        let w0 = 2.0 * std::f32::consts::PI * cutoff_hz / sample_rate_hz.max(1) as f32;
+        let alpha = w0.sin() / (2.0 * std::f32::consts::FRAC_1_SQRT_2);
+        let cos = w0.cos();
+        let a0 = 1.0 + alpha;
+        Self {
+            dc_x1: 0.0,
+            dc_y1: 0.0,
+            b: [(1.0 + cos) / 2.0 / a0, -(1.0 + cos) / a0, (1.0 + cos) / 2.0 / a0],
+            a: [-2.0 * cos / a0, (1.0 - alpha) / a0],
+            x: [0.0; 2],
+            y: [0.0; 2],
+        }
+    }
+}
+
+impl AudioProcessor for HighPass {
+    fn process(&mut self, block: &mut AudioBlock) {
+        for s in block.samples.iter_mut() {
+            let dc_free = *s - self.dc_x1 + DC_BLOCKER_POLE * self.dc_y1;
+            self.dc_x1 = *s;
+            self.dc_y1 = dc_free;
+            let out = self.b[0] * dc_free + self.b[1] * self.x[0] + self.b[2] * self.x[1] - self.a[0] * self.y[0] - self.a[1] * self.y[1];
+            self.x = [dc_free, self.x[0]];
+            self.y = [out, self.y[0]];
+            *s = out.clamp(-1.0, 1.0);
+        }
+    }
+}
+
+struct Gain {
+    factor: f32,
+}
+
+impl AudioProcessor for Gain {
+    fn process(&mut self, block: &mut AudioBlock) {
+        for s in block.samples.iter_mut() {
+            *s = (*s * self.factor).clamp(-1.0, 1.0);
+        }
+    }
+}
+
+/// RNNoise suppression; only works on 48 kHz input.
+struct Denoiser {
+    state: Box<DenoiseState<'static>>,
+    pending: Vec<f32>,
+}
+
+impl Denoiser {
+    fn new(sample_rate_hz: u32) -> Option<Self> {
+        if sample_rate_hz != 48_000 {
+            log::warn!("noise suppression needs 48 kHz input; device runs at {} Hz, leaving audio unfiltered", sample_rate_hz);
+            return None;
+        }
+        Some(Self { state: DenoiseState::new(), pending: Vec::with_capacity(DenoiseState::FRAME_SIZE * 2) })
+    }
+}
+
+impl AudioProcessor for Denoiser {
+    /// Keeps only whole processed frames, carrying the rest over to the next block.
+    fn process(&mut self, block: &mut AudioBlock) {
+        // nnnoiseless expects i16-range floats.
+        self.pending.extend(block.samples.iter().map(|s| s * i16::MAX as f32));
+        let frames = self.pending.len() / DenoiseState::FRAME_SIZE;
+        block.samples.clear();
+        let mut frame_out = [0.0f32; DenoiseState::FRAME_SIZE];
+        for frame in self.pending.chunks_exact(DenoiseState::FRAME_SIZE) {
+            self.state.process_frame(&mut frame_out, frame);
+            block.samples.extend(frame_out.iter().map(|v| (v / i16::MAX as f32).clamp(-1.0, 1.0)));
+        }
+        self.pending.drain(..frames * DenoiseState::FRAME_SIZE);
+    }
+}
+
+/// Marks each block as speech when its level clears the input's speech threshold.
+struct Vad {
+    threshold_db: f32,
+}
+
+impl AudioProcessor for Vad {
+    fn process(&mut self, block: &mut AudioBlock) {
+        if block.samples.is_empty() { return; }
+        let mean_sq = block.samples.iter().map(|s| s * s).sum::<f32>() / block.samples.len() as f32;
+        block.speech = Some(10.0 * mean_sq.max(1e-24).log10() >= self.threshold_db);
+    }
+}
+
+struct PipelineSettings {
+    config: AudioPipelineConfig,
+    noise_floor_db: Option<f32>,
+}
+
+type Stages = Vec<Box<dyn AudioProcessor>>;
+
+/// Freshly built stages plus the speech threshold the fallback VAD should use with them.
+struct StageUpdate {
+    stages: Stages,
+    threshold_db: f32,
+}
+
+static SETTINGS: Mutex<Option<PipelineSettings>> = Mutex::new(None);
+/// Open pipelines by sample rate, each waiting for its next set of stages.
+static PIPELINES: Mutex<Vec<(u32, Sender<StageUpdate>)>> = Mutex::new(Vec::new());
+
+/// Re-read the stage list and the current input's noise floor, and send every open stream
+/// freshly built stages. They take over on the stream's next callback.
+pub fn configure(app: &AppHandle) {
+    let settings = PipelineSettings { config: read_audio_pipeline_config(app), noise_floor_db: current_noise_floor_db(app) };
+    if let Ok(mut current) = SETTINGS.lock() {
+        *current = Some(settings);
+    }
+    if let Ok(mut pipelines) = PIPELINES.lock() {
+        // A closed stream's receiver is gone, so its sender drops out here.
+        pipelines.retain(|(sample_rate_hz, tx)| tx.send(build_update(*sample_rate_hz)).is_ok());
+    }
+}
+
+/// Whether the denoise stage is on, which makes 48 kHz the preferred capture rate.
+pub fn denoise_enabled() -> bool {
+    SETTINGS
+        .lock()
+        .ok()
+        .and_then(|s| s.as_ref().map(|s| s.config.stages.iter().any(|stage| stage.enabled && stage.kind == AudioStageKind::Denoise)))
+        .unwrap_or(false)
+}
+
+/// The stages of one stream. Each stream owns its own, since stages keep per-stream state.
+pub struct Pipeline {
+    stages: Stages,
+    /// Judges blocks no `vad` stage judged, since auto-stop needs a verdict whatever the chain.
+    fallback_vad: Vad,
+    updates: Receiver<StageUpdate>,
+}
+
+impl Pipeline {
+    /// Call off the audio thread: this builds the initial stages and registers for updates.
+    pub fn new(sample_rate_hz: u32) -> Self {
+        let (tx, updates) = channel();
+        if let Ok(mut pipelines) = PIPELINES.lock() {
+            pipelines.push((sample_rate_hz, tx));
+        }
+        let StageUpdate { stages, threshold_db } = build_update(sample_rate_hz);
+        Self { stages, fallback_vad: Vad { threshold_db }, updates }
+    }
+
+    pub fn process(&mut self, samples: Vec<f32>) -> AudioBlock {
+        while let Ok(update) = self.updates.try_recv() {
+            self.stages = update.stages;
+            self.fallback_vad.threshold_db = update.threshold_db;
+        }
+        let mut block = AudioBlock { samples, speech: None };
+        for stage in self.stages.iter_mut() {
+            stage.process(&mut block);
+        }
+        if block.speech.is_none() {
+            self.fallback_vad.process(&mut block);
+        }
+        block
+    }
+}
+
+fn build_update(sample_rate_hz: u32) -> StageUpdate {
+    let unconfigured = || StageUpdate { stages: Vec::new(), threshold_db: speech_threshold_db(None) };
+    let Ok(guard) = SETTINGS.lock() else { return unconfigured() };
+    let Some(settings) = guard.as_ref() else { return unconfigured() };
+    StageUpdate { stages: build_stages(settings, sample_rate_hz), threshold_db: speech_threshold_db(settings.noise_floor_db) }
+}
+
+fn build_stages(settings: &PipelineSettings, sample_rate_hz: u32) -> Stages {
+    settings
+        .config
+        .stages
+        .iter()
+        .filter(|stage| stage.enabled)
+        .filter_map(|stage| -> Option<Box<dyn AudioProcessor>> {
+            match stage.kind {
+                AudioStageKind::HighPass => Some(Box::new(HighPass::new(stage.cutoff_hz, sample_rate_hz))),
+                AudioStageKind::Gain => Some(Box::new(Gain { factor: 10f32.powf(stage.gain_db / 20.0) })),
+                AudioStageKind::Denoise => Denoiser::new(sample_rate_hz).map(|d| Box::new(d) as Box<dyn AudioProcessor>),
+                AudioStageKind::Vad => Some(Box::new(Vad { threshold_db: speech_threshold_db(settings.noise_floor_db) })),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATE: u32 = 16_000;
+
+    /// RMS of a tone after the high-pass, over the second half so the filter has settled.
+    fn filtered_rms(freq_hz: f32) -> f32 {
+        let samples = (0..RATE).map(|i| 0.5 * (2.0 * std::f32::consts::PI * freq_hz * i as f32 / RATE as f32).sin()).collect();
+        let mut block = AudioBlock { samples, speech: None };
+        HighPass::new(120.0, RATE).process(&mut block);
+        let tail = &block.samples[block.samples.len() / 2..];
+        (tail.iter().map(|s| s * s).sum::<f32>() / tail.len() as f32).sqrt()
+    }
+
+    #[test]
+    fn high_pass_removes_dc() {
+        let mut block = AudioBlock { samples: vec![0.3; RATE as usize], speech: None };
+        HighPass::new(120.0, RATE).process(&mut block);
+        assert!(block.samples[block.samples.len() - 100..].iter().all(|s| s.abs() < 1e-3));
+    }
+
+    #[test]
+    fn high_pass_cuts_hum_and_keeps_speech_band() {
+        let input_rms = 0.5 * std::f32::consts::FRAC_1_SQRT_2;
+        assert!(filtered_rms(50.0) < 0.25 * input_rms);
+        assert!(filtered_rms(1000.0) > 0.95 * input_rms);
+    }
+}
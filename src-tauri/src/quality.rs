@@ -0,0 +1,98 @@
+//! Heuristic checks for whisper output that is probably a hallucination rather than speech.
+
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum QualityIssue {
+    /// No text although the recording was loud enough to contain speech.
+    Empty,
+    /// The same phrase looped over and over, whisper's classic failure on noise.
+    Repetition,
+    /// The text is in a different script than the requested language uses.
+    LanguageMismatch,
+}
+
+/// Phrase length used to spot loops; long enough to ignore natural repeats like "no, no".
+const REPEAT_NGRAM: usize = 3;
+/// Below this many words a repetition ratio says little.
+const MIN_WORDS_FOR_REPETITION: usize = 12;
+/// Languages written mostly outside the Latin script.
+const NON_LATIN_LANGUAGES: &[&str] = &[
+    "ar", "be", "bg", "bn", "el", "fa", "gu", "he", "hi", "hy", "ja", "ka", "kk", "km", "kn", "ko",
+    "lo", "mk", "ml", "mn", "mr", "my", "ne", "pa", "ru", "si", "sr", "ta", "te", "th", "uk", "ur", "yi", "zh",
+];
+
+/// First problem found in `text`, if any. `language` is the requested language code (none or
+/// `auto` skips the script check); `expect_speech` is false for recordings already flagged too quiet.
+pub fn assess(text: &str, language: Option<&str>, expect_speech: bool, max_repetition_ratio: f32) -> Option<QualityIssue> {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return expect_speech.then_some(QualityIssue::Empty);
+    }
+    if repetition_ratio(trimmed) > max_repetition_ratio {
+        return Some(QualityIssue::Repetition);
+    }
+    if let Some(lang) = language.filter(|l| !l.is_empty() && *l != "auto") {
+        if latin_share(trimmed).is_some_and(|share| (share >= 0.5) == NON_LATIN_LANGUAGES.contains(&lang)) {
+            return Some(QualityIssue::LanguageMismatch);
+        }
+    }
+    None
+}
+
+/// Fraction of word n-grams that repeat an earlier one; 0 for short texts.
+fn repetition_ratio(text: &str) -> f32 {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase())
+        .filter(|w| !w.is_empty())
+        .collect();
+    if words.len() < MIN_WORDS_FOR_REPETITION {
+        return 0.0;
+    }
+    let grams: Vec<&[String]> = words.windows(REPEAT_NGRAM).collect();
+    let mut seen = std::collections::HashSet::new();
+    let repeats = grams.iter().filter(|g| !seen.insert(**g)).count();
+    repeats as f32 / grams.len() as f32
+}
+
+/// Share of letters that are Latin; `None` when there are no letters to judge by.
+fn latin_share(text: &str) -> Option<f32> {
+    let letters: Vec<char> = text.chars().filter(|c| c.is_alphabetic()).collect();
+    if letters.is_empty() {
+        return None;
+    }
+    // Basic Latin through Latin Extended-B, plus Latin Extended Additional.
+    let latin = letters
+        .iter()
+        .filter(|c| matches!(**c as u32, 0x41..=0x24F | 0x1E00..=0x1EFF))
+        .count();
+    Some(latin as f32 / letters.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_text_is_an_issue_only_when_speech_was_expected() {
+        assert_eq!(assess("  ", None, true, 0.5), Some(QualityIssue::Empty));
+        assert_eq!(assess("", None, false, 0.5), None);
+    }
+
+    #[test]
+    fn looping_phrases_are_flagged() {
+        let looped = "thank you for watching ".repeat(6);
+        assert_eq!(assess(&looped, None, true, 0.5), Some(QualityIssue::Repetition));
+        let normal = "the quick brown fox jumps over the lazy dog while the cat sleeps on the warm mat";
+        assert_eq!(assess(normal, None, true, 0.5), None);
+    }
+
+    #[test]
+    fn script_must_match_the_requested_language() {
+        assert_eq!(assess("привет как дела", Some("en"), true, 0.5), Some(QualityIssue::LanguageMismatch));
+        assert_eq!(assess("hello there", Some("ru"), true, 0.5), Some(QualityIssue::LanguageMismatch));
+        assert_eq!(assess("привет как дела", Some("ru"), true, 0.5), None);
+        assert_eq!(assess("привет как дела", Some("auto"), true, 0.5), None);
+        assert_eq!(assess("123 456", Some("ru"), true, 0.5), None);
+    }
+}
@@ -0,0 +1,439 @@
+use crate::announce::{announce, announce_and_wait};
+use crate::audio::{input_unavailable, play_tick, AudioController, NoInputDeviceEvent, SYSTEM_AUDIO_DEVICE, interleave_tracks, mix_tracks, read_recording_mono, recording_path, save_recording, AutoStopState, CountdownKind, LastCapture, RecorderState, RecordingCountdown};
+use crate::config::{is_inline_timestamps_enabled, read_streaming_config, read_auto_stop_config, read_meeting_mode_config, read_recording_blocklist, read_recording_limit_config, read_recording_retention_config, read_time_box_config, read_media_pause_config, read_arming_delay_config, MeetingTrackMode};
+use crate::jobs::{fail_job, start_job, start_reserved_job, JobSource, RecordingSpan};
+use crate::http_server::touch_model;
+use crate::perf_stats::estimate_processing_ms;
+use crate::platform::{frontmost_application, output_audio_playing, send_media_play_pause, FrontmostApp};
+use crate::sessions::record_session_audio;
+use crate::whisper_service::resample_linear;
+use crate::streaming::{start_streaming, take_worker, StreamingWorker};
+use crate::transcription::{transcribe_and_copy, transcribe_meeting, TranscriptionOverrides};
+use crate::tray::{make_default_icon, make_recording_icon, make_transcribing_icon};
+use log::{error, info, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager};
+
+#[derive(Clone, serde::Serialize)]
+pub struct RecordingBlockedEvent {
+    pub app: Option<String>,
+    pub bundle_id: Option<String>,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToggleOutcome {
+    Started,
+    Stopped,
+    Blocked,
+}
+
+/// Set when recording paused media playback, so stopping resumes it exactly once.
+static MEDIA_PAUSED: AtomicBool = AtomicBool::new(false);
+
+/// Pause playing media for the recording. Skipped when nothing is playing, since the media key
+/// would start playback instead, and when system audio is what is being recorded.
+fn pause_media(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>) {
+    if !read_media_pause_config(app).enabled { return; }
+    let capturing_system_audio = read_meeting_mode_config(app).enabled
+        || recorder.lock().map(|st| st.input_device_name.as_deref() == Some(SYSTEM_AUDIO_DEVICE)).unwrap_or(false);
+    if capturing_system_audio || !output_audio_playing() { return; }
+    info!("Pausing media playback while recording");
+    send_media_play_pause(app);
+    MEDIA_PAUSED.store(true, Ordering::Relaxed);
+}
+
+fn resume_media(app: &AppHandle) {
+    if MEDIA_PAUSED.swap(false, Ordering::Relaxed) {
+        info!("Resuming media playback");
+        send_media_play_pause(app);
+    }
+}
+
+/// Take a hold on the microphone, which is only open while something needs it.
+fn acquire_input(app: &AppHandle) -> bool {
+    app.try_state::<AudioController>().is_some_and(|c| c.acquire())
+}
+
+fn release_input(app: &AppHandle) {
+    if let Some(controller) = app.try_state::<AudioController>() {
+        controller.release();
+    }
+}
+
+/// Returns the frontmost app if it is on the recording blocklist.
+fn blocked_frontmost_app(app: &AppHandle) -> Option<FrontmostApp> {
+    let blocklist = read_recording_blocklist(app);
+    if blocklist.apps.is_empty() { return None; }
+    let front = frontmost_application()?;
+    if blocklist.apps.iter().any(|a| front.matches(a)) { Some(front) } else { None }
+}
+
+/// Start capturing unless already recording or the frontmost app is blocklisted.
+/// `overrides` are the ones the recording is expected to stop with, so streamed chunks are
+/// transcribed the same way. Returns true when a new recording was started.
+pub fn start_recording(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, overrides: &TranscriptionOverrides) -> bool {
+    if let Some(front) = blocked_frontmost_app(app) {
+        let label = front.name.clone().or_else(|| front.bundle_id.clone()).unwrap_or_default();
+        warn!("Recording blocked: {} is on the recording blocklist", label);
+        let _ = app.emit("recording-blocked", RecordingBlockedEvent {
+            app: front.name,
+            bundle_id: front.bundle_id,
+            reason: format!("Recording is disabled while {} is in the foreground", label),
+        });
+        return false;
+    }
+    let opened = acquire_input(app);
+    let unavailable = input_unavailable();
+    if !opened || unavailable.is_some() {
+        release_input(app);
+        let event = unavailable.unwrap_or_else(|| NoInputDeviceEvent {
+            reason: "open-timeout",
+            message: "The microphone did not open in time".to_string(),
+        });
+        warn!("Recording not started: {}", event.message);
+        announce(app, "No microphone available");
+        let _ = app.emit("no-input-device", event);
+        return false;
+    }
+    // Spoken before capture starts, so with speakers the cue is not transcribed. The pre-roll
+    // window heard it, so it is dropped.
+    if announce_and_wait(app, "Recording") {
+        if let Ok(mut st) = recorder.lock() { st.pre_roll.clear(); }
+    }
+    let auto_stop_cfg = read_auto_stop_config(app);
+    let auto_stop = auto_stop_cfg.enabled.then(|| AutoStopState {
+        silence_ms: auto_stop_cfg.silence_ms,
+        last_speech: None,
+        triggered: false,
+    });
+    let limit_cfg = read_recording_limit_config(app);
+    let arming = read_arming_delay_config(app);
+    let arming_delay = arming.enabled.then(|| Duration::from_millis(arming.delay_ms));
+    // Chunks are transcribed without inline timestamps and meeting tracks are not streamed.
+    let streaming_cfg = read_streaming_config(app);
+    let stream = streaming_cfg.enabled && !read_meeting_mode_config(app).enabled && !is_inline_timestamps_enabled(app);
+    let mut started: Option<Instant> = None;
+    if let Ok(mut st) = recorder.lock() {
+        if !st.is_recording {
+            st.is_recording = true;
+            st.is_paused = false;
+            st.buffer.clear();
+            st.loopback_buffer.clear();
+            let now = Instant::now();
+            st.start_instant = Some(now);
+            st.auto_stop = auto_stop.clone();
+            st.time_box = None;
+            st.wrapped = false;
+            st.streamed_samples = 0;
+            let rate = st.sample_rate_hz as usize;
+            st.buffer.set_max_len(rate * limit_cfg.max_duration_secs as usize);
+            let loopback_max_len = st.loopback_sample_rate_hz as usize * limit_cfg.max_duration_secs as usize;
+            st.loopback_buffer.set_max_len(loopback_max_len);
+            let warn_at_samples = if limit_cfg.warning_secs == 0 || limit_cfg.warning_secs >= limit_cfg.max_duration_secs {
+                u64::MAX
+            } else {
+                rate as u64 * (limit_cfg.max_duration_secs - limit_cfg.warning_secs)
+            };
+            st.limit = RecordingCountdown::new(CountdownKind::Limit, warn_at_samples, st.buffer.max_len() as u64, limit_cfg.stop_at_limit);
+            // Seed with the audio from just before the shortcut so a quick first word is kept.
+            // An arming delay exists to keep the key click out, which pre-roll would bring back.
+            let pre_roll = st.pre_roll.take();
+            match arming_delay {
+                Some(delay) => {
+                    st.armed_until = Some(now + delay);
+                    st.recording_started_at = Some(SystemTime::now() + delay);
+                }
+                None => {
+                    let pre_roll_ms = pre_roll.len() as u64 * 1000 / rate.max(1) as u64;
+                    st.buffer.extend(pre_roll);
+                    st.armed_until = None;
+                    st.recording_started_at = Some(SystemTime::now() - Duration::from_millis(pre_roll_ms));
+                }
+            }
+            // The limit counts the buffer, pre-roll included.
+            st.limit.position = st.buffer.len() as u64;
+            // What is said before this recording is transcribed with it, not again later.
+            st.retroactive.clear();
+            started = Some(now);
+        }
+    }
+    let Some(started_at) = started else {
+        release_input(app);
+        return false;
+    };
+    if arming_delay.is_some() && arming.tick {
+        play_tick();
+    }
+    if stream {
+        start_streaming(app, recorder, started_at, &streaming_cfg, overrides);
+    }
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        let _ = tray.set_tooltip(Some("Recording…"));
+        let _ = tray.set_icon(Some(make_recording_icon()));
+        // Clears the previous dictation's low-confidence mark.
+        let _ = tray.set_title(None::<&str>);
+    }
+    pause_media(app, recorder);
+    // A dictation is coming, so the idle watcher must not unload the model now.
+    touch_model();
+    let _ = app.emit("recording-start", true);
+    true
+}
+
+/// Stop an active recording and transcribe it on a background thread.
+/// Returns true when a recording was stopped.
+pub fn stop_recording_and_transcribe(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, source: JobSource) -> bool {
+    stop_recording_and_transcribe_with(app, recorder, source, TranscriptionOverrides::default())
+}
+
+/// Like `stop_recording_and_transcribe`, with one-off language/prompt/model overrides.
+pub fn stop_recording_and_transcribe_with(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, source: JobSource, overrides: TranscriptionOverrides) -> bool {
+    let mut data = Vec::<f32>::new();
+    let mut sample_rate = 16_000;
+    let mut loopback = Vec::<f32>::new();
+    let mut loopback_rate = 16_000;
+    let mut streamed_samples = 0;
+    let mut started_at = None;
+    let mut wrapped = false;
+    let mut stopped = false;
+    if let Ok(mut st) = recorder.lock() {
+        if st.is_recording {
+            st.is_recording = false;
+            st.is_paused = false;
+            data = st.buffer.take();
+            loopback = st.loopback_buffer.take();
+            st.start_instant = None;
+            sample_rate = st.sample_rate_hz;
+            loopback_rate = st.loopback_sample_rate_hz;
+            streamed_samples = std::mem::take(&mut st.streamed_samples);
+            started_at = st.recording_started_at.take();
+            wrapped = st.wrapped;
+            stopped = true;
+        }
+    }
+    if !stopped { return false; }
+    release_input(app);
+    // A wrapped buffer lost its start, so the span is measured back from the end.
+    let span = match started_at {
+        Some(started) if !wrapped => RecordingSpan::since(started),
+        _ => RecordingSpan::ending_now(data.len() as u64 * 1000 / sample_rate.max(1) as u64),
+    };
+    let meeting = read_meeting_mode_config(app);
+    // Align the system-audio track with the mic so the two can be mixed or saved as channels.
+    let loopback = (meeting.enabled && !loopback.is_empty()).then(|| resample_linear(&loopback, loopback_rate, sample_rate));
+    let (data, loopback) = match (loopback, meeting.track_mode) {
+        (Some(them), MeetingTrackMode::Mixed) => (mix_tracks(&data, &them), None),
+        (them, _) => (data, them),
+    };
+    if let Ok(mut st) = recorder.lock() {
+        st.last_capture = Some(LastCapture { samples: data.clone(), sample_rate_hz: sample_rate });
+    }
+    // Save for debugging; separate meeting tracks are kept as left (mic) and right (system)
+    let retention = read_recording_retention_config(app);
+    let to_save = retention.save_recordings.then(|| match &loopback {
+        Some(them) => (interleave_tracks(&data, them), 2),
+        None => (data.clone(), 1),
+    });
+    let started_at_ms = span.started_at_ms;
+    let loopback = loopback.map(|samples| LastCapture { samples, sample_rate_hz: sample_rate });
+    let streamed = take_worker().map(|worker| (worker, streamed_samples));
+    transcribe_in_background(app, data, sample_rate, loopback, streamed, source, overrides, Some(span));
+    // Encoding a long FLAC takes a while, so it runs after the transcription is under way.
+    if let Some((samples, channels)) = to_save {
+        let app = app.clone();
+        std::thread::spawn(move || {
+            match save_recording(&app, &samples, sample_rate, channels, retention.format, started_at_ms) {
+                Ok(p) => info!("Saved recording: {}", p.display()),
+                Err(e) => warn!("Failed to save recording: {}", e),
+            }
+        });
+    }
+    resume_media(app);
+    announce(app, "Transcribing");
+    let _ = app.emit("recording-stop", true);
+    true
+}
+
+/// Stop an active recording and throw the audio away. Returns true when a recording was cancelled.
+pub fn cancel_recording(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>) -> bool {
+    let mut cancelled = false;
+    if let Ok(mut st) = recorder.lock() {
+        if st.is_recording {
+            st.is_recording = false;
+            st.is_paused = false;
+            st.buffer.clear();
+            st.loopback_buffer.clear();
+            st.start_instant = None;
+            st.streamed_samples = 0;
+            cancelled = true;
+        }
+    }
+    if !cancelled { return false; }
+    release_input(app);
+    // The worker sees the recording end and exits; its text is simply dropped.
+    drop(take_worker());
+    info!("Recording cancelled");
+    resume_media(app);
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        let _ = tray.set_tooltip(Some("Commander"));
+        let _ = tray.set_icon(Some(make_default_icon()));
+    }
+    announce(app, "Cancelled");
+    let _ = app.emit("recording-cancelled", true);
+    true
+}
+
+/// Pause or resume the current recording; both halves end up in one transcription.
+/// Returns the new paused state, or `None` when nothing is being recorded.
+pub fn toggle_pause(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>) -> Option<bool> {
+    let paused = {
+        let mut st = recorder.lock().ok()?;
+        if !st.is_recording { return None; }
+        st.is_paused = !st.is_paused;
+        // Silence while paused must not count towards auto-stop.
+        if let Some(vad) = st.auto_stop.as_mut() {
+            vad.last_speech = None;
+        }
+        st.is_paused
+    };
+    info!("Recording {}", if paused { "paused" } else { "resumed" });
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        if paused {
+            let _ = tray.set_tooltip(Some("Paused"));
+            let _ = tray.set_icon(Some(make_default_icon()));
+        } else {
+            let _ = tray.set_tooltip(Some("Recording…"));
+            let _ = tray.set_icon(Some(make_recording_icon()));
+        }
+    }
+    announce(app, if paused { "Paused" } else { "Resumed" });
+    let _ = app.emit(if paused { "recording-paused" } else { "recording-resumed" }, true);
+    Some(paused)
+}
+
+/// Start the configured time box for the running recording, replacing any earlier one.
+fn arm_time_box(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>) {
+    let cfg = read_time_box_config(app);
+    if !cfg.enabled || cfg.stop_after_secs == 0 { return; }
+    if let Ok(mut st) = recorder.lock() {
+        if !st.is_recording { return; }
+        let rate = st.sample_rate_hz as u64;
+        st.time_box = Some(RecordingCountdown::new(CountdownKind::TimeBox, rate * cfg.warn_after_secs.min(cfg.stop_after_secs), rate * cfg.stop_after_secs, true));
+    }
+}
+
+/// Keep a time-boxed recording going after its warning by starting a fresh time box.
+/// Returns false when no time-boxed recording is running.
+pub fn continue_recording(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>) -> bool {
+    let boxed = recorder.lock().map(|st| st.is_recording && st.time_box.is_some()).unwrap_or(false);
+    if !boxed { return false; }
+    arm_time_box(app, recorder);
+    info!("Recording continued past its time box warning");
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        let _ = tray.set_tooltip(Some("Recording…"));
+    }
+    let _ = app.emit("recording-continued", true);
+    true
+}
+
+/// Transcribe the retroactive buffer, the last stretch of audio heard while not recording,
+/// and empty it. Returns false while recording or when nothing has been buffered.
+pub fn transcribe_recent(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, source: JobSource) -> bool {
+    let (data, sample_rate) = {
+        let Ok(mut st) = recorder.lock() else { return false };
+        if st.is_recording || st.retroactive.is_empty() { return false; }
+        let data = st.retroactive.take();
+        st.last_capture = Some(LastCapture { samples: data.clone(), sample_rate_hz: st.sample_rate_hz });
+        (data, st.sample_rate_hz)
+    };
+    info!("Transcribing the last {} ms heard", data.len() as u64 * 1000 / sample_rate.max(1) as u64);
+    let span = RecordingSpan::ending_now(data.len() as u64 * 1000 / sample_rate.max(1) as u64);
+    transcribe_in_background(app, data, sample_rate, None, None, source, TranscriptionOverrides::default(), Some(span));
+    announce(app, "Transcribing");
+    true
+}
+
+/// Re-run a WAV from the recordings directory through the transcription pipeline.
+pub fn transcribe_saved_recording(app: &AppHandle, filename: &str) -> anyhow::Result<()> {
+    let path = recording_path(app, filename)?;
+    let (data, sample_rate) = read_recording_mono(&path)?;
+    info!("Transcribing saved recording: {}", path.display());
+    transcribe_in_background(app, data, sample_rate, None, None, JobSource::SavedRecording, TranscriptionOverrides::default(), None);
+    Ok(())
+}
+
+/// Switch the tray to "transcribing", register the job with its ETA and run it off-thread.
+/// `loopback` carries meeting mode's system-audio track, transcribed separately from the mic.
+/// `streamed` is the chunk worker of a streamed recording with the samples it has taken.
+/// `recording` is when the audio was captured, if known.
+#[allow(clippy::too_many_arguments)]
+fn transcribe_in_background(app: &AppHandle, data: Vec<f32>, sample_rate: u32, loopback: Option<LastCapture>, streamed: Option<(StreamingWorker, usize)>, source: JobSource, overrides: TranscriptionOverrides, recording: Option<RecordingSpan>) -> u64 {
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        let _ = tray.set_tooltip(Some("Transcribing…"));
+        let _ = tray.set_icon(Some(make_transcribing_icon()));
+    }
+    let audio_ms = data.len() as u64 * 1000 / sample_rate.max(1) as u64;
+    let eta_ms = overrides
+        .effective_model_id(app)
+        .and_then(|id| estimate_processing_ms(app, &id, audio_ms));
+    // Expected processing time based on this model's history; `None` until it has run once.
+    // A streamed recording keeps the id its chunks were sent under.
+    let job_id = match streamed.as_ref() {
+        Some((worker, _)) => start_reserved_job(worker.job_id(), source, audio_ms, eta_ms, recording),
+        None => start_job(source, audio_ms, eta_ms, recording),
+    };
+    record_session_audio(app, job_id, LastCapture { samples: data.clone(), sample_rate_hz: sample_rate }, loopback.clone());
+    spawn_transcription(app.clone(), job_id, data, sample_rate, loopback, streamed, overrides);
+    job_id
+}
+
+pub fn toggle_recording(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, source: JobSource) -> ToggleOutcome {
+    toggle_recording_with(app, recorder, source, TranscriptionOverrides::default())
+}
+
+/// Toggle recording; `overrides` apply if this call stops and transcribes.
+pub fn toggle_recording_with(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, source: JobSource, overrides: TranscriptionOverrides) -> ToggleOutcome {
+    let is_recording = recorder.lock().map(|st| st.is_recording).unwrap_or(false);
+    if is_recording {
+        stop_recording_and_transcribe_with(app, recorder, source, overrides);
+        ToggleOutcome::Stopped
+    } else if start_recording(app, recorder, &overrides) {
+        // Held recordings end on release, so only toggled ones get a time box.
+        arm_time_box(app, recorder);
+        ToggleOutcome::Started
+    } else {
+        ToggleOutcome::Blocked
+    }
+}
+
+fn spawn_transcription(app: AppHandle, job_id: u64, data: Vec<f32>, sample_rate: u32, loopback: Option<LastCapture>, streamed: Option<(StreamingWorker, usize)>, overrides: TranscriptionOverrides) {
+    std::thread::spawn(move || {
+        // A failed stream only costs latency: the whole recording is transcribed instead. So
+        // does a stop with other overrides, e.g. Translate, which the chunks did not apply.
+        let prefix = streamed.filter(|(worker, _)| worker.overrides() == &overrides).and_then(|(worker, samples)| {
+            worker.finish(samples).map_err(|e| warn!("streamed transcription failed, transcribing the whole recording: {}", e)).ok()
+        });
+        let result = match loopback {
+            Some(them) => transcribe_meeting(&app, job_id, data, sample_rate, them.samples, them.sample_rate_hz, &overrides),
+            None => transcribe_and_copy(&app, job_id, data, sample_rate, prefix, &overrides),
+        };
+        if let Err(err) = result {
+            error!("transcription error: {err:?}");
+            if let Some(tray) = app.tray_by_id("main-tray") {
+                let _ = tray.set_tooltip(Some("Transcription failed"));
+                let _ = tray.set_icon(Some(make_default_icon()));
+            }
+            fail_job(job_id, err.to_string());
+            let app2 = app.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(std::time::Duration::from_secs(2));
+                if let Some(tray) = app2.tray_by_id("main-tray") {
+                    let _ = tray.set_tooltip(Some("Commander"));
+                }
+            });
+        }
+    });
+}
@@ -0,0 +1,115 @@
+use crate::audio::{read_wav_mono, write_float_wav, LastCapture};
+use crate::config::temp_dir;
+use crate::jobs::get_job;
+use crate::transcription::{timestamped_segments, transcribe_to_segments};
+use anyhow::{anyhow, Result};
+use log::warn;
+use std::collections::VecDeque;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use tauri::AppHandle;
+
+/// Recent dictations kept for `export_session`.
+const SESSION_HISTORY_CAPACITY: usize = 5;
+
+/// Everything needed to reproduce one dictation: the audio as recorded and what came back.
+/// The audio lives in WAV files under `session_dir`, removed when the session is dropped.
+struct Session {
+    job_id: u64,
+    audio: PathBuf,
+    /// Meeting mode's system-audio track.
+    loopback: Option<PathBuf>,
+    text: Option<String>,
+    profile: Option<serde_json::Value>,
+}
+
+static SESSIONS: OnceLock<Mutex<VecDeque<Session>>> = OnceLock::new();
+
+fn sessions() -> &'static Mutex<VecDeque<Session>> {
+    SESSIONS.get_or_init(|| Mutex::new(VecDeque::new()))
+}
+
+fn session_dir(app: &AppHandle) -> PathBuf {
+    temp_dir(app).join("commander-sessions")
+}
+
+/// Remember the audio of a job that is about to be transcribed. It is written to disk on a
+/// background thread so a long dictation is not held in memory, nor delayed by the write.
+pub fn record_session_audio(app: &AppHandle, job_id: u64, audio: LastCapture, loopback: Option<LastCapture>) {
+    let dir = session_dir(app);
+    if SESSIONS.get().is_none() {
+        // Left over from an earlier run, whose job ids are reused.
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+    let audio_path = dir.join(format!("job_{}.wav", job_id));
+    let loopback_path = loopback.as_ref().map(|_| dir.join(format!("job_{}_loopback.wav", job_id)));
+    {
+        let Ok(mut list) = sessions().lock() else { return };
+        if list.len() == SESSION_HISTORY_CAPACITY {
+            if let Some(old) = list.pop_front() {
+                let _ = std::fs::remove_file(&old.audio);
+                if let Some(loopback) = &old.loopback { let _ = std::fs::remove_file(loopback); }
+            }
+        }
+        list.push_back(Session { job_id, audio: audio_path.clone(), loopback: loopback_path.clone(), text: None, profile: None });
+    }
+    std::thread::spawn(move || {
+        let written = std::fs::create_dir_all(&dir).map_err(anyhow::Error::from)
+            .and_then(|_| write_float_wav(&audio_path, &audio.samples, audio.sample_rate_hz, 1))
+            .and_then(|_| match (&loopback_path, &loopback) {
+                (Some(path), Some(track)) => write_float_wav(path, &track.samples, track.sample_rate_hz, 1),
+                _ => Ok(()),
+            });
+        if let Err(e) = written {
+            warn!("Failed to keep the audio of job {} for export: {}", job_id, e);
+        }
+    });
+}
+
+/// Attach the transcript and timing profile once the job is delivered.
+pub fn record_session_result(job_id: u64, text: &str, profile: &serde_json::Value) {
+    let Ok(mut list) = sessions().lock() else { return };
+    if let Some(session) = list.iter_mut().find(|s| s.job_id == job_id) {
+        session.text = Some(text.to_string());
+        session.profile = Some(profile.clone());
+    }
+}
+
+/// Write a zip with the WAV(s), transcript, per-segment timestamps and profile of `job_id`.
+/// Segments come from the inline timestamps when the transcript has them; otherwise the audio
+/// is transcribed again with timestamps, which blocks until the model finishes.
+pub fn export_session(app: &AppHandle, job_id: u64, path: &Path) -> Result<()> {
+    let (audio, loopback, text, profile) = {
+        let list = sessions().lock().map_err(|_| anyhow!("session history unavailable"))?;
+        let session = list.iter().find(|s| s.job_id == job_id).ok_or_else(|| anyhow!("no recorded session for job {}", job_id))?;
+        (session.audio.clone(), session.loopback.clone(), session.text.clone(), session.profile.clone())
+    };
+    let text = text.ok_or_else(|| anyhow!("job {} has not finished transcribing", job_id))?;
+    let segments = match timestamped_segments(&text) {
+        Some(segments) => segments,
+        None => {
+            let (samples, sample_rate_hz) = read_wav_mono(&audio)?;
+            transcribe_to_segments(app, job_id, &samples, sample_rate_hz)?
+        }
+    };
+
+    let mut zip = zip::ZipWriter::new(std::fs::File::create(path)?);
+    let options = zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file("audio.wav", options)?;
+    std::io::copy(&mut std::fs::File::open(&audio)?, &mut zip)?;
+    if let Some(loopback) = &loopback {
+        zip.start_file("audio_loopback.wav", options)?;
+        std::io::copy(&mut std::fs::File::open(loopback)?, &mut zip)?;
+    }
+    zip.start_file("transcript.txt", options)?;
+    zip.write_all(text.as_bytes())?;
+    zip.start_file("segments.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&segments)?.as_bytes())?;
+    zip.start_file("profile.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&profile.unwrap_or(serde_json::Value::Null))?.as_bytes())?;
+    zip.start_file("job.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&get_job(job_id))?.as_bytes())?;
+    zip.finish()?;
+    Ok(())
+}
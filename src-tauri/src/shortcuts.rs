@@ -0,0 +1,187 @@
+use crate::audio::RecorderState;
+use crate::actions::run_action_with;
+use crate::config::{is_hold_to_record_enabled, read_hold_to_record_config, read_model_config, read_shortcut_bindings, read_shortcut_config, ShortcutAction, ShortcutConfig};
+use crate::http_server::preload_override_model;
+use crate::jobs::JobSource;
+use crate::recording::{cancel_recording, start_recording, stop_recording_and_transcribe_with};
+use crate::transcription::TranscriptionOverrides;
+use log::{error, info, warn};
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_global_shortcut::{Code, GlobalShortcutExt, Modifiers, Shortcut, ShortcutState};
+
+#[derive(Clone, Debug)]
+pub struct ShortcutBinding {
+    pub shortcut: Shortcut,
+    pub action: ShortcutAction,
+    pub profile: Option<String>,
+    /// Model that transcribes recordings this shortcut stops.
+    pub model: Option<String>,
+}
+
+/// Press/release bookkeeping for hold-to-record, to tell taps from holds.
+#[derive(Default)]
+struct HoldTracker {
+    pressed_at: Option<Instant>,
+    /// A tap started a recording that keeps running until the next press.
+    latched: bool,
+    /// The press that stopped a latched recording; its release must not act again.
+    ignore_release: bool,
+}
+
+/// Shortcuts currently registered with the OS and the action each one triggers.
+#[derive(Default)]
+pub struct ShortcutBindings {
+    bindings: Mutex<Vec<ShortcutBinding>>,
+    hold: Mutex<HoldTracker>,
+}
+
+/// Fallback when the configured main shortcut cannot be registered.
+fn default_shortcut() -> Shortcut {
+    Shortcut::new(Some(Modifiers::SUPER | Modifiers::SHIFT), Code::F9)
+}
+
+fn binding_from_config(cfg: &ShortcutConfig) -> anyhow::Result<ShortcutBinding> {
+    Ok(ShortcutBinding { shortcut: cfg.to_shortcut()?, action: cfg.action, profile: cfg.profile.clone(), model: cfg.model.clone() })
+}
+
+/// (Re-)register the main shortcut and all extra bindings, replacing whatever was registered before.
+pub fn register_shortcuts(app: &AppHandle) {
+    let main = read_shortcut_config(app);
+    info!("Loading shortcut configuration: {:?} + {} ({:?})", main.modifiers, main.key, main.action);
+    app.global_shortcut().unregister_all().ok();
+
+    let mut registered = Vec::new();
+    match binding_from_config(&main).and_then(|b| {
+        app.global_shortcut().register(b.shortcut).map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        Ok(b)
+    }) {
+        Ok(binding) => {
+            info!("✅ Main shortcut registered");
+            registered.push(binding);
+        }
+        Err(e) => {
+            error!("❌ Failed to register main shortcut: {}", e);
+            warn!("Falling back to default shortcut...");
+            let fallback = ShortcutBinding { shortcut: default_shortcut(), action: main.action, profile: main.profile.clone(), model: main.model.clone() };
+            if app.global_shortcut().register(fallback.shortcut).is_ok() {
+                info!("✅ Fallback shortcut registered");
+                registered.push(fallback);
+            }
+        }
+    }
+
+    for cfg in read_shortcut_bindings(app).bindings {
+        let binding = match binding_from_config(&cfg) {
+            Ok(b) => b,
+            Err(e) => { warn!("Skipping invalid shortcut binding {:?} + {}: {}", cfg.modifiers, cfg.key, e); continue; }
+        };
+        if registered.iter().any(|r| r.shortcut.id() == binding.shortcut.id()) {
+            warn!("Skipping duplicate shortcut binding {:?} + {}", cfg.modifiers, cfg.key);
+            continue;
+        }
+        match app.global_shortcut().register(binding.shortcut) {
+            Ok(_) => {
+                info!("✅ Shortcut {:?} + {} registered for {:?}", cfg.modifiers, cfg.key, cfg.action);
+                registered.push(binding);
+            }
+            Err(e) => error!("❌ Failed to register shortcut {:?} + {}: {}", cfg.modifiers, cfg.key, e),
+        }
+    }
+
+    if registered.is_empty() {
+        error!("❌ Failed to register any shortcuts!");
+        warn!("On macOS, ensure accessibility permissions are granted.");
+    }
+    let selected = read_model_config(app).and_then(|c| c.selected_id);
+    let mut mapped: Vec<String> = registered.iter().filter_map(|b| b.model.clone()).filter(|m| Some(m) != selected.as_ref()).collect();
+    mapped.sort();
+    mapped.dedup();
+    if mapped.len() > 1 {
+        warn!("Shortcuts map to {} different models; only one is kept loaded next to the selected model", mapped.len());
+    }
+    if let Some(model) = mapped.into_iter().next() {
+        preload_override_model(model);
+    }
+    if let Some(state) = app.try_state::<ShortcutBindings>() {
+        if let Ok(mut bindings) = state.bindings.lock() { *bindings = registered; }
+    }
+}
+
+fn handle_hold(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, hold: &mut HoldTracker, state: ShortcutState, overrides: TranscriptionOverrides) {
+    let is_recording = recorder.lock().map(|st| st.is_recording).unwrap_or(false);
+    match state {
+        ShortcutState::Pressed => {
+            if std::mem::take(&mut hold.latched) && is_recording {
+                hold.ignore_release = true;
+                stop_recording_and_transcribe_with(app, recorder, JobSource::Shortcut, overrides);
+                return;
+            }
+            hold.pressed_at = Some(Instant::now());
+            start_recording(app, recorder, &overrides);
+        }
+        ShortcutState::Released => {
+            if std::mem::take(&mut hold.ignore_release) { return; }
+            let Some(pressed_at) = hold.pressed_at.take() else { return };
+            let cfg = read_hold_to_record_config(app);
+            if (pressed_at.elapsed().as_millis() as u64) >= cfg.min_hold_ms {
+                stop_recording_and_transcribe_with(app, recorder, JobSource::Shortcut, overrides);
+            } else if cfg.tap_toggles && is_recording {
+                info!("Shortcut tapped; recording until the next press");
+                hold.latched = true;
+            } else {
+                info!("Shortcut released after less than {} ms; discarding", cfg.min_hold_ms);
+                cancel_recording(app, recorder);
+            }
+        }
+    }
+}
+
+type QueuedShortcut = (AppHandle, Arc<Mutex<RecorderState>>, u32, ShortcutState);
+
+static SHORTCUT_QUEUE: OnceLock<Mutex<Sender<QueuedShortcut>>> = OnceLock::new();
+
+/// Global shortcut handler. It runs on the main thread, and starting a recording waits for
+/// the microphone to open, so events are handed to one worker that keeps them in order.
+pub fn dispatch(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, shortcut: &Shortcut, state: ShortcutState) {
+    let queue = SHORTCUT_QUEUE.get_or_init(|| {
+        let (tx, rx) = mpsc::channel::<QueuedShortcut>();
+        std::thread::spawn(move || {
+            for (app, recorder, id, state) in rx {
+                run_shortcut(&app, &recorder, id, state);
+            }
+        });
+        Mutex::new(tx)
+    });
+    if let Ok(tx) = queue.lock() {
+        let _ = tx.send((app.clone(), recorder.clone(), shortcut.id(), state));
+    }
+}
+
+/// Run the action bound to the shortcut with this id.
+fn run_shortcut(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, shortcut_id: u32, state: ShortcutState) {
+    let Some(state_bindings) = app.try_state::<ShortcutBindings>() else { return };
+    let binding = state_bindings
+        .bindings
+        .lock()
+        .ok()
+        .and_then(|b| b.iter().find(|b| b.shortcut.id() == shortcut_id).cloned());
+    let Some(binding) = binding else { return };
+    let action = match binding.action {
+        ShortcutAction::Record if is_hold_to_record_enabled(app) => ShortcutAction::Hold,
+        ShortcutAction::Record => ShortcutAction::Toggle,
+        other => other,
+    };
+    let overrides = TranscriptionOverrides { model: binding.model.clone(), ..Default::default() };
+    match (action, state) {
+        (ShortcutAction::Hold, state) => {
+            if let Ok(mut hold) = state_bindings.hold.lock() {
+                handle_hold(app, recorder, &mut hold, state, overrides);
+            }
+        }
+        (action, ShortcutState::Pressed) => run_action_with(app, recorder, action, binding.profile.as_deref(), JobSource::Shortcut, overrides),
+        (_, ShortcutState::Released) => {}
+    }
+}
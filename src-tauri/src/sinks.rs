@@ -0,0 +1,263 @@
+use crate::config::{is_auto_paste_enabled, read_auto_paste_rules, read_auto_paste_timing_config, read_paste_review_config, OutputSinkConfig};
+use crate::editors::deliver_to_active_editor;
+use crate::platform::frontmost_application;
+use crate::jobs::{get_job, RecordingSpan};
+use crate::whisper_service::LanguageProbability;
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
+use tauri::{AppHandle, Emitter, Manager, WebviewUrl, WebviewWindowBuilder};
+use tauri_plugin_clipboard_manager::ClipboardExt;
+
+#[derive(Clone, serde::Serialize)]
+pub struct OutputSinkErrorEvent {
+    pub job_id: u64,
+    pub sink: String,
+    pub error: String,
+}
+
+/// The most recent transcript delivered to the sinks, for the paste-last action.
+#[derive(Default)]
+pub struct LastTranscript(Mutex<Option<String>>);
+
+/// A long transcript waiting in the review window for the user to paste or discard.
+#[derive(Default)]
+pub struct PendingPasteReview(Mutex<Option<String>>);
+
+pub const PASTE_REVIEW_WINDOW: &str = "paste-review";
+
+/// Deliver a finished transcript to each configured sink in order. A failing sink is
+/// reported via `output-sink-error` and does not stop the ones after it. When an editor
+/// plugin covers the frontmost app it receives the text instead of the clipboard and paste.
+pub fn run_output_sinks(app: &AppHandle, job_id: u64, text: &str, sinks: &[OutputSinkConfig]) {
+    if let Some(last) = app.try_state::<LastTranscript>() {
+        if let Ok(mut last) = last.0.lock() { *last = Some(text.to_string()); }
+    }
+    let wants_paste = sinks.iter().any(|s| matches!(s, OutputSinkConfig::Clipboard | OutputSinkConfig::AutoPaste));
+    let sent_to_editor = wants_paste && deliver_to_active_editor(job_id, text);
+    for sink in sinks {
+        if sent_to_editor && matches!(sink, OutputSinkConfig::Clipboard | OutputSinkConfig::AutoPaste) {
+            continue;
+        }
+        let result = match sink {
+            OutputSinkConfig::Clipboard => app.clipboard().write_text(text.to_string()).map_err(|e| anyhow!(e.to_string())),
+            OutputSinkConfig::AutoPaste => {
+                if !should_auto_paste(app) {
+                    Ok(())
+                } else if needs_review(app, text) {
+                    open_paste_review(app, text)
+                } else {
+                    trigger_auto_paste(app, text.to_string());
+                    Ok(())
+                }
+            }
+            OutputSinkConfig::FileAppend { path } => append_to_file(path, text),
+            OutputSinkConfig::Webhook { url } => {
+                post_webhook(url.clone(), job_id, text.to_string());
+                Ok(())
+            }
+            OutputSinkConfig::History => append_history(app, job_id, text),
+        };
+        if let Err(e) = result {
+            warn!("output sink {} failed: {}", sink.name(), e);
+            let _ = app.emit("output-sink-error", OutputSinkErrorEvent { job_id, sink: sink.name().to_string(), error: e.to_string() });
+        }
+    }
+}
+
+/// Copy the last transcript and paste it into the frontmost app. Returns false when
+/// nothing has been transcribed yet.
+pub fn paste_last(app: &AppHandle) -> Result<bool> {
+    let text = app
+        .try_state::<LastTranscript>()
+        .and_then(|last| last.0.lock().ok().and_then(|t| t.clone()));
+    let Some(text) = text else { return Ok(false) };
+    app.clipboard().write_text(text.clone()).map_err(|e| anyhow!(e.to_string()))?;
+    trigger_auto_paste(app, text);
+    Ok(true)
+}
+
+/// Swap the last transcript for `text` and copy it, without pasting again.
+pub fn replace_last_transcript(app: &AppHandle, text: &str) -> Result<()> {
+    app.clipboard().write_text(text.to_string()).map_err(|e| anyhow!(e.to_string()))?;
+    if let Some(last) = app.try_state::<LastTranscript>() {
+        if let Ok(mut last) = last.0.lock() { *last = Some(text.to_string()); }
+    }
+    Ok(())
+}
+
+fn needs_review(app: &AppHandle, text: &str) -> bool {
+    let cfg = read_paste_review_config(app);
+    cfg.enabled && text.chars().count() > cfg.min_chars
+}
+
+/// Park the transcript and show the review window; a window that is already open
+/// switches to the newer transcript via `paste-review`.
+fn open_paste_review(app: &AppHandle, text: &str) -> Result<()> {
+    if let Some(pending) = app.try_state::<PendingPasteReview>() {
+        if let Ok(mut pending) = pending.0.lock() { *pending = Some(text.to_string()); }
+    }
+    if let Some(win) = app.get_webview_window(PASTE_REVIEW_WINDOW) {
+        let _ = app.emit_to(PASTE_REVIEW_WINDOW, "paste-review", text.to_string());
+        win.show()?;
+        win.set_focus()?;
+        return Ok(());
+    }
+    info!("transcript of {} chars held for review before pasting", text.chars().count());
+    WebviewWindowBuilder::new(app, PASTE_REVIEW_WINDOW, WebviewUrl::App("review.html".into()))
+        .title("Review transcript")
+        .inner_size(480.0, 320.0)
+        .always_on_top(true)
+        .center()
+        .build()?;
+    Ok(())
+}
+
+pub fn pending_paste_review(app: &AppHandle) -> Option<String> {
+    app.try_state::<PendingPasteReview>()
+        .and_then(|pending| pending.0.lock().ok().and_then(|t| t.clone()))
+}
+
+/// Close the review window. With `text`, the (possibly edited) transcript is copied and
+/// pasted into the app that was frontmost before the window appeared.
+pub fn finish_paste_review(app: &AppHandle, text: Option<String>) -> Result<()> {
+    if let Some(pending) = app.try_state::<PendingPasteReview>() {
+        if let Ok(mut pending) = pending.0.lock() { *pending = None; }
+    }
+    if let Some(win) = app.get_webview_window(PASTE_REVIEW_WINDOW) {
+        win.close()?;
+    }
+    let Some(text) = text else { return Ok(()) };
+    app.clipboard().write_text(text.clone()).map_err(|e| anyhow!(e.to_string()))?;
+    if let Some(last) = app.try_state::<LastTranscript>() {
+        if let Ok(mut last) = last.0.lock() { *last = Some(text.clone()); }
+    }
+    // Hand focus back to the previous app so Cmd+V does not land in Commander.
+    app.hide()?;
+    trigger_auto_paste(app, text);
+    Ok(())
+}
+
+fn append_to_file(path: &str, text: &str) -> Result<()> {
+    let path = std::path::Path::new(path);
+    if let Some(dir) = path.parent() { std::fs::create_dir_all(dir)?; }
+    let mut file = std::fs::OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", text)?;
+    Ok(())
+}
+
+/// Fire-and-forget POST of `{job_id, text}`; failures are only logged.
+fn post_webhook(url: String, job_id: u64, text: String) {
+    std::thread::spawn(move || {
+        let rt = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(rt) => rt,
+            Err(e) => { warn!("webhook sink: failed to build runtime: {}", e); return; }
+        };
+        rt.block_on(async move {
+            let resp = reqwest::Client::new()
+                .post(&url)
+                .json(&serde_json::json!({ "job_id": job_id, "text": text }))
+                .send()
+                .await;
+            match resp {
+                Ok(r) if !r.status().is_success() => warn!("webhook sink {} returned {}", url, r.status()),
+                Err(e) => warn!("webhook sink {} failed: {}", url, e),
+                _ => {}
+            }
+        });
+    });
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct HistoryEntry {
+    pub job_id: u64,
+    pub timestamp_ms: u128,
+    pub text: String,
+    /// When the dictation was recorded; missing in entries written by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub recording: Option<RecordingSpan>,
+    /// Language detected for dictations transcribed with `auto`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detected_language: Option<LanguageProbability>,
+}
+
+pub fn history_path(app: &AppHandle) -> Result<std::path::PathBuf> {
+    Ok(app.path().app_data_dir()?.join("history.jsonl"))
+}
+
+fn append_history(app: &AppHandle, job_id: u64, text: &str) -> Result<()> {
+    let job = get_job(job_id);
+    let entry = HistoryEntry {
+        job_id,
+        timestamp_ms: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis(),
+        text: text.to_string(),
+        recording: job.as_ref().and_then(|job| job.recording),
+        detected_language: job.and_then(|job| job.detected_language),
+    };
+    append_to_file(&history_path(app)?.to_string_lossy(), &serde_json::to_string(&entry)?)
+}
+
+/// Resolve auto-paste for the current frontmost app: a matching per-app rule wins,
+/// otherwise the global toggle applies.
+fn should_auto_paste(app: &AppHandle) -> bool {
+    let global = is_auto_paste_enabled(app);
+    let rules = read_auto_paste_rules(app);
+    if rules.rules.is_empty() { return global; }
+    match frontmost_application() {
+        Some(front) => match rules.rules.iter().find(|r| front.matches(&r.app)) {
+            Some(rule) => {
+                info!("auto-paste rule for {:?} ({:?}): allow={}", front.name, front.bundle_id, rule.allow);
+                rule.allow
+            }
+            None => global,
+        },
+        None => global,
+    }
+}
+
+fn trigger_auto_paste(app: &AppHandle, _text: String) {
+    let timing = read_auto_paste_timing_config(app);
+    let own_bundle_id = app.config().identifier.clone();
+    let app = app.clone();
+    std::thread::spawn(move || {
+        use std::time::Duration;
+        std::thread::sleep(Duration::from_millis(timing.delay_ms));
+        if timing.wait_for_frontmost && !wait_for_stable_frontmost(&own_bundle_id, timing.frontmost_timeout_ms) {
+            warn!("auto-paste skipped: no stable target app within {} ms", timing.frontmost_timeout_ms);
+            let _ = app.emit("auto-paste-skipped", serde_json::json!({ "reason": "frontmost_app_not_ready" }));
+            return;
+        }
+        #[cfg(target_os = "macos")]
+        {
+            use rdev::{simulate, EventType, Key};
+            let _ = simulate(&EventType::KeyPress(Key::MetaLeft));
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let _ = simulate(&EventType::KeyPress(Key::KeyV));
+            let _ = simulate(&EventType::KeyRelease(Key::KeyV));
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            let _ = simulate(&EventType::KeyRelease(Key::MetaLeft));
+        }
+    });
+}
+
+/// Poll until the same non-Commander app is frontmost on two consecutive checks, so the
+/// paste lands after focus has settled. Returns false on timeout.
+fn wait_for_stable_frontmost(own_bundle_id: &str, timeout_ms: u64) -> bool {
+    const POLL_MS: u64 = 50;
+    let deadline = Instant::now() + std::time::Duration::from_millis(timeout_ms);
+    let mut previous: Option<String> = None;
+    while Instant::now() < deadline {
+        let current = frontmost_application()
+            .and_then(|f| f.bundle_id)
+            .filter(|b| b != own_bundle_id);
+        if current.is_some() && current == previous {
+            return true;
+        }
+        previous = current;
+        std::thread::sleep(std::time::Duration::from_millis(POLL_MS));
+    }
+    false
+}
+
@@ -0,0 +1,121 @@
+//! Transcribe a long recording in chunks while it is still running, so only the tail is
+//! left to transcribe when the user stops.
+
+use crate::audio::RecorderState;
+use crate::config::StreamingConfig;
+use crate::jobs::reserve_job_id;
+use crate::transcription::{transcribe_chunk, TranscriptionOverrides};
+use anyhow::{anyhow, Result};
+use log::info;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter};
+
+const POLL_INTERVAL_MS: u64 = 500;
+/// A chunk ends at the quietest 20 ms frame within this much of its nominal end, so words
+/// are not cut in half.
+const CUT_SEARCH_MS: usize = 2000;
+const CUT_FRAME_MS: usize = 20;
+
+/// Text transcribed from the start of a recording before it was stopped.
+pub struct StreamedPrefix {
+    /// Leading samples of the recording the text covers.
+    pub samples: usize,
+    pub text: String,
+}
+
+/// The chunk worker of one recording; `finish` waits for its last chunk.
+pub struct StreamingWorker {
+    handle: JoinHandle<Result<String>>,
+    /// What the chunks were transcribed with; a stop with other overrides cannot reuse them.
+    overrides: TranscriptionOverrides,
+    /// Reserved when streaming starts; the recording's job is registered under it on stop.
+    job_id: u64,
+}
+
+impl StreamingWorker {
+    pub fn job_id(&self) -> u64 {
+        self.job_id
+    }
+
+    pub fn overrides(&self) -> &TranscriptionOverrides {
+        &self.overrides
+    }
+
+    /// Wait for the in-flight chunk and return the stitched text covering `samples`.
+    pub fn finish(self, samples: usize) -> Result<StreamedPrefix> {
+        let text = self.handle.join().map_err(|_| anyhow!("streaming worker panicked"))??;
+        Ok(StreamedPrefix { samples, text })
+    }
+}
+
+static ACTIVE_WORKER: OnceLock<Mutex<Option<StreamingWorker>>> = OnceLock::new();
+
+fn active_worker() -> &'static Mutex<Option<StreamingWorker>> {
+    ACTIVE_WORKER.get_or_init(|| Mutex::new(None))
+}
+
+/// The worker of the recording that just stopped, if it was streamed.
+pub fn take_worker() -> Option<StreamingWorker> {
+    active_worker().lock().ok()?.take()
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct PartialTranscriptionEvent {
+    pub text: String,
+    pub audio_ms: u64,
+}
+
+/// Start chunking the recording that began at `started`, with the overrides it was started
+/// with. The worker ends on its own once that recording stops or is cancelled;
+/// `take_worker` hands it to the final transcription.
+pub fn start_streaming(app: &AppHandle, recorder: &Arc<Mutex<RecorderState>>, started: Instant, cfg: &StreamingConfig, overrides: &TranscriptionOverrides) {
+    let app = app.clone();
+    let recorder = recorder.clone();
+    let chunk_secs = cfg.chunk_secs.max(5) as usize;
+    let job_id = reserve_job_id();
+    let chunk_overrides = overrides.clone();
+    let handle = std::thread::spawn(move || -> Result<String> {
+        let mut parts: Vec<String> = Vec::new();
+        loop {
+            std::thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+            let (chunk, rate, offset_samples) = {
+                let mut st = recorder.lock().map_err(|_| anyhow!("recorder unavailable"))?;
+                if !st.is_recording || st.start_instant != Some(started) { break; }
+                let rate = st.sample_rate_hz as usize;
+                let start = st.streamed_samples;
+                let end = start + chunk_secs * rate;
+                if st.buffer.len() < end { continue; }
+                let chunk = st.buffer.copy_range(start..end);
+                let cut = quietest_cut(&chunk, rate);
+                st.streamed_samples = start + cut;
+                (chunk[..cut].to_vec(), rate as u32, start + cut)
+            };
+            let text = transcribe_chunk(&app, job_id, &chunk, rate, &chunk_overrides)?;
+            if !text.trim().is_empty() { parts.push(text.trim().to_string()); }
+            let audio_ms = offset_samples as u64 * 1000 / rate.max(1) as u64;
+            info!("streamed chunk transcribed; {} ms of the recording done", audio_ms);
+            let _ = app.emit("partial-transcription", PartialTranscriptionEvent { text: parts.join(" "), audio_ms });
+        }
+        Ok(parts.join(" "))
+    });
+    if let Ok(mut active) = active_worker().lock() {
+        *active = Some(StreamingWorker { handle, overrides: overrides.clone(), job_id });
+    }
+}
+
+/// Offset into `chunk` to cut at: the start of the quietest frame near its end.
+fn quietest_cut(chunk: &[f32], rate: usize) -> usize {
+    let frame = (rate * CUT_FRAME_MS / 1000).max(1);
+    let search_start = chunk.len().saturating_sub(rate * CUT_SEARCH_MS / 1000);
+    (search_start..chunk.len().saturating_sub(frame))
+        .step_by(frame)
+        .min_by(|&a, &b| energy(&chunk[a..a + frame]).total_cmp(&energy(&chunk[b..b + frame])))
+        .filter(|&i| i > 0)
+        .unwrap_or(chunk.len())
+}
+
+fn energy(frame: &[f32]) -> f32 {
+    frame.iter().map(|v| v * v).sum()
+}
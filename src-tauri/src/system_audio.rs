@@ -0,0 +1,69 @@
+use anyhow::{anyhow, Result};
+use core_media_rs::cm_sample_buffer::CMSampleBuffer;
+use screencapturekit::shareable_content::SCShareableContent;
+use screencapturekit::stream::configuration::SCStreamConfiguration;
+use screencapturekit::stream::content_filter::SCContentFilter;
+use screencapturekit::stream::output_trait::SCStreamOutputTrait;
+use screencapturekit::stream::output_type::SCStreamOutputType;
+use screencapturekit::stream::SCStream;
+use std::sync::Mutex;
+
+/// ScreenCaptureKit delivers audio at the rate we ask for; 48 kHz mono matches RNNoise.
+pub const SYSTEM_AUDIO_SAMPLE_RATE: u32 = 48_000;
+
+/// Everything the Mac is playing, captured through ScreenCaptureKit. Needs the Screen
+/// Recording permission. Capture stops when this is dropped, like a CPAL stream.
+pub struct SystemAudioCapture {
+    stream: SCStream,
+}
+
+impl Drop for SystemAudioCapture {
+    fn drop(&mut self) {
+        if let Err(e) = self.stream.stop_capture() {
+            log::warn!("failed to stop system audio capture: {:?}", e);
+        }
+    }
+}
+
+struct AudioOutput<F> {
+    on_samples: Mutex<F>,
+}
+
+impl<F> SCStreamOutputTrait for AudioOutput<F>
+where
+    F: FnMut(&[f32], usize) + Send + 'static,
+{
+    fn did_output_sample_buffer(&self, sample: CMSampleBuffer, of_type: SCStreamOutputType) {
+        if !matches!(of_type, SCStreamOutputType::Audio) { return; }
+        let Ok(list) = sample.get_audio_buffer_list() else { return };
+        let Ok(mut on_samples) = self.on_samples.lock() else { return };
+        // One channel was requested, so every buffer is plain mono f32.
+        for buffer in list.buffers() {
+            let samples: Vec<f32> = buffer
+                .data()
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .collect();
+            if !samples.is_empty() { on_samples(&samples, 1); }
+        }
+    }
+}
+
+/// Start capturing system output as mono f32 at `SYSTEM_AUDIO_SAMPLE_RATE`.
+pub fn start_system_audio_capture<F>(on_samples: F) -> Result<SystemAudioCapture>
+where
+    F: FnMut(&[f32], usize) + Send + 'static,
+{
+    let content = SCShareableContent::get().map_err(|e| anyhow!("screen capture unavailable (is Screen Recording allowed?): {:?}", e))?;
+    let display = content.displays().into_iter().next().ok_or_else(|| anyhow!("no display to capture audio from"))?;
+    let filter = SCContentFilter::new().with_display_excluding_windows(&display, &[]);
+    let config = SCStreamConfiguration::new()
+        .set_captures_audio(true)
+        .and_then(|c| c.set_sample_rate(SYSTEM_AUDIO_SAMPLE_RATE))
+        .and_then(|c| c.set_channel_count(1))
+        .map_err(|e| anyhow!("invalid capture configuration: {:?}", e))?;
+    let mut stream = SCStream::new(&filter, &config);
+    stream.add_output_handler(AudioOutput { on_samples: Mutex::new(on_samples) }, SCStreamOutputType::Audio);
+    stream.start_capture().map_err(|e| anyhow!("failed to start system audio capture: {:?}", e))?;
+    Ok(SystemAudioCapture { stream })
+}
@@ -1,27 +1,381 @@
-use crate::config::{is_auto_paste_enabled, LanguageConfig, PromptConfig};
+use crate::config::{active_profile, is_inline_timestamps_enabled, read_model_config, read_whisper_params_config, read_decoding_config, read_input_source_language_config, read_append_mode_config, read_meeting_mode_config, read_quality_guard_config, read_fast_preview_config, read_storage_config, read_latency_budget_config, current_noise_floor_db, temp_dir, LanguageConfig, PromptConfig};
+use crate::audio::{level_stats, LevelStats};
+use crate::platform::input_source_language;
+use crate::sinks::{replace_last_transcript, run_output_sinks};
+use crate::phrases::{record_usage as record_phrase_usage, weave_into_prompt};
+use crate::quality::assess;
+use crate::sessions::record_session_result;
+use crate::streaming::StreamedPrefix;
+use crate::vad::{speech_bounds, speech_threshold_db};
 use anyhow::{anyhow, Result};
-use std::time::Instant;
+use log::{info, warn};
+use crate::http_server::{client_token, set_last_transcription, LastTranscription};
+use crate::jobs::{complete_job, get_job, set_job_language, set_job_rejected_text, RecordingSpan};
+use crate::perf_stats::{latency_hint, latency_report, record_run, LatencyBudgetExceeded};
+use crate::whisper_service::{LanguageProbability, ServiceTimings, Transcript, WordTiming};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Instant, SystemTime};
 use tauri::{image::Image, AppHandle, Emitter, Manager};
-use tauri_plugin_clipboard_manager::ClipboardExt;
 
 #[derive(Clone, serde::Serialize)]
 pub struct TranscriptionEvent {
+    pub job_id: u64,
     pub text: String,
+    /// Word timings relative to the start of the recording; empty for streamed and meeting dictations.
+    pub words: Vec<WordTiming>,
+    /// Language whisper detected when the dictation language is `auto`.
+    pub detected_language: Option<LanguageProbability>,
+    /// Mean word probability, 0–1; `None` for streamed and meeting dictations.
+    pub confidence: Option<f32>,
+    /// Guidance when the input looked unusable (e.g. near-silent microphone).
+    pub warning: Option<String>,
+    /// When the audio was recorded, if known.
+    pub recording: Option<RecordingSpan>,
 }
 
-pub fn transcribe_and_copy(app: &AppHandle, pcm_mono_16: Vec<i16>, sample_rate_hz: u32) -> Result<()> {
+/// One-off settings for a single dictation; unset fields fall back to the saved config.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct TranscriptionOverrides {
+    pub language: Option<String>,
+    pub prompt: Option<String>,
+    /// Catalog id of an installed model to use instead of the selected one.
+    pub model: Option<String>,
+    /// Translate into English; unset follows the saved setting.
+    pub translate: Option<bool>,
+}
+
+impl TranscriptionOverrides {
+    /// Model whose stats this dictation feeds: the override, else the selected model.
+    pub fn effective_model_id(&self, app: &AppHandle) -> Option<String> {
+        self.model.clone().or_else(|| read_model_config(app).and_then(|c| c.selected_id))
+    }
+}
+
+const TOO_QUIET_WARNING: &str = "Your microphone input was very quiet. Check the selected input device, move closer to the mic, or raise the input volume in System Settings → Sound.";
+
+/// Transcribe a dictation and deliver it. With `streamed`, the leading part of the recording
+/// was already transcribed while recording; only the rest is sent and the texts are joined.
+pub fn transcribe_and_copy(app: &AppHandle, job_id: u64, pcm_mono: Vec<f32>, sample_rate_hz: u32, streamed: Option<StreamedPrefix>, overrides: &TranscriptionOverrides) -> Result<()> {
+    let t_total = Instant::now();
+    let (prefix, pcm_mono) = match streamed {
+        Some(streamed) => {
+            let start = streamed.samples.min(pcm_mono.len());
+            (Some(streamed.text), pcm_mono[start..].to_vec())
+        }
+        None => (None, pcm_mono),
+    };
+    if let Some(prefix) = &prefix {
+        if (pcm_mono.len() as u64) * 1000 < MIN_STREAMED_TAIL_MS * sample_rate_hz as u64 {
+            let profile_json = serde_json::json!({ "client": { "streamed": true } });
+            return deliver_transcript(app, job_id, Transcript { text: prefix.clone(), ..Default::default() }, None, profile_json, 0, t_total, overrides);
+        }
+    }
+    let pcm_len = pcm_mono.len();
+    let noise_floor_db = current_noise_floor_db(app);
+    let levels = level_stats(&pcm_mono);
+    // Whisper turns silence into stock phrases like "Thank you.", so nothing is sent at all.
+    if prefix.is_none() && speech_bounds(&pcm_mono, sample_rate_hz, speech_threshold_db(noise_floor_db), 0).is_none() {
+        return skip_silent_recording(app, job_id, pcm_len as u64 * 1000 / sample_rate_hz.max(1) as u64, levels);
+    }
+    let warning = if levels.is_too_quiet(noise_floor_db) {
+        warn!("input too quiet: rms {:.1} dBFS, peak {:.1} dBFS", levels.rms_db, levels.peak_db);
+        let _ = app.emit("input-too-quiet", &levels);
+        Some(TOO_QUIET_WARNING.to_string())
+    } else {
+        None
+    };
+    let speech = trim_silence(&pcm_mono, sample_rate_hz, noise_floor_db);
+    let lead_ms = speech.start as u64 * 1000 / sample_rate_hz.max(1) as u64;
+    let pcm = &pcm_mono[speech];
+    let trimmed_ms = (pcm_len - pcm.len()) as u64 * 1000 / sample_rate_hz.max(1) as u64;
+    let timestamps = is_inline_timestamps_enabled(app);
+    // The final pass would only cover the tail of a streamed recording, so it gets no preview.
+    let preview_overrides = if prefix.is_some() { None } else { preview_overrides(app, overrides) };
+    let first_pass = preview_overrides.as_ref().unwrap_or(overrides);
+    let (mut transcript, mut profile_json) = request_transcription(app, job_id, pcm, sample_rate_hz, "record", timestamps, first_pass, None)?;
+    let guard = read_quality_guard_config(app);
+    if guard.enabled {
+        let language = match &transcript.detected_language {
+            Some(detected) => Some(detected.language.clone()),
+            None => profile_json["client"]["language"].as_str().map(str::to_string),
+        };
+        if let Some(issue) = assess(&transcript.text, language.as_deref(), warning.is_none(), guard.max_repetition_ratio) {
+            warn!("transcript failed quality check ({:?}); retrying with greedy decoding", issue);
+            let (retry, retry_profile) = request_transcription(app, job_id, pcm, sample_rate_hz, "record", timestamps, first_pass, Some(guard.retry_temperature))?;
+            let retry_issue = assess(&retry.text, language.as_deref(), warning.is_none(), guard.max_repetition_ratio);
+            profile_json["quality"] = serde_json::json!({
+                "issue": issue,
+                "retried": true,
+                "retry_temperature": guard.retry_temperature,
+                "retry_issue": retry_issue,
+                "retry": retry_profile,
+            });
+            if let Some(retry_issue) = retry_issue {
+                // Still a failure, but the likelier of the two texts goes with it.
+                let best = if transcript.confidence.unwrap_or(0.0) > retry.confidence.unwrap_or(0.0) { &transcript } else { &retry };
+                let text = [prefix.as_deref().unwrap_or(""), best.text.trim()].iter().filter(|t| !t.is_empty()).copied().collect::<Vec<_>>().join(" ");
+                set_job_rejected_text(job_id, text);
+                app.emit("transcription-profile", &profile_json).ok();
+                return Err(anyhow!("transcript failed quality check after retry: {:?}", retry_issue));
+            }
+            transcript = retry;
+        }
+    }
+    profile_json["client"]["trimmed_silence_ms"] = serde_json::json!(trimmed_ms);
+    if let Some(prefix) = prefix {
+        profile_json["client"]["streamed"] = serde_json::json!(true);
+        transcript.text = [prefix.as_str(), transcript.text.trim()].iter().filter(|t| !t.is_empty()).copied().collect::<Vec<_>>().join(" ");
+        transcript.words.clear();
+        transcript.confidence = None;
+    }
+    for word in &mut transcript.words {
+        word.start_ms += lead_ms;
+        word.end_ms += lead_ms;
+    }
+    let audio_ms = pcm.len() as u64 * 1000 / sample_rate_hz.max(1) as u64;
+    if preview_overrides.is_none() {
+        return deliver_transcript(app, job_id, transcript, warning, profile_json, audio_ms, t_total, overrides);
+    }
+    let preview = transcript.text.clone();
+    deliver_transcript(app, job_id, transcript, warning, profile_json, audio_ms, t_total, first_pass)?;
+    spawn_final_pass(app.clone(), job_id, pcm.to_vec(), sample_rate_hz, timestamps, overrides.clone(), preview);
+    Ok(())
+}
+
+/// Overrides for the fast preview pass, or `None` when this dictation should go straight to
+/// the final model (preview off, no preview model, or an explicit model was requested).
+fn preview_overrides(app: &AppHandle, overrides: &TranscriptionOverrides) -> Option<TranscriptionOverrides> {
+    let cfg = read_fast_preview_config(app);
+    if !cfg.enabled || overrides.model.is_some() { return None; }
+    let preview_model = cfg.preview_model_id?;
+    if Some(&preview_model) == overrides.effective_model_id(app).as_ref() { return None; }
+    Some(TranscriptionOverrides { model: Some(preview_model), ..overrides.clone() })
+}
+
+/// A final-model transcript of a dictation that was already pasted from the preview model.
+#[derive(Clone, serde::Serialize)]
+pub struct ImprovedTranscript {
+    pub job_id: u64,
+    pub preview: String,
+    pub text: String,
+}
+
+/// The latest improved transcript, until it is applied or replaced by a newer one.
+#[derive(Default)]
+pub struct PendingImprovedTranscript(Mutex<Option<ImprovedTranscript>>);
+
+/// Job of the transcript delivered most recently. An improved transcript is only offered
+/// while its job is still the latest, so it never replaces a newer dictation.
+static LAST_DELIVERED_JOB: AtomicU64 = AtomicU64::new(0);
+
+/// Re-run the dictation on the selected model in the background and offer the result via
+/// `improved-transcript-ready` when it differs from the pasted preview.
+fn spawn_final_pass(app: AppHandle, job_id: u64, pcm: Vec<f32>, sample_rate_hz: u32, timestamps: bool, overrides: TranscriptionOverrides, preview: String) {
+    std::thread::spawn(move || {
+        let text = match request_transcription(&app, job_id, &pcm, sample_rate_hz, "record_final", timestamps, &overrides, None) {
+            Ok((transcript, _)) => transcript.text,
+            Err(e) => {
+                warn!("final pass for job {} failed: {}", job_id, e);
+                return;
+            }
+        };
+        if text.trim() == preview.trim() {
+            info!("final pass for job {} matches the preview", job_id);
+            return;
+        }
+        if LAST_DELIVERED_JOB.load(Ordering::Relaxed) != job_id {
+            info!("final pass for job {} finished after a newer dictation; dropping it", job_id);
+            return;
+        }
+        let improved = ImprovedTranscript { job_id, preview, text };
+        if let Some(pending) = app.try_state::<PendingImprovedTranscript>() {
+            if let Ok(mut pending) = pending.0.lock() { *pending = Some(improved.clone()); }
+        }
+        let _ = app.emit("improved-transcript-ready", improved);
+    });
+}
+
+/// Put the pending improved transcript on the clipboard in place of the preview.
+/// Returns `None` when there is nothing to apply.
+pub fn apply_improved_transcript(app: &AppHandle) -> Result<Option<ImprovedTranscript>> {
+    let pending = app.try_state::<PendingImprovedTranscript>().ok_or_else(|| anyhow!("improved transcript unavailable"))?;
+    let improved = pending.0.lock().map_err(|_| anyhow!("improved transcript poisoned"))?.take();
+    let Some(improved) = improved else { return Ok(None) };
+    if LAST_DELIVERED_JOB.load(Ordering::Relaxed) != improved.job_id {
+        info!("improved transcript for job {} is stale; a newer dictation was delivered", improved.job_id);
+        return Ok(None);
+    }
+    replace_last_transcript(app, &improved.text)?;
+    let _ = app.emit("improved-transcript-applied", improved.clone());
+    Ok(Some(improved))
+}
+
+/// A streamed recording's tail shorter than this is not worth a transcription request.
+const MIN_STREAMED_TAIL_MS: u64 = 300;
+
+/// Transcribe one chunk of a recording that is still running, under the job id reserved for it.
+pub fn transcribe_chunk(app: &AppHandle, job_id: u64, pcm_mono: &[f32], sample_rate_hz: u32, overrides: &TranscriptionOverrides) -> Result<String> {
+    let (transcript, _) = request_transcription(app, job_id, pcm_mono, sample_rate_hz, "stream_chunk", false, overrides, None)?;
+    Ok(transcript.text)
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct NoSpeechEvent {
+    pub job_id: u64,
+    pub audio_ms: u64,
+    pub levels: LevelStats,
+}
+
+/// End a dictation in which no frame rose above the speech threshold, without transcribing it.
+fn skip_silent_recording(app: &AppHandle, job_id: u64, audio_ms: u64, levels: LevelStats) -> Result<()> {
+    info!("no speech detected in {} ms of audio (rms {:.1} dBFS), skipping transcription", audio_ms, levels.rms_db);
+    app.emit("no-speech-detected", NoSpeechEvent { job_id, audio_ms, levels })?;
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        let _ = tray.set_tooltip(Some("No speech detected"));
+        let _ = tray.set_icon(Some(Image::from_bytes(include_bytes!("../icons/commander-icon.png"))?));
+    }
+    complete_job(job_id);
+    let app2 = app.clone();
+    std::thread::spawn(move || {
+        std::thread::sleep(std::time::Duration::from_secs(2));
+        if let Some(tray) = app2.tray_by_id("main-tray") {
+            let _ = tray.set_tooltip(Some("Commander"));
+        }
+    });
+    Ok(())
+}
+
+/// Kept around detected speech so word onsets and endings are not clipped.
+const TRIM_PADDING_MS: u32 = 200;
+
+/// Drop leading and trailing silence so whisper does not spend time on dead air.
+/// Returns the range of `pcm` to keep, all of it when no speech is detected.
+fn trim_silence(pcm: &[f32], sample_rate_hz: u32, noise_floor_db: Option<f32>) -> std::ops::Range<usize> {
+    speech_bounds(pcm, sample_rate_hz, speech_threshold_db(noise_floor_db), TRIM_PADDING_MS).unwrap_or(0..pcm.len())
+}
+
+/// Meeting mode: transcribe the mic and loopback tracks separately and interleave them
+/// by segment time as "Me:" / "Them:" lines.
+pub fn transcribe_meeting(app: &AppHandle, job_id: u64, mic: Vec<f32>, mic_rate_hz: u32, loopback: Vec<f32>, loopback_rate_hz: u32, overrides: &TranscriptionOverrides) -> Result<()> {
     let t_total = Instant::now();
-    let tmp_dir = std::env::temp_dir();
-    let wav_path = tmp_dir.join("commander_record.wav");
+    let cfg = read_meeting_mode_config(app);
+    let (me, mut profile_json) = request_transcription(app, job_id, &mic, mic_rate_hz, "record_me", true, overrides, None)?;
+    let (them, them_profile) = request_transcription(app, job_id, &loopback, loopback_rate_hz, "record_them", true, overrides, None)?;
+    profile_json["loopback"] = them_profile;
+    let text = merge_tracks(&me.text, &cfg.me_label, &them.text, &cfg.them_label, is_inline_timestamps_enabled(app));
+    let audio_ms = mic.len() as u64 * 1000 / mic_rate_hz.max(1) as u64;
+    deliver_transcript(app, job_id, Transcript { text, ..Default::default() }, None, profile_json, audio_ms, t_total, overrides)
+}
+
+/// One timed piece of a transcript.
+#[derive(Clone, serde::Serialize)]
+pub struct TranscriptSegment {
+    pub start_ms: u64,
+    pub text: String,
+}
+
+/// Transcribe audio outside the dictation flow (no sinks, no events) into timed segments.
+pub fn transcribe_to_segments(app: &AppHandle, job_id: u64, pcm_mono: &[f32], sample_rate_hz: u32) -> Result<Vec<TranscriptSegment>> {
+    let (transcript, _) = request_transcription(app, job_id, pcm_mono, sample_rate_hz, "watch_folder", true, &TranscriptionOverrides::default(), None)?;
+    Ok(to_segments(&transcript.text))
+}
+
+/// Transcribe a recording uploaded by a paired phone, with the saved settings plus the upload's overrides.
+pub fn transcribe_upload(app: &AppHandle, job_id: u64, pcm_mono: &[f32], sample_rate_hz: u32, overrides: &TranscriptionOverrides) -> Result<String> {
+    let (transcript, _) = request_transcription(app, job_id, pcm_mono, sample_rate_hz, "companion", false, overrides, None)?;
+    Ok(transcript.text)
+}
+
+/// Segments of a transcript that carries inline `[mm:ss]` timestamps; `None` when it has none.
+pub fn timestamped_segments(text: &str) -> Option<Vec<TranscriptSegment>> {
+    let first = text.split_whitespace().next()?;
+    (first.starts_with('[') && first.ends_with(']') && first.contains(':')).then(|| to_segments(text))
+}
+
+fn to_segments(text: &str) -> Vec<TranscriptSegment> {
+    parse_timestamped(text)
+        .into_iter()
+        .map(|(secs, text)| TranscriptSegment { start_ms: secs as u64 * 1000, text })
+        .collect()
+}
+
+/// Split "[mm:ss] text [mm:ss] text" output into (seconds, text) segments.
+fn parse_timestamped(text: &str) -> Vec<(u32, String)> {
+    let mut segments: Vec<(u32, String)> = Vec::new();
+    for word in text.split_whitespace() {
+        let stamp = word
+            .strip_prefix('[')
+            .and_then(|w| w.strip_suffix(']'))
+            .and_then(|w| w.split_once(':'))
+            .and_then(|(m, s)| Some(m.parse::<u32>().ok()? * 60 + s.parse::<u32>().ok()?));
+        match (stamp, segments.last_mut()) {
+            (Some(t), _) => segments.push((t, String::new())),
+            (None, Some((_, seg))) => {
+                if !seg.is_empty() { seg.push(' '); }
+                seg.push_str(word);
+            }
+            (None, None) => segments.push((0, word.to_string())),
+        }
+    }
+    segments.retain(|(_, s)| !s.is_empty());
+    segments
+}
+
+fn merge_tracks(me: &str, me_label: &str, them: &str, them_label: &str, keep_timestamps: bool) -> String {
+    let mut segments: Vec<(u32, &str, String)> = parse_timestamped(me).into_iter().map(|(t, s)| (t, me_label, s)).collect();
+    segments.extend(parse_timestamped(them).into_iter().map(|(t, s)| (t, them_label, s)));
+    // Stable sort keeps the mic first when both speak in the same second.
+    segments.sort_by_key(|(t, _, _)| *t);
+    let mut lines: Vec<String> = Vec::new();
+    let mut current: Option<&str> = None;
+    for (t, label, seg) in segments {
+        if current == Some(label) {
+            if let Some(line) = lines.last_mut() {
+                line.push(' ');
+                line.push_str(&seg);
+                continue;
+            }
+        }
+        current = Some(label);
+        if keep_timestamps {
+            lines.push(format!("[{:02}:{:02}] {}: {}", t / 60, t % 60, label, seg));
+        } else {
+            lines.push(format!("{}: {}", label, seg));
+        }
+    }
+    lines.join("\n")
+}
+
+static WAV_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// Write `pcm` to a temp WAV, post it to the local server and return the transcript and timing profile.
+/// The WAV is deleted once read unless `keep_temp_wavs` is set. `fallback_temperature` switches
+/// to greedy decoding at that temperature, for quality-guard retries.
+#[allow(clippy::too_many_arguments)]
+fn request_transcription(app: &AppHandle, job_id: u64, pcm_mono: &[f32], sample_rate_hz: u32, wav_label: &str, timestamps: bool, overrides: &TranscriptionOverrides, fallback_temperature: Option<f32>) -> Result<(Transcript, serde_json::Value)> {
+    let tmp_dir = temp_dir(app);
+    std::fs::create_dir_all(&tmp_dir)?;
+    // Unique per request so concurrent jobs and retries never share or overwrite a file.
+    let seq = WAV_SEQ.fetch_add(1, Ordering::Relaxed);
+    let wav_path = tmp_dir.join(format!("commander_{}_{}_{}.wav", wav_label, job_id, seq));
     let t_wav_start = Instant::now();
-    write_wav_mono(&wav_path, &pcm_mono_16, sample_rate_hz)?;
+    write_wav_mono(&wav_path, pcm_mono, sample_rate_hz)?;
     let wav_write_ms = t_wav_start.elapsed().as_millis();
+    let keep_wav = read_storage_config(app).keep_temp_wavs;
 
     let rt = tokio::runtime::Runtime::new()?;
-    let (text, mut profile_json) = rt.block_on(async move {
+    rt.block_on(async move {
         let client = reqwest::Client::new();
         let t_read_start = Instant::now();
-        let bytes = tokio::fs::read(&wav_path).await?;
+        let read = tokio::fs::read(&wav_path).await;
+        if keep_wav {
+            info!("Keeping temp WAV {}", wav_path.display());
+        } else if let Err(e) = tokio::fs::remove_file(&wav_path).await {
+            warn!("failed to remove temp WAV {}: {}", wav_path.display(), e);
+        }
+        let bytes = read?;
         let read_file_ms = t_read_start.elapsed().as_millis();
         let mut url = std::env::var("WHISPER_LOCAL_URL").unwrap_or_else(|_| "http://127.0.0.1:9000/transcribe".to_string());
 
@@ -40,10 +394,19 @@ pub fn transcribe_and_copy(app: &AppHandle, pcm_mono_16: Vec<i16>, sample_rate_h
         } else {
             maybe_lang = Some("en".to_string());
         }
-        if let Some(lang) = maybe_lang {
+        // Per-dictation hint from the active keyboard layout; the saved default is untouched.
+        if read_input_source_language_config(app).enabled {
+            if let Some(lang) = input_source_language(app) {
+                info!("Using input source language for this dictation: {}", lang);
+                maybe_lang = Some(lang);
+            }
+        }
+        if let Some(lang) = overrides.language.clone() {
+            maybe_lang = Some(lang);
+        }
+        if let Some(lang) = maybe_lang.as_deref() {
             if !lang.is_empty() {
-                if url.contains('?') { url.push('&'); } else { url.push('?'); }
-                url.push_str(&format!("lang={}", urlencoding::encode(&lang)));
+                push_query(&mut url, "lang", lang);
             }
         }
 
@@ -58,17 +421,70 @@ pub fn transcribe_and_copy(app: &AppHandle, pcm_mono_16: Vec<i16>, sample_rate_h
                 }
             }
         }
+        if let Some(prompt) = overrides.prompt.clone() {
+            maybe_prompt = Some(prompt);
+        }
+        let maybe_prompt = weave_into_prompt(app, maybe_prompt);
         if let Some(prompt) = maybe_prompt {
             if !prompt.is_empty() {
-                if url.contains('?') { url.push('&'); } else { url.push('?'); }
-                url.push_str(&format!("prompt={}", urlencoding::encode(&prompt)));
+                push_query(&mut url, "prompt", &prompt);
             }
         }
 
+        if timestamps {
+            push_query(&mut url, "timestamps", "1");
+        }
+        if let Some(model) = overrides.model.as_deref() {
+            push_query(&mut url, "model", model);
+        }
+
+        let decoding = read_decoding_config(app);
+        // Quality-guard retries always decode greedily at their own temperature.
+        match fallback_temperature {
+            Some(temperature) => {
+                push_query(&mut url, "strategy", "greedy");
+                push_query(&mut url, "temperature", &temperature.to_string());
+            }
+            None => {
+                push_query(&mut url, "strategy", decoding.strategy.as_str());
+                push_query(&mut url, "temperature", &decoding.temperature.to_string());
+                push_query(&mut url, "best_of", &decoding.best_of.to_string());
+            }
+        }
+        push_query(&mut url, "beam_size", &decoding.beam_size.to_string());
+        if let Some(patience) = decoding.patience {
+            push_query(&mut url, "patience", &patience.to_string());
+        }
+
+        let params = read_whisper_params_config(app);
+        if let Some(thold) = params.entropy_threshold {
+            push_query(&mut url, "entropy_thold", &thold.to_string());
+        }
+        if let Some(thold) = params.logprob_threshold {
+            push_query(&mut url, "logprob_thold", &thold.to_string());
+        }
+        if let Some(suppress) = params.suppress_blank {
+            push_query(&mut url, "suppress_blank", if suppress { "1" } else { "0" });
+        }
+        if let Some(suppress) = params.suppress_non_speech_tokens {
+            push_query(&mut url, "suppress_nst", if suppress { "1" } else { "0" });
+        }
+        if let Some(thold) = params.no_speech_threshold {
+            push_query(&mut url, "no_speech_thold", &thold.to_string());
+        }
+        for text in params.suppressed_tokens.iter().filter(|t| !t.is_empty()) {
+            push_query(&mut url, "suppress", text);
+        }
+        if overrides.translate.unwrap_or(params.translate) {
+            push_query(&mut url, "translate", "1");
+        }
+
         let t_http_start = Instant::now();
         let resp = client
             .post(url)
             .header("Content-Type", "audio/wav")
+            .header("Authorization", format!("Bearer {}", client_token()))
+            .header("X-Job-Id", job_id.to_string())
             .body(bytes)
             .send()
             .await?;
@@ -80,33 +496,101 @@ pub fn transcribe_and_copy(app: &AppHandle, pcm_mono_16: Vec<i16>, sample_rate_h
         }
 
         let mut profile_json = serde_json::json!({
-            "client": { "wav_write_ms": wav_write_ms, "read_file_ms": read_file_ms, "http_ms": http_ms }
+            "client": { "wav_write_ms": wav_write_ms, "read_file_ms": read_file_ms, "http_ms": http_ms, "language": maybe_lang }
         });
-        let text = if let Ok(v) = serde_json::from_str::<serde_json::Value>(&body) {
-            if let Some(t) = v.get("text").and_then(|t| t.as_str()) {
+        let transcript = if let Ok(v) = serde_json::from_str::<serde_json::Value>(&body) {
+            if let Ok(t) = serde_json::from_value::<Transcript>(v.clone()) {
                 if let Some(p) = v.get("profile").cloned() {
                     profile_json["server"] = p;
                 }
-                t.to_string()
-            } else { body }
-        } else { body };
+                t
+            } else { Transcript { text: body, ..Default::default() } }
+        } else { Transcript { text: body, ..Default::default() } };
+
+        Ok::<(Transcript, serde_json::Value), anyhow::Error>((transcript, profile_json))
+    })
+}
 
-        Ok::<(String, serde_json::Value), anyhow::Error>((text, profile_json))
-    })?;
+/// Tray tooltip while the last dictation is low confidence.
+const LOW_CONFIDENCE_TOOLTIP: &str = "Transcribed (low confidence, check the text)";
+/// Tray title while the last dictation is low confidence.
+const LOW_CONFIDENCE_TITLE: &str = "⚠";
 
-    app.clipboard().write_text(text.clone())?;
-    app.emit("transcription", TranscriptionEvent { text: text.clone() })?;
+/// Publish a finished transcript: events, perf stats, then the append buffer or the output sinks.
+#[allow(clippy::too_many_arguments)]
+fn deliver_transcript(app: &AppHandle, job_id: u64, transcript: Transcript, warning: Option<String>, mut profile_json: serde_json::Value, audio_ms: u64, t_total: Instant, overrides: &TranscriptionOverrides) -> Result<()> {
+    let append_mode = read_append_mode_config(app);
+    let recording = get_job(job_id).and_then(|job| job.recording);
+    LAST_DELIVERED_JOB.store(job_id, Ordering::Relaxed);
+    let Transcript { text, words, detected_language, confidence, .. } = transcript;
+    if let Some(detected) = detected_language.clone() {
+        set_job_language(job_id, detected);
+    }
+    app.emit("transcription", TranscriptionEvent { job_id, text: text.clone(), words, detected_language, confidence, warning: warning.clone(), recording })?;
+    let low_confidence = match (confidence, read_quality_guard_config(app).low_confidence_threshold) {
+        (Some(confidence), Some(threshold)) => confidence < threshold,
+        _ => false,
+    };
+    if low_confidence {
+        info!("low-confidence transcript for job {} ({:.2})", job_id, confidence.unwrap_or_default());
+    }
     let total_ms = t_total.elapsed().as_millis();
+    let model_id = overrides.effective_model_id(app);
+    if let Err(e) = record_phrase_usage(app, &text) {
+        warn!("failed to update phrase usage: {}", e);
+    }
+    set_last_transcription(LastTranscription {
+        job_id,
+        text: text.clone(),
+        timestamp_ms: SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis(),
+        audio_ms,
+        total_ms,
+        model_id: model_id.clone(),
+        warning,
+        recording,
+    });
     profile_json["client"]["total_ms"] = serde_json::json!(total_ms);
+    profile_json["job_id"] = serde_json::json!(job_id);
+    let report = latency_report(job_id, &profile_json, audio_ms, total_ms as u64);
+    profile_json["latency"] = serde_json::to_value(&report).unwrap_or_default();
+    let budget = read_latency_budget_config(app);
+    if budget.enabled && report.total_ms > budget.target_ms {
+        warn!("dictation took {} ms (target {} ms), mostly {}", report.total_ms, budget.target_ms, report.dominant_stage);
+        let hint = latency_hint(report.dominant_stage);
+        app.emit("latency-budget-exceeded", LatencyBudgetExceeded { report, target_ms: budget.target_ms, hint }).ok();
+    }
+    record_session_result(job_id, &text, &profile_json);
+    if let Ok(timings) = serde_json::from_value::<ServiceTimings>(profile_json["server"]["whisper"].clone()) {
+        if let Some(model_id) = &model_id {
+            if let Err(e) = record_run(app, model_id, audio_ms, &timings, total_ms as u64) {
+                warn!("failed to record perf stats: {}", e);
+            }
+        }
+    }
     app.emit("transcription-profile", profile_json).ok();
+    let tooltip = if append_mode.enabled {
+        let pending = append_to_buffer(app, text);
+        match low_confidence {
+            true => format!("Added to buffer ({} pending, low confidence, check the text)", pending),
+            false => format!("Added to buffer ({} pending)", pending),
+        }
+    } else {
+        run_output_sinks(app, job_id, &text, &active_profile(app).output_sinks);
+        match low_confidence {
+            true => LOW_CONFIDENCE_TOOLTIP.to_string(),
+            false => "Transcribed".to_string(),
+        }
+    };
     if let Some(tray) = app.tray_by_id("main-tray") {
-        let _ = tray.set_tooltip(Some("Transcribed and copied"));
+        let _ = tray.set_tooltip(Some(&tooltip));
         let _ = tray.set_icon(Some(Image::from_bytes(include_bytes!("../icons/commander-icon.png"))?));
+        // Shown next to the icon until the next recording starts, so it is not missed.
+        let _ = tray.set_title(low_confidence.then_some(LOW_CONFIDENCE_TITLE));
     }
-    let _ = app.emit("transcription-complete", true);
-
-    if is_auto_paste_enabled(app) {
-        trigger_auto_paste(text.clone());
+    complete_job(job_id);
+    // A low-confidence notice stays until the next recording replaces it.
+    if low_confidence {
+        return Ok(());
     }
     let app2 = app.clone();
     std::thread::spawn(move || {
@@ -118,26 +602,58 @@ pub fn transcribe_and_copy(app: &AppHandle, pcm_mono_16: Vec<i16>, sample_rate_h
     Ok(())
 }
 
-fn trigger_auto_paste(_text: String) {
-    std::thread::spawn(move || {
-        use std::time::Duration;
-        std::thread::sleep(Duration::from_millis(150));
-        #[cfg(target_os = "macos")]
-        {
-            use rdev::{simulate, EventType, Key};
-            let _ = simulate(&EventType::KeyPress(Key::MetaLeft));
-            std::thread::sleep(std::time::Duration::from_millis(50));
-            let _ = simulate(&EventType::KeyPress(Key::KeyV));
-            let _ = simulate(&EventType::KeyRelease(Key::KeyV));
-            std::thread::sleep(std::time::Duration::from_millis(50));
-            let _ = simulate(&EventType::KeyRelease(Key::MetaLeft));
+/// Dictations collected in append mode, waiting for an explicit flush.
+#[derive(Default)]
+pub struct AppendBuffer {
+    parts: Mutex<Vec<String>>,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct AppendBufferEvent {
+    pub pending: usize,
+    pub text: String,
+}
+
+fn append_to_buffer(app: &AppHandle, text: String) -> usize {
+    let Some(buffer) = app.try_state::<AppendBuffer>() else { return 0 };
+    let separator = read_append_mode_config(app).separator;
+    let (pending, joined) = match buffer.parts.lock() {
+        Ok(mut parts) => {
+            if !text.trim().is_empty() { parts.push(text.trim().to_string()); }
+            (parts.len(), parts.join(&separator))
         }
-    });
+        Err(_) => return 0,
+    };
+    let _ = app.emit("append-buffer-updated", AppendBufferEvent { pending, text: joined });
+    pending
+}
+
+/// Join the pending dictations, send them through the active profile's sinks and clear the buffer.
+/// Returns the flushed text, or `None` when nothing was pending.
+pub fn flush_append_buffer(app: &AppHandle) -> Result<Option<String>> {
+    let buffer = app.try_state::<AppendBuffer>().ok_or_else(|| anyhow!("append buffer unavailable"))?;
+    let separator = read_append_mode_config(app).separator;
+    let parts = std::mem::take(&mut *buffer.parts.lock().map_err(|_| anyhow!("append buffer poisoned"))?);
+    if parts.is_empty() { return Ok(None); }
+    let text = parts.join(&separator);
+    let _ = app.emit("append-buffer-flushed", AppendBufferEvent { pending: parts.len(), text: text.clone() });
+    run_output_sinks(app, 0, &text, &active_profile(app).output_sinks);
+    if let Some(tray) = app.tray_by_id("main-tray") {
+        let _ = tray.set_tooltip(Some("Buffer flushed"));
+    }
+    Ok(Some(text))
+}
+
+fn push_query(url: &mut String, key: &str, value: &str) {
+    if url.contains('?') { url.push('&'); } else { url.push('?'); }
+    url.push_str(&format!("{}={}", key, urlencoding::encode(value)));
 }
 
-fn write_wav_mono(path: &std::path::Path, samples: &[i16], sample_rate_hz: u32) -> Result<()> {
-    let spec = hound::WavSpec { channels: 1, sample_rate: sample_rate_hz, bits_per_sample: 16, sample_format: hound::SampleFormat::Int };
-    let mut writer = hound::WavWriter::create(path, spec)?;
+/// Write a float WAV readable only by the current user; the temp dir may be shared.
+fn write_wav_mono(path: &std::path::Path, samples: &[f32], sample_rate_hz: u32) -> Result<()> {
+    let spec = hound::WavSpec { channels: 1, sample_rate: sample_rate_hz, bits_per_sample: 32, sample_format: hound::SampleFormat::Float };
+    let file = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+    let mut writer = hound::WavWriter::new(std::io::BufWriter::new(file), spec)?;
     for s in samples { writer.write_sample(*s)?; }
     writer.finalize()?;
     Ok(())
@@ -0,0 +1,89 @@
+//! Energy-based voice activity detection, shared by everything that needs to tell
+//! speech from silence in a captured buffer.
+
+/// Analysis frame length.
+pub const FRAME_MS: u32 = 30;
+/// Speech threshold used until the input device has been calibrated.
+pub const DEFAULT_SPEECH_THRESHOLD_DBFS: f32 = -45.0;
+/// How far above the calibrated noise floor a frame must be to count as speech.
+const SPEECH_MARGIN_DB: f32 = 10.0;
+
+/// Level above which a frame counts as speech, given the device's noise floor if known.
+pub fn speech_threshold_db(noise_floor_db: Option<f32>) -> f32 {
+    match noise_floor_db {
+        Some(floor) => floor + SPEECH_MARGIN_DB,
+        None => DEFAULT_SPEECH_THRESHOLD_DBFS,
+    }
+}
+
+/// RMS level of each `FRAME_MS` frame in dBFS. Samples are expected in [-1, 1].
+pub fn frame_levels_db(samples: &[f32], sample_rate_hz: u32) -> Vec<f32> {
+    let frame_len = frame_len(sample_rate_hz);
+    samples
+        .chunks(frame_len)
+        .map(|frame| {
+            let mean_sq = frame.iter().map(|s| s * s).sum::<f32>() / frame.len() as f32;
+            10.0 * mean_sq.max(1e-10).log10()
+        })
+        .collect()
+}
+
+pub fn frame_len(sample_rate_hz: u32) -> usize {
+    (sample_rate_hz as usize * FRAME_MS as usize / 1000).max(1)
+}
+
+/// Sample offsets at which to split `samples` into pieces of roughly `target_secs`.
+/// Each cut lands on the quietest frame within `search_secs` of the nominal boundary,
+/// so words are not sliced in half when there is a pause nearby.
+pub fn silence_split_points(samples: &[f32], sample_rate_hz: u32, target_secs: u32, search_secs: u32) -> Vec<usize> {
+    let levels = frame_levels_db(samples, sample_rate_hz);
+    let frame_len = frame_len(sample_rate_hz);
+    let target_frames = (target_secs * 1000 / FRAME_MS) as usize;
+    let search_frames = (search_secs * 1000 / FRAME_MS) as usize;
+    let mut splits = Vec::new();
+    let mut last = 0usize;
+    // Only cut when the remainder is long enough to be worth a separate piece.
+    while last + target_frames + search_frames < levels.len() {
+        let nominal = last + target_frames;
+        let lo = nominal.saturating_sub(search_frames).max(last + 1);
+        let hi = (nominal + search_frames).min(levels.len() - 1);
+        let cut = (lo..=hi)
+            .min_by(|a, b| levels[*a].total_cmp(&levels[*b]))
+            .unwrap_or(nominal);
+        splits.push(cut * frame_len);
+        last = cut;
+    }
+    splits
+}
+
+/// Sample range from the first to the last speech frame, widened by `pad_ms` on each side
+/// so soft onsets and trailing consonants survive. `None` when no frame reaches the threshold.
+pub fn speech_bounds(samples: &[f32], sample_rate_hz: u32, threshold_db: f32, pad_ms: u32) -> Option<std::ops::Range<usize>> {
+    let levels = frame_levels_db(samples, sample_rate_hz);
+    let first = levels.iter().position(|l| *l >= threshold_db)?;
+    let last = levels.iter().rposition(|l| *l >= threshold_db)?;
+    let frame_len = frame_len(sample_rate_hz);
+    let pad = sample_rate_hz as usize * pad_ms as usize / 1000;
+    let start = (first * frame_len).saturating_sub(pad);
+    let end = ((last + 1) * frame_len + pad).min(samples.len());
+    Some(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speech_bounds_pads_the_loud_frames() {
+        let mut samples = vec![0.0f32; 300];
+        samples.extend(std::iter::repeat(0.5).take(300));
+        samples.extend(std::iter::repeat(0.0).take(300));
+        assert_eq!(speech_bounds(&samples, 1000, -45.0, 60), Some(240..660));
+        assert_eq!(speech_bounds(&samples, 1000, -45.0, 1000), Some(0..900));
+    }
+
+    #[test]
+    fn speech_bounds_is_none_for_silence() {
+        assert_eq!(speech_bounds(&[0.0; 900], 1000, -45.0, 60), None);
+    }
+}
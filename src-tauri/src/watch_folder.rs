@@ -0,0 +1,136 @@
+use crate::audio::{is_saved_recording, read_recording_mono};
+use crate::config::read_watch_folder_config;
+use crate::jobs::{complete_job, fail_job, start_job, JobSource};
+use crate::transcription::{transcribe_to_segments, TranscriptSegment};
+use anyhow::Result;
+use log::{info, warn};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter};
+
+#[derive(Clone, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WatchFolderStatus {
+    Started,
+    Completed,
+    Failed,
+}
+
+#[derive(Clone, serde::Serialize)]
+pub struct WatchFolderEvent {
+    pub file: String,
+    pub status: WatchFolderStatus,
+    pub job_id: Option<u64>,
+    pub error: Option<String>,
+    /// Files found but not yet transcribed, including this one while it runs.
+    pub pending: usize,
+}
+
+/// Poll the configured folder and transcribe new WAV and FLAC files into `.txt` (and `.srt`) sidecars.
+/// The config is re-read on every tick, so enabling, disabling or moving the folder needs no restart.
+pub fn start_watch_folder(app: AppHandle) {
+    std::thread::spawn(move || {
+        // Size at the previous scan; a file is only picked up once its size stops changing,
+        // so a recorder still syncing it is left alone.
+        let mut seen_sizes: HashMap<PathBuf, u64> = HashMap::new();
+        let mut failed: HashSet<PathBuf> = HashSet::new();
+        loop {
+            let cfg = read_watch_folder_config(&app);
+            std::thread::sleep(Duration::from_secs(cfg.poll_secs.max(1)));
+            let Some(dir) = cfg.directory.filter(|_| cfg.enabled) else {
+                seen_sizes.clear();
+                continue;
+            };
+            let ready = match scan(Path::new(&dir), &mut seen_sizes, &failed) {
+                Ok(ready) => ready,
+                Err(e) => {
+                    warn!("watch folder {}: {}", dir, e);
+                    continue;
+                }
+            };
+            let mut pending = ready.len();
+            for path in ready {
+                if let Err(e) = process_file(&app, &path, cfg.write_srt, pending) {
+                    warn!("watch folder: {} failed: {}", path.display(), e);
+                    failed.insert(path.clone());
+                }
+                seen_sizes.remove(&path);
+                pending -= 1;
+            }
+        }
+    });
+}
+
+/// Audio files without a `.txt` sidecar whose size matched the previous scan. An entry that
+/// cannot be read, such as a file deleted mid-scan, is skipped rather than ending the scan.
+fn scan(dir: &Path, seen_sizes: &mut HashMap<PathBuf, u64>, failed: &HashSet<PathBuf>) -> Result<Vec<PathBuf>> {
+    let mut ready = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let Ok(entry) = entry else { continue };
+        let path = entry.path();
+        if !is_saved_recording(&path) || failed.contains(&path) || path.with_extension("txt").exists() {
+            continue;
+        }
+        let Ok(metadata) = std::fs::metadata(&path) else { continue };
+        let size = metadata.len();
+        if seen_sizes.insert(path.clone(), size) == Some(size) && size > 0 {
+            ready.push(path);
+        }
+    }
+    ready.sort();
+    Ok(ready)
+}
+
+fn process_file(app: &AppHandle, path: &Path, write_srt: bool, pending: usize) -> Result<()> {
+    let file = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    let emit = |status: WatchFolderStatus, job_id: Option<u64>, error: Option<String>| {
+        let _ = app.emit("watch-folder-progress", WatchFolderEvent { file: file.clone(), status, job_id, error, pending });
+    };
+    let (samples, sample_rate) = match read_recording_mono(path) {
+        Ok(v) => v,
+        Err(e) => {
+            emit(WatchFolderStatus::Failed, None, Some(e.to_string()));
+            return Err(e);
+        }
+    };
+    let audio_ms = samples.len() as u64 * 1000 / sample_rate.max(1) as u64;
+    let job_id = start_job(JobSource::WatchFolder, audio_ms, None, None);
+    info!("watch folder: transcribing {} (job {})", path.display(), job_id);
+    emit(WatchFolderStatus::Started, Some(job_id), None);
+    let result = transcribe_to_segments(app, job_id, &samples, sample_rate).and_then(|segments| {
+        let text = segments.iter().map(|s| s.text.as_str()).collect::<Vec<_>>().join(" ");
+        if write_srt {
+            std::fs::write(path.with_extension("srt"), to_srt(&segments, audio_ms))?;
+        }
+        // The .txt doubles as the "done" marker, so it is written last.
+        std::fs::write(path.with_extension("txt"), format!("{}\n", text))?;
+        Ok(())
+    });
+    match result {
+        Ok(()) => {
+            complete_job(job_id);
+            emit(WatchFolderStatus::Completed, Some(job_id), None);
+            Ok(())
+        }
+        Err(e) => {
+            fail_job(job_id, e.to_string());
+            emit(WatchFolderStatus::Failed, Some(job_id), Some(e.to_string()));
+            Err(e)
+        }
+    }
+}
+
+/// SubRip cues; each segment ends where the next one starts, the last at the end of the audio.
+fn to_srt(segments: &[TranscriptSegment], audio_ms: u64) -> String {
+    let mut out = String::new();
+    for (i, seg) in segments.iter().enumerate() {
+        let end_ms = segments.get(i + 1).map(|n| n.start_ms).unwrap_or(audio_ms).max(seg.start_ms);
+        out.push_str(&format!("{}\n{} --> {}\n{}\n\n", i + 1, srt_time(seg.start_ms), srt_time(end_ms), seg.text));
+    }
+    out
+}
+
+fn srt_time(ms: u64) -> String {
+    format!("{:02}:{:02}:{:02},{:03}", ms / 3_600_000, ms / 60_000 % 60, ms / 1000 % 60, ms % 1000)
+}
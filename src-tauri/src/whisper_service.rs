@@ -1,12 +1,20 @@
 use anyhow::{anyhow, Context, Result};
 use hound::WavReader;
 use std::io::Cursor;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::Instant;
-use whisper_rs::{FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters};
+use whisper_rs::{DtwMode, DtwModelPreset, DtwParameters, FullParams, SamplingStrategy, WhisperContext, WhisperContextParameters, WhisperState, WhisperTokenData};
 use log::{debug, error, info, warn};
 use std::ffi::CStr;
 use whisper_rs_sys as sys;
+use crate::vad::silence_split_points;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct LanguageProbability {
+    pub language: String,
+    pub probability: f32,
+}
 
 #[derive(serde::Serialize, Clone, Debug)]
 pub struct BackendInfo {
@@ -14,26 +22,188 @@ pub struct BackendInfo {
     pub ggml_metal_path_resources: Option<String>,
     pub metallib_present: bool,
     pub likely_using_metal: bool,
+    /// False when the model was loaded with GPU inference turned off.
+    pub gpu_enabled: bool,
+    /// Whether the model was loaded with flash attention.
+    pub flash_attn: bool,
+    /// `metal` or `cpu`, following `likely_using_metal`.
+    pub effective_backend: String,
     pub model_path: String,
 }
 
-#[derive(serde::Serialize, Clone, Debug)]
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
 pub struct ServiceTimings {
     pub sample_to_mono_ms: u128,
     pub resample_ms: u128,
     pub create_state_ms: u128,
+    /// Language detection pass for `lang=auto`; 0 when a language was given.
+    #[serde(default)]
+    pub language_detect_ms: u128,
     pub inference_ms: u128,
     pub collect_segments_ms: u128,
     pub total_ms: u128,
 }
 
+/// One word of a transcript with its timing, from whisper's token timestamps.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct WordTiming {
+    pub text: String,
+    pub start_ms: u64,
+    pub end_ms: u64,
+    /// Mean probability of the word's tokens.
+    pub probability: f32,
+}
+
+/// One whisper segment with its decoding statistics.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+pub struct Segment {
+    pub index: usize,
+    /// Start and end in milliseconds from the start of the audio.
+    pub t0: u64,
+    pub t1: u64,
+    pub text: String,
+    /// Mean log-probability of the segment's text tokens.
+    pub avg_logprob: f32,
+    /// Whisper's probability that the window holding this segment is silence.
+    pub no_speech_prob: f32,
+}
+
+/// Result of one transcription request.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, Default)]
+pub struct Transcript {
+    pub text: String,
+    #[serde(default)]
+    pub words: Vec<WordTiming>,
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    /// Language found by detection when the request asked for `auto`.
+    #[serde(default)]
+    pub detected_language: Option<LanguageProbability>,
+    /// Mean probability of the transcript's words, 0–1; `None` when nothing was decoded.
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+/// Live inference progress reported through whisper.cpp callbacks.
+#[derive(serde::Serialize, Clone, Debug)]
+pub struct TranscriptionProgress {
+    pub job_id: Option<u64>,
+    pub percent: i32,
+    pub latest_segment: Option<String>,
+    /// Remaining inference time extrapolated from elapsed time and percent done.
+    pub eta_ms: Option<u64>,
+}
+
+/// CPU threads whisper uses per request; 0 picks them automatically.
+static INFERENCE_THREADS: AtomicUsize = AtomicUsize::new(0);
+
+/// Set the CPU thread count for later requests; `None` picks it automatically.
+pub fn set_inference_threads(threads: Option<u32>) {
+    INFERENCE_THREADS.store(threads.unwrap_or(0) as usize, Ordering::Relaxed);
+}
+
+/// Logical CPUs on this machine.
+pub fn cpu_count() -> usize {
+    std::thread::available_parallelism().map(|n| n.get()).unwrap_or(4)
+}
+
+fn inference_threads() -> usize {
+    match INFERENCE_THREADS.load(Ordering::Relaxed) {
+        0 => cpu_count().min(4),
+        n => n.min(cpu_count()),
+    }
+}
+
+type ProgressListener = Box<dyn Fn(TranscriptionProgress) + Send + Sync>;
+
+static PROGRESS_LISTENER: OnceLock<ProgressListener> = OnceLock::new();
+
+/// Register the process-wide receiver of inference progress (set once at startup).
+pub fn set_progress_listener<F>(listener: F)
+where
+    F: Fn(TranscriptionProgress) + Send + Sync + 'static,
+{
+    let _ = PROGRESS_LISTENER.set(Box::new(listener));
+}
+
+fn report_progress(progress: &Arc<Mutex<TranscriptionProgress>>) {
+    if let Some(listener) = PROGRESS_LISTENER.get() {
+        if let Ok(p) = progress.lock() {
+            listener(p.clone());
+        }
+    }
+}
+
+/// Whisper's 30 s input window at 16 kHz; language detection never looks further.
+const LANGUAGE_DETECT_SAMPLES: usize = 30 * 16_000;
+
+/// Language value that asks for detection instead of a fixed language.
+pub const AUTO_LANGUAGE: &str = "auto";
+
+/// Whisper conditions on at most half the text context (448 / 2) of prompt tokens.
+pub const MAX_PROMPT_TOKENS: usize = 224;
+
+/// Per-request decoding options passed from the HTTP layer.
+#[derive(Clone, Debug, Default)]
+pub struct TranscribeOptions {
+    /// Job this request belongs to, echoed in progress events.
+    pub job_id: Option<u64>,
+    /// Decoding language; `auto` detects it first and decodes in the most likely one.
+    pub language: Option<String>,
+    pub initial_prompt: Option<String>,
+    /// Prefix each segment with `[mm:ss]` of its start time.
+    pub inline_timestamps: bool,
+    pub patience: Option<f32>,
+    pub entropy_threshold: Option<f32>,
+    pub logprob_threshold: Option<f32>,
+    /// Decode greedily instead of with beam search.
+    pub greedy: bool,
+    pub beam_size: Option<u32>,
+    /// Samples kept per step when decoding greedily with temperature.
+    pub best_of: Option<u32>,
+    pub temperature: Option<f32>,
+    pub suppress_blank: Option<bool>,
+    pub suppress_non_speech_tokens: Option<bool>,
+    /// Passed to whisper.cpp, which skips a window when its no-speech probability is above
+    /// this and its average log-probability is below `logprob_threshold`.
+    pub no_speech_threshold: Option<f32>,
+    /// Text whose tokens are never sampled.
+    pub suppressed_tokens: Vec<String>,
+    /// Output English text whatever language is spoken.
+    pub translate: bool,
+}
+
+/// Options fixed when a model is loaded; changing them means loading it again.
+#[derive(Clone, Debug)]
+pub struct ContextOptions {
+    /// Run on the GPU (Metal) when available; off forces CPU-only inference.
+    pub use_gpu: bool,
+    /// Use flash attention, which is faster and lighter on memory on Metal.
+    pub flash_attn: bool,
+    /// Alignment heads of the model for DTW token timestamps; `None` leaves DTW off.
+    /// whisper.cpp ignores it when `flash_attn` is on.
+    pub dtw_preset: Option<DtwModelPreset>,
+}
+
+impl Default for ContextOptions {
+    fn default() -> Self {
+        Self { use_gpu: true, flash_attn: false, dtw_preset: None }
+    }
+}
+
+/// Idle decoder states kept for reuse. Each holds its own KV cache and buffers (tens to a few
+/// hundred MB depending on the model), so only enough for the usual concurrency are kept.
+const MAX_POOLED_STATES: usize = 2;
+
 pub struct WhisperService {
+    states: Mutex<Vec<WhisperState>>,
     ctx: Arc<WhisperContext>,
     model_path: String,
+    context: ContextOptions,
 }
 
 impl WhisperService {
-    pub fn from_model_path(model_path: &str) -> Result<Self> {
+    pub fn from_model_path(model_path: &str, context: &ContextOptions) -> Result<Self> {
         let t0 = Instant::now();
 
         unsafe extern "C" fn ggml_logger(
@@ -56,14 +226,40 @@ impl WhisperService {
         // Capture all internal ggml/whisper logs (Metal init, model load, buffers, etc.)
         unsafe { sys::whisper_log_set(Some(ggml_logger), std::ptr::null_mut()); }
 
-        info!("Loading whisper model: {}", model_path);
-        let ctx = WhisperContext::new_with_params(
-            model_path,
-            WhisperContextParameters::default(),
-        )
+        info!("Loading whisper model: {} ({:?})", model_path, context);
+        let mut ctx_params = WhisperContextParameters::default();
+        ctx_params.use_gpu(context.use_gpu);
+        ctx_params.flash_attn(context.flash_attn);
+        if let Some(model_preset) = context.dtw_preset.clone() {
+            ctx_params.dtw_parameters(DtwParameters { mode: DtwMode::ModelPreset { model_preset }, ..Default::default() });
+        }
+        let ctx = WhisperContext::new_with_params(model_path, ctx_params)
         .with_context(|| format!("failed to load whisper model at {}", model_path))?;
         info!("whisper model loaded in {} ms", t0.elapsed().as_millis());
-        Ok(Self { ctx: Arc::new(ctx), model_path: model_path.to_string() })
+        let svc = Self { states: Mutex::new(Vec::new()), ctx: Arc::new(ctx), model_path: model_path.to_string(), context: context.clone() };
+        // Warm one state up front so the first dictation does not pay for it.
+        let state = svc.take_state()?;
+        svc.return_state(state);
+        Ok(svc)
+    }
+
+    /// An idle state from the pool, or a new one when all are in use. A state keeps its own
+    /// reference to the model, so it stays valid however long it is held.
+    fn take_state(&self) -> Result<WhisperState> {
+        if let Some(state) = self.states.lock().ok().and_then(|mut pool| pool.pop()) {
+            return Ok(state);
+        }
+        self.ctx.create_state().context("failed to create whisper state")
+    }
+
+    /// Put a state back after a successful run. Each `full` call clears the previous results
+    /// and, with `no_context`, the previous prompt, so a reused state decodes like a new one.
+    fn return_state(&self, state: WhisperState) {
+        if let Ok(mut pool) = self.states.lock() {
+            if pool.len() < MAX_POOLED_STATES {
+                pool.push(state);
+            }
+        }
     }
 
     pub fn backend_info(&self) -> BackendInfo {
@@ -76,17 +272,76 @@ impl WhisperService {
         // Heuristic: on macOS, with metallib present, whisper-rs built with metal feature,
         // and env configured by app, we are likely using Metal. This doesn't guarantee it,
         // but is a strong indicator for our UI log.
-        let likely_using_metal = cfg!(target_os = "macos") && metallib_present;
+        let likely_using_metal = cfg!(target_os = "macos") && metallib_present && self.context.use_gpu;
         BackendInfo {
             target_os,
             ggml_metal_path_resources,
             metallib_present,
             likely_using_metal,
+            gpu_enabled: self.context.use_gpu,
+            flash_attn: self.context.flash_attn,
+            effective_backend: if likely_using_metal { "metal" } else { "cpu" }.to_string(),
             model_path: self.model_path.clone(),
         }
     }
 
-    pub fn transcribe_wav_bytes_profiled(&self, wav_bytes: &[u8], language: Option<&str>, initial_prompt: Option<&str>) -> Result<(String, ServiceTimings)> {
+    /// Number of tokens the model's tokenizer produces for `text`.
+    pub fn count_tokens(&self, text: &str) -> Result<usize> {
+        if text.is_empty() { return Ok(0); }
+        let tokens = self
+            .ctx
+            .tokenize(text, text.len() + 1)
+            .map_err(|e| anyhow!("failed to tokenize prompt: {:?}", e))?;
+        Ok(tokens.len())
+    }
+
+    /// Token ids of `texts`, both as written and with the leading space whisper puts before
+    /// most words.
+    fn suppressed_tokens(&self, texts: &[String]) -> Result<SuppressedTokens> {
+        let mut sequences = Vec::new();
+        for text in texts.iter().filter(|t| !t.trim().is_empty()) {
+            for variant in [text.trim().to_string(), format!(" {}", text.trim())] {
+                let tokens = self
+                    .ctx
+                    .tokenize(&variant, variant.len() + 1)
+                    .map_err(|e| anyhow!("failed to tokenize suppressed text: {:?}", e))?;
+                if !tokens.is_empty() { sequences.push(tokens); }
+            }
+        }
+        sequences.sort_unstable();
+        sequences.dedup();
+        Ok(SuppressedTokens(sequences))
+    }
+
+    /// Trim `prompt` from the front so it fits the prompt budget, keeping the most recent
+    /// context (the tail) and starting on a word boundary.
+    pub fn fit_prompt(&self, prompt: &str) -> Result<String> {
+        fit_prompt_tail(prompt, MAX_PROMPT_TOKENS, |text| self.count_tokens(text))
+    }
+
+    /// Run only the language-detection pass on 16 kHz mono audio and return the
+    /// `top_n` most likely languages, most probable first. Whisper only looks at the first
+    /// window, so only that much audio is converted to a spectrogram.
+    pub fn detect_language(&self, audio_16k: &[f32], top_n: usize) -> Result<Vec<LanguageProbability>> {
+        let threads = inference_threads();
+        let window = &audio_16k[..audio_16k.len().min(LANGUAGE_DETECT_SAMPLES)];
+        let mut state = self.take_state()?;
+        state.pcm_to_mel(window, threads).map_err(|e| anyhow!("failed to compute mel spectrogram: {:?}", e))?;
+        let (_, probs) = state.lang_detect(0, threads).map_err(|e| anyhow!("language detection failed: {:?}", e))?;
+        self.return_state(state);
+        let mut ranked: Vec<LanguageProbability> = probs
+            .iter()
+            .enumerate()
+            .filter_map(|(id, p)| {
+                whisper_rs::get_lang_str(id as i32).map(|lang| LanguageProbability { language: lang.to_string(), probability: *p })
+            })
+            .collect();
+        ranked.sort_by(|a, b| b.probability.total_cmp(&a.probability));
+        ranked.truncate(top_n);
+        Ok(ranked)
+    }
+
+    pub fn transcribe_wav_bytes_profiled(&self, wav_bytes: &[u8], opts: &TranscribeOptions) -> Result<(Transcript, ServiceTimings)> {
         let t_total = Instant::now();
         // Decode WAV
         let cursor = Cursor::new(wav_bytes);
@@ -170,63 +425,400 @@ impl WhisperService {
         };
         let resample_ms = t_resample_start.elapsed().as_millis();
 
-        // Run whisper
+        let fitted_prompt = match opts.initial_prompt.as_deref() {
+            Some(prompt) => {
+                let fitted = self.fit_prompt(prompt)?;
+                if fitted.len() < prompt.len() {
+                    warn!("initial prompt exceeds {} tokens; keeping last {} of {} chars", MAX_PROMPT_TOKENS, fitted.len(), prompt.len());
+                }
+                Some(fitted)
+            }
+            None => None,
+        };
+        let t_detect_start = Instant::now();
+        let detected_language = match opts.language.as_deref() {
+            Some(AUTO_LANGUAGE) => self.detect_language(&audio_16k, 1)?.into_iter().next(),
+            _ => None,
+        };
+        let language_detect_ms = if detected_language.is_some() { t_detect_start.elapsed().as_millis() } else { 0 };
+        let detected_opts;
+        let opts = match &detected_language {
+            Some(detected) => {
+                info!("detected language {} (p={:.2})", detected.language, detected.probability);
+                detected_opts = TranscribeOptions { language: Some(detected.language.clone()), ..opts.clone() };
+                &detected_opts
+            }
+            None => opts,
+        };
+        let suppressed = self.suppressed_tokens(&opts.suppressed_tokens)?;
+        let chunks = chunk_ranges(&audio_16k);
+        if chunks.len() > 1 {
+            info!("long audio ({} s): transcribing in {} chunks", audio_16k.len() / 16_000, chunks.len());
+        }
+        let progress = Arc::new(Mutex::new(TranscriptionProgress { job_id: opts.job_id, percent: 0, latest_segment: None, eta_ms: None }));
+        let started = Instant::now();
+        let mut out = String::new();
+        let mut words: Vec<WordTiming> = Vec::new();
+        let mut segments: Vec<Segment> = Vec::new();
         let t_state_start = Instant::now();
-        let mut state = self
-            .ctx
-            .create_state()
-            .context("failed to create whisper state")?;
+        let mut state = self.take_state()?;
         let create_state_ms = t_state_start.elapsed().as_millis();
-        let mut params = FullParams::new(SamplingStrategy::BeamSearch {
-            beam_size: 5,
-            patience: -1.0,
-        });
-        if let Some(lang) = language {
-            params.set_language(Some(lang));
-        }
-        if let Some(prompt) = initial_prompt {
-            // whisper-rs exposes set_initial_prompt in FullParams as of 0.11
-            params.set_initial_prompt(prompt);
-        }
-        let t_infer_start = Instant::now();
-        state.full(params, &audio_16k[..]).context("whisper full failed")?;
-        let inference_ms = t_infer_start.elapsed().as_millis();
+        let mut inference_ms = 0;
+        let mut collect_segments_ms = 0;
+        for (idx, range) in chunks.iter().enumerate() {
+            // Run whisper
+            let params = build_params(opts, fitted_prompt.as_deref(), &suppressed, &progress, started, idx, chunks.len());
+            let t_infer_start = Instant::now();
+            state.full(params, &audio_16k[range.clone()]).context("whisper full failed")?;
+            inference_ms += t_infer_start.elapsed().as_millis();
 
-        // Collect segments (API updated in whisper-rs 0.11)
-        let t_collect_start = Instant::now();
-        let mut out = String::new();
-        let num_segments = match state.full_n_segments() {
-            Ok(n) => n,
-            Err(_) => 0,
-        };
-        for i in 0..num_segments {
-            let seg_text = match state.full_get_segment_text(i as i32) {
-                Ok(text) => text,
-                Err(_) => String::new(),
-            };
-            if !seg_text.is_empty() {
-                if !out.is_empty() {
-                    out.push(' ');
+            // Collect segments
+            let t_collect_start = Instant::now();
+            let offset_cs = (range.start / 160) as i64;
+            let mut chunk_text = String::new();
+            for segment in state.as_iter() {
+                let seg_text = segment.to_str_lossy().unwrap_or_default();
+                if !seg_text.is_empty() {
+                    if !chunk_text.is_empty() {
+                        chunk_text.push(' ');
+                    }
+                    if opts.inline_timestamps {
+                        chunk_text.push_str(&format_segment_timestamp(offset_cs + segment.start_timestamp()));
+                        chunk_text.push(' ');
+                    }
+                    chunk_text.push_str(seg_text.trim());
                 }
-                out.push_str(seg_text.trim());
             }
+            out = stitch_overlap(&out, &chunk_text);
+            // Words inside the overlap were already taken from the previous chunk.
+            let covered_ms = words.last().map(|w| w.end_ms).unwrap_or(0);
+            words.extend(self.collect_words(&state, offset_cs).into_iter().filter(|w| w.start_ms >= covered_ms));
+            let covered_ms = segments.last().map(|s| s.t1).unwrap_or(0);
+            segments.extend(self.collect_segments(&state, offset_cs).into_iter().filter(|s| s.t0 >= covered_ms));
+            collect_segments_ms += t_collect_start.elapsed().as_millis();
         }
-        let collect_segments_ms = t_collect_start.elapsed().as_millis();
+        self.return_state(state);
         let total_ms = t_total.elapsed().as_millis();
 
         let timings = ServiceTimings {
             sample_to_mono_ms,
             resample_ms,
             create_state_ms,
+            language_detect_ms,
             inference_ms,
             collect_segments_ms,
             total_ms,
         };
-        Ok((out, timings))
+        for (index, segment) in segments.iter_mut().enumerate() {
+            segment.index = index;
+        }
+        let confidence = (!words.is_empty()).then(|| words.iter().map(|w| w.probability).sum::<f32>() / words.len() as f32);
+        Ok((Transcript { text: out, words, segments, detected_language, confidence }, timings))
     }
+
+    /// Non-empty segments with their times shifted by `offset_cs`; indices are assigned by the caller.
+    fn collect_segments(&self, state: &WhisperState, offset_cs: i64) -> Vec<Segment> {
+        let eot = self.ctx.token_eot();
+        let to_ms = |t_cs: i64| ((offset_cs + t_cs).max(0) * 10) as u64;
+        state
+            .as_iter()
+            .filter_map(|seg| {
+                let text = seg.to_str_lossy().ok()?.trim().to_string();
+                if text.is_empty() { return None; }
+                let logprobs: Vec<f32> = (0..seg.n_tokens())
+                    .filter_map(|tok| seg.get_token(tok).map(|t| t.token_data()))
+                    .filter(|data| data.id < eot)
+                    .map(|data| data.plog)
+                    .collect();
+                Some(Segment {
+                    index: 0,
+                    t0: to_ms(seg.start_timestamp()),
+                    t1: to_ms(seg.end_timestamp()),
+                    text,
+                    avg_logprob: if logprobs.is_empty() { 0.0 } else { logprobs.iter().sum::<f32>() / logprobs.len() as f32 },
+                    no_speech_prob: seg.no_speech_probability(),
+                })
+            })
+            .collect()
+    }
+
+    /// Word timings from the decoded tokens of every segment in `state`; see `group_words`.
+    fn collect_words(&self, state: &WhisperState, offset_cs: i64) -> Vec<WordTiming> {
+        let eot = self.ctx.token_eot();
+        let data: Vec<WhisperTokenData> = state
+            .as_iter()
+            .flat_map(|seg| (0..seg.n_tokens()).filter_map(move |tok| seg.get_token(tok).map(|t| t.token_data())))
+            .collect();
+        // Special tokens (timestamps, end of text) are not part of any word.
+        let text: Vec<&WhisperTokenData> = data.iter().filter(|data| data.id < eot).collect();
+        let tokens = text.iter().enumerate().filter_map(|(i, data)| {
+            let bytes = self.ctx.token_to_bytes(data.id).ok()?;
+            let (t0_cs, t1_cs) = dtw_span(data.t_dtw, text.get(i + 1).map(|next| next.t_dtw), data.t1).unwrap_or((data.t0, data.t1));
+            Some(DecodedToken { bytes, t0_cs, t1_cs, p: data.p })
+        });
+        group_words(tokens, offset_cs)
+    }
+}
+
+/// One decoded text token with its timing (centiseconds into the chunk) and probability.
+struct DecodedToken<'a> {
+    bytes: &'a [u8],
+    t0_cs: i64,
+    t1_cs: i64,
+    p: f32,
+}
+
+/// A token's span from DTW alignment, which gives each token the time it starts: it ends
+/// where the next token starts, or at its own `t1_cs` when it is the last. `None` when DTW
+/// did not run (`t_dtw` is -1).
+fn dtw_span(t_dtw: i64, next_t_dtw: Option<i64>, t1_cs: i64) -> Option<(i64, i64)> {
+    if t_dtw < 0 { return None; }
+    let end = next_t_dtw.filter(|&next| next >= t_dtw).unwrap_or(t1_cs.max(t_dtw));
+    Some((t_dtw, end))
+}
+
+/// Group decoded tokens into words; a token starting with a space begins a new word. A token
+/// can hold part of a multi-byte character (CJK, emoji), so each word's bytes are decoded
+/// only once complete.
+fn group_words<'a>(tokens: impl IntoIterator<Item = DecodedToken<'a>>, offset_cs: i64) -> Vec<WordTiming> {
+    let to_ms = |t_cs: i64| ((offset_cs + t_cs).max(0) * 10) as u64;
+    let mut words: Vec<(Vec<u8>, WordTiming, usize)> = Vec::new();
+    for token in tokens {
+        let starts_word = token.bytes.first() == Some(&b' ') || words.is_empty();
+        match words.last_mut() {
+            Some((word_bytes, word, n)) if !starts_word => {
+                word_bytes.extend_from_slice(token.bytes);
+                word.end_ms = to_ms(token.t1_cs).max(word.end_ms);
+                word.probability += token.p;
+                *n += 1;
+            }
+            _ => words.push((
+                token.bytes.to_vec(),
+                WordTiming { text: String::new(), start_ms: to_ms(token.t0_cs), end_ms: to_ms(token.t1_cs), probability: token.p },
+                1,
+            )),
+        }
+    }
+    words
+        .into_iter()
+        .filter_map(|(bytes, mut w, n)| {
+            w.text = String::from_utf8_lossy(&bytes).trim().to_string();
+            w.probability /= n as f32;
+            (!w.text.is_empty()).then_some(w)
+        })
+        .collect()
+}
+
+/// Longest tail of `prompt` that `count_tokens` puts within `max_tokens`, starting on a word
+/// boundary unless the whole prompt fits.
+fn fit_prompt_tail(prompt: &str, max_tokens: usize, count_tokens: impl Fn(&str) -> Result<usize>) -> Result<String> {
+    if count_tokens(prompt)? <= max_tokens {
+        return Ok(prompt.to_string());
+    }
+    let boundaries: Vec<usize> = prompt.char_indices().map(|(i, _)| i).collect();
+    // Binary search the smallest start offset whose tail fits.
+    let (mut lo, mut hi) = (0usize, boundaries.len());
+    while lo < hi {
+        let mid = (lo + hi) / 2;
+        if count_tokens(&prompt[boundaries[mid]..])? <= max_tokens { hi = mid; } else { lo = mid + 1; }
+    }
+    let tail = &prompt[boundaries.get(lo).copied().unwrap_or(prompt.len())..];
+    let tail = match tail.find(char::is_whitespace) {
+        Some(ws) if lo > 0 => tail[ws..].trim_start(),
+        _ => tail,
+    };
+    Ok(tail.to_string())
+}
+
+/// Audio longer than this is transcribed in pieces.
+const LONG_AUDIO_CHUNK_SECS: u32 = 120;
+/// How far either side of a nominal chunk boundary to look for a pause.
+const CHUNK_SEARCH_SECS: u32 = 5;
+/// Audio repeated at the start of each chunk so whisper has context; duplicated words are stitched out.
+const CHUNK_OVERLAP_SAMPLES: usize = 16_000;
+
+/// Split 16 kHz audio into overlapping ranges cut at silence (a single range for short audio).
+fn chunk_ranges(audio_16k: &[f32]) -> Vec<std::ops::Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for split in silence_split_points(audio_16k, 16_000, LONG_AUDIO_CHUNK_SECS, CHUNK_SEARCH_SECS) {
+        ranges.push(start.saturating_sub(CHUNK_OVERLAP_SAMPLES)..split);
+        start = split;
+    }
+    ranges.push(start.saturating_sub(CHUNK_OVERLAP_SAMPLES)..audio_16k.len());
+    ranges
+}
+
+/// Append `next` to `prev`, dropping the longest run of words at the start of `next`
+/// that repeats the end of `prev` (the overlap between adjacent chunks). Inline `[mm:ss]`
+/// timestamps differ between the chunks, so they are skipped while matching.
+fn stitch_overlap(prev: &str, next: &str) -> String {
+    const MAX_OVERLAP_WORDS: usize = 12;
+    if prev.is_empty() { return next.to_string(); }
+    if next.is_empty() { return prev.to_string(); }
+    let norm = |w: &str| w.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+    let prev_words: Vec<String> = prev.split_whitespace().filter(|w| !is_segment_timestamp(w)).map(norm).collect();
+    let next_tokens: Vec<&str> = next.split_whitespace().collect();
+    // Index into `next_tokens` of each word that is not a timestamp.
+    let next_words: Vec<usize> = (0..next_tokens.len()).filter(|&i| !is_segment_timestamp(next_tokens[i])).collect();
+    let max = MAX_OVERLAP_WORDS.min(prev_words.len()).min(next_words.len());
+    let overlap = (1..=max)
+        .rev()
+        .find(|&n| prev_words[prev_words.len() - n..].iter().zip(&next_words[..n]).all(|(a, &b)| *a == norm(next_tokens[b])))
+        .unwrap_or(0);
+    let mut rest: Vec<&str> = match overlap {
+        0 => next_tokens.clone(),
+        n => next_tokens[next_words[n - 1] + 1..].to_vec(),
+    };
+    // Words left over from a segment whose start was dropped keep that segment's timestamp.
+    if overlap > 0 && rest.first().is_some_and(|w| !is_segment_timestamp(w)) {
+        if let Some(stamp) = next_tokens[..next_words[overlap - 1]].iter().rev().find(|w| is_segment_timestamp(w)) {
+            rest.insert(0, *stamp);
+        }
+    }
+    let rest = rest.join(" ");
+    if rest.is_empty() { prev.to_string() } else { format!("{} {}", prev, rest) }
+}
+
+/// Whether `word` is an inline timestamp as written by `format_segment_timestamp`.
+fn is_segment_timestamp(word: &str) -> bool {
+    word.strip_prefix('[')
+        .and_then(|w| w.strip_suffix(']'))
+        .and_then(|w| w.split_once(':'))
+        .is_some_and(|(m, s)| !m.is_empty() && !s.is_empty() && m.chars().chain(s.chars()).all(|c| c.is_ascii_digit()))
 }
 
-fn resample_linear(input: &[f32], src_rate: u32, dst_rate: u32) -> Vec<f32> {
+/// Decoding parameters for one chunk; progress is scaled so it covers the whole job.
+fn build_params<'a>(
+    opts: &'a TranscribeOptions,
+    prompt: Option<&str>,
+    suppressed: &'a SuppressedTokens,
+    progress: &'a Arc<Mutex<TranscriptionProgress>>,
+    started: Instant,
+    chunk: usize,
+    chunks: usize,
+) -> FullParams<'a, 'a> {
+    let strategy = if opts.greedy {
+        SamplingStrategy::Greedy { best_of: opts.best_of.unwrap_or(1) as i32 }
+    } else {
+        SamplingStrategy::BeamSearch { beam_size: opts.beam_size.unwrap_or(5) as i32, patience: opts.patience.unwrap_or(-1.0) }
+    };
+    let mut params = FullParams::new(strategy);
+    params.set_n_threads(inference_threads() as i32);
+    params.set_token_timestamps(true);
+    // States are pooled; never condition on text a previous request left behind.
+    params.set_no_context(true);
+    if let Some(temperature) = opts.temperature {
+        params.set_temperature(temperature);
+    }
+    if let Some(thold) = opts.entropy_threshold {
+        params.set_entropy_thold(thold);
+    }
+    if let Some(thold) = opts.logprob_threshold {
+        params.set_logprob_thold(thold);
+    }
+    if let Some(thold) = opts.no_speech_threshold {
+        params.set_no_speech_thold(thold);
+    }
+    if let Some(suppress) = opts.suppress_blank {
+        params.set_suppress_blank(suppress);
+    }
+    if let Some(suppress) = opts.suppress_non_speech_tokens {
+        params.set_suppress_nst(suppress);
+    }
+    if !suppressed.0.is_empty() {
+        // SAFETY: `suppressed` outlives the params and so every decode that calls the filter.
+        unsafe {
+            params.set_filter_logits_callback(Some(suppress_tokens_filter));
+            params.set_filter_logits_callback_user_data(suppressed as *const SuppressedTokens as *mut std::os::raw::c_void);
+        }
+    }
+    if let Some(lang) = opts.language.as_deref() {
+        params.set_language(Some(lang));
+    }
+    params.set_translate(opts.translate);
+    if let Some(prompt) = prompt {
+        // whisper-rs exposes set_initial_prompt in FullParams
+        params.set_initial_prompt(prompt);
+    }
+    {
+        let progress = progress.clone();
+        params.set_progress_callback_safe(move |percent: i32| {
+            let overall = ((chunk as i32 * 100 + percent.min(100)) / chunks.max(1) as i32).min(100);
+            if let Ok(mut p) = progress.lock() {
+                p.percent = overall;
+                if overall > 0 {
+                    let elapsed = started.elapsed().as_millis() as u64;
+                    p.eta_ms = Some(elapsed * (100 - overall) as u64 / overall as u64);
+                }
+            }
+            report_progress(&progress);
+        });
+    }
+    // SAFETY: `progress` outlives the params and so every decode that reports a segment.
+    unsafe {
+        params.set_new_segment_callback(Some(latest_segment_callback));
+        params.set_new_segment_callback_user_data(progress as *const Arc<Mutex<TranscriptionProgress>> as *mut std::os::raw::c_void);
+    }
+    params
+}
+
+/// whisper.cpp new-segment callback that reports the newest segment's text as progress;
+/// `user_data` is the job's `Arc<Mutex<TranscriptionProgress>>`.
+unsafe extern "C" fn latest_segment_callback(
+    _ctx: *mut sys::whisper_context,
+    state: *mut sys::whisper_state,
+    n_new: std::os::raw::c_int,
+    user_data: *mut std::os::raw::c_void,
+) {
+    if state.is_null() || user_data.is_null() || n_new <= 0 { return; }
+    let progress = unsafe { &*(user_data as *const Arc<Mutex<TranscriptionProgress>>) };
+    let last = unsafe { sys::whisper_full_n_segments_from_state(state) } - 1;
+    let text = unsafe { sys::whisper_full_get_segment_text_from_state(state, last) };
+    if text.is_null() { return; }
+    let text = unsafe { CStr::from_ptr(text) }.to_string_lossy().trim().to_string();
+    if let Ok(mut p) = progress.lock() { p.latest_segment = Some(text); }
+    report_progress(progress);
+}
+
+/// Token sequences the logits filter keeps whisper from producing.
+struct SuppressedTokens(Vec<Vec<sys::whisper_token>>);
+
+/// whisper.cpp logits filter for the `SuppressedTokens` in `user_data`: the last token of a
+/// sequence is ruled out only when the text decoded so far ends with the rest of it, so the
+/// fragments a sequence is made of stay available everywhere else.
+unsafe extern "C" fn suppress_tokens_filter(
+    ctx: *mut sys::whisper_context,
+    _state: *mut sys::whisper_state,
+    tokens: *const sys::whisper_token_data,
+    n_tokens: std::os::raw::c_int,
+    logits: *mut f32,
+    user_data: *mut std::os::raw::c_void,
+) {
+    if logits.is_null() || user_data.is_null() { return; }
+    let sequences = unsafe { &(*(user_data as *const SuppressedTokens)).0 };
+    let n_vocab = unsafe { sys::whisper_n_vocab(ctx) };
+    let eot = unsafe { sys::whisper_token_eot(ctx) };
+    let decoded: Vec<sys::whisper_token> = if tokens.is_null() || n_tokens <= 0 {
+        Vec::new()
+    } else {
+        unsafe { std::slice::from_raw_parts(tokens, n_tokens as usize) }.iter().map(|t| t.id).filter(|&id| id < eot).collect()
+    };
+    for sequence in sequences {
+        let Some((&last, prefix)) = sequence.split_last() else { continue };
+        if last >= 0 && last < n_vocab && decoded.ends_with(prefix) {
+            unsafe { *logits.add(last as usize) = f32::NEG_INFINITY; }
+        }
+    }
+}
+
+/// Format a whisper segment time (centiseconds) as `[mm:ss]`.
+fn format_segment_timestamp(t_cs: i64) -> String {
+    let total_secs = t_cs.max(0) / 100;
+    format!("[{:02}:{:02}]", total_secs / 60, total_secs % 60)
+}
+
+pub(crate) fn resample_linear(input: &[f32], src_rate: u32, dst_rate: u32) -> Vec<f32> {
     if input.is_empty() {
         return Vec::new();
     }
@@ -249,3 +841,89 @@ fn resample_linear(input: &[f32], src_rate: u32, dst_rate: u32) -> Vec<f32> {
 }
 
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Stand-in tokenizer: one token per word.
+    fn words(text: &str) -> Result<usize> {
+        Ok(text.split_whitespace().count())
+    }
+
+    #[test]
+    fn fit_prompt_keeps_a_prompt_within_budget() {
+        assert_eq!(fit_prompt_tail("one two three", 3, words).unwrap(), "one two three");
+    }
+
+    #[test]
+    fn fit_prompt_keeps_the_tail_from_a_word_boundary() {
+        assert_eq!(fit_prompt_tail("alpha beta gamma delta", 2, words).unwrap(), "gamma delta");
+        // A budget met mid-word still starts at the next whole word.
+        let chars = |text: &str| -> Result<usize> { Ok(text.chars().count()) };
+        assert_eq!(fit_prompt_tail("alpha beta gamma", 8, chars).unwrap(), "gamma");
+        assert_eq!(fit_prompt_tail("alpha beta", 0, words).unwrap(), "");
+    }
+
+    fn token(bytes: &[u8], t0_cs: i64, t1_cs: i64, p: f32) -> DecodedToken<'_> {
+        DecodedToken { bytes, t0_cs, t1_cs, p }
+    }
+
+    #[test]
+    fn group_words_joins_tokens_until_the_next_space() {
+        let words = group_words(
+            [token(b" Hel", 0, 10, 0.5), token(b"lo", 10, 20, 1.0), token(b" world", 25, 40, 0.75)],
+            100,
+        );
+        assert_eq!(words.len(), 2);
+        assert_eq!((words[0].text.as_str(), words[0].start_ms, words[0].end_ms), ("Hello", 1000, 1200));
+        assert!((words[0].probability - 0.75).abs() < 1e-6);
+        assert_eq!((words[1].text.as_str(), words[1].start_ms, words[1].end_ms), ("world", 1250, 1400));
+    }
+
+    #[test]
+    fn group_words_decodes_characters_split_across_tokens() {
+        let snowman = "\u{2603}".as_bytes();
+        let words = group_words([token(b" ", 0, 0, 1.0), token(&snowman[..1], 0, 5, 1.0), token(&snowman[1..], 5, 10, 1.0)], 0);
+        assert_eq!(words.len(), 1);
+        assert_eq!(words[0].text, "\u{2603}");
+        assert_eq!(words[0].end_ms, 100);
+    }
+
+    #[test]
+    fn dtw_span_ends_at_the_next_aligned_token() {
+        assert_eq!(dtw_span(-1, Some(20), 30), None);
+        assert_eq!(dtw_span(12, Some(20), 30), Some((12, 20)));
+        assert_eq!(dtw_span(12, None, 30), Some((12, 30)));
+        assert_eq!(dtw_span(12, Some(-1), 5), Some((12, 12)));
+    }
+
+    #[test]
+    fn stitch_overlap_drops_repeated_words() {
+        assert_eq!(stitch_overlap("hello world this is", "this is a test"), "hello world this is a test");
+        assert_eq!(stitch_overlap("Hello, World.", "world again"), "Hello, World. again");
+        assert_eq!(stitch_overlap("one two", "three four"), "one two three four");
+        assert_eq!(stitch_overlap("a b c", "b c"), "a b c");
+        assert_eq!(stitch_overlap("", "next"), "next");
+    }
+
+    #[test]
+    fn stitch_overlap_ignores_inline_timestamps() {
+        assert_eq!(
+            stitch_overlap("[00:00] one two three", "[00:28] two three four"),
+            "[00:00] one two three [00:28] four"
+        );
+        assert_eq!(
+            stitch_overlap("[00:00] one two", "[00:28] two [00:29] five"),
+            "[00:00] one two [00:29] five"
+        );
+    }
+
+    #[test]
+    fn segment_timestamps_need_digits_on_both_sides() {
+        assert!(is_segment_timestamp("[01:05]"));
+        assert!(!is_segment_timestamp("[note:]"));
+        assert!(!is_segment_timestamp("[a:b]"));
+        assert!(!is_segment_timestamp("01:05"));
+    }
+}
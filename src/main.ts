@@ -116,6 +116,43 @@ window.addEventListener('DOMContentLoaded', async () => {
     });
   }
 
+  // Microphone permission: the prompt can be shown from here, but once denied it can only be
+  // changed in System Settings.
+  const micBanner = document.getElementById('mic-banner') as HTMLDivElement | null;
+  const micBannerDetail = document.getElementById('mic-banner-detail');
+  const allowMicBtn = document.getElementById('allow-mic-btn') as HTMLButtonElement | null;
+  const showMicStatus = (status: string) => {
+    if (micBanner) micBanner.style.display = status === 'authorized' ? 'none' : 'block';
+    const undecided = status === 'not_determined';
+    if (allowMicBtn) allowMicBtn.style.display = undecided ? '' : 'none';
+    if (micBannerDetail) {
+      micBannerDetail.textContent = undecided
+        ? 'Commander needs the microphone to record dictations.'
+        : status === 'restricted'
+          ? 'Microphone access is blocked by a device management profile.'
+          : 'Grant access in System Settings → Privacy & Security → Microphone.';
+    }
+  };
+  await listen('microphone-status', (e) => {
+    showMicStatus((e.payload as { status: string }).status);
+  });
+  if (allowMicBtn) {
+    allowMicBtn.addEventListener('click', async () => {
+      try {
+        await invoke('request_microphone_access');
+      } catch (e) {
+        console.error('Failed to request microphone access:', e);
+      }
+    });
+  }
+  if (isMac) {
+    try {
+      showMicStatus((await invoke('get_microphone_status')) as string);
+    } catch {
+      // Command may not exist on non-mac builds; ignore
+    }
+  }
+
   await listen('recording-start', () => {
     console.log('🔴 Recording started');
     addLog('🔴 Recording started');
@@ -143,13 +180,25 @@ window.addEventListener('DOMContentLoaded', async () => {
   // Live audio level visualization
   const levelBar = document.getElementById('audio-level-bar') as HTMLDivElement | null;
   const levelText = document.getElementById('audio-level-text') as HTMLDivElement | null;
-  let lastLevelUpdate = 0;
+  const spectrum = document.getElementById('audio-spectrum') as HTMLCanvasElement | null;
+  const spectrumCtx = spectrum?.getContext('2d') ?? null;
+  const drawSpectrum = (bands: number[]) => {
+    if (!spectrum || !spectrumCtx) return;
+    spectrum.style.display = bands.length ? 'block' : 'none';
+    const { width, height } = spectrum;
+    spectrumCtx.clearRect(0, 0, width, height);
+    spectrumCtx.fillStyle = isRecording ? '#E52222' : '#888';
+    const barWidth = width / Math.max(1, bands.length);
+    bands.forEach((level, i) => {
+      const h = Math.max(1, level * height);
+      spectrumCtx.fillRect(i * barWidth + 1, height - h, Math.max(1, barWidth - 2), h);
+    });
+  };
+  // The backend throttles these to the interval set in the audio-level settings
   await listen('audio-level', (e) => {
     try {
-      const now = performance.now();
-      if (now - lastLevelUpdate < 30) return;
-      lastLevelUpdate = now;
       const payload = (e.payload as any) || {};
+      drawSpectrum(Array.isArray(payload.bands) ? (payload.bands as number[]) : []);
       const peak = Number(payload.peak) || 0;
       const db = Number(payload.db);
       const percent = Math.max(0, Math.min(100, Math.round(peak * 100)));
@@ -163,6 +212,56 @@ window.addEventListener('DOMContentLoaded', async () => {
     } catch {}
   });
 
+  // Live waveform from `audio-frame` events, scrolling right to left
+  const waveform = document.getElementById('audio-waveform') as HTMLCanvasElement | null;
+  const waveformCtx = waveform?.getContext('2d') ?? null;
+  const waveformPeaks: number[] = [];
+  const drawWaveform = () => {
+    if (!waveform || !waveformCtx) return;
+    const { width, height } = waveform;
+    waveformCtx.clearRect(0, 0, width, height);
+    waveformCtx.fillStyle = isRecording ? '#E52222' : '#888';
+    const offset = width - waveformPeaks.length;
+    waveformPeaks.forEach((peak, i) => {
+      const h = Math.max(1, peak * height);
+      waveformCtx.fillRect(offset + i, (height - h) / 2, 1, h);
+    });
+  };
+  await listen('audio-frame', (e) => {
+    const payload = (e.payload as any) || {};
+    const peaks = Array.isArray(payload.peaks) ? (payload.peaks as number[]) : [];
+    waveformPeaks.push(...peaks);
+    const width = waveform?.width ?? 0;
+    if (waveformPeaks.length > width) waveformPeaks.splice(0, waveformPeaks.length - width);
+    requestAnimationFrame(drawWaveform);
+  });
+  const setAudioFramesSubscribed = async (enabled: boolean) => {
+    try {
+      await invoke('subscribe_audio_frames', { enabled });
+    } catch (e) {
+      console.error('Failed to change the audio frame subscription:', e);
+    }
+  };
+  if (waveform) {
+    await setAudioFramesSubscribed(true);
+    // Frames come ~60 times a second, so they are only sent while the window is visible.
+    document.addEventListener('visibilitychange', () => setAudioFramesSubscribed(!document.hidden));
+    window.addEventListener('pagehide', () => setAudioFramesSubscribed(false));
+  }
+
+  // Pick up a recording that was started before this window loaded
+  try {
+    const state = (await invoke('get_recording_state')) as { recording: boolean };
+    if (state.recording && recordBtn) {
+      isRecording = true;
+      updateStatus('Recording...');
+      recordBtn.textContent = '⏹️ Stop Recording';
+      recordBtn.style.background = '#E52222';
+    }
+  } catch (e) {
+    console.error('Failed to get recording state:', e);
+  }
+
   await listen('transcription-start', () => {
     console.log('⏳ Transcribing...');
     addLog('⏳ Transcribing audio...');
@@ -228,6 +327,22 @@ window.addEventListener('DOMContentLoaded', async () => {
     }
   });
 
+  await listen('model-changed', (e) => {
+    const m = e.payload as { model_path: string };
+    addLog(`🧠 Model loaded: ${m.model_path.split('/').pop()}`);
+  });
+
+  await listen('model-unloaded', (e) => {
+    const m = e.payload as { model_path: string };
+    addLog(`💤 Model unloaded: ${m.model_path.split('/').pop()}`);
+    updateStatus('Model unloaded');
+  });
+
+  await listen('latency-budget-exceeded', (e) => {
+    const r = e.payload as { total_ms: number; target_ms: number; dominant_stage: string; hint: string | null };
+    addLog(`🐢 Took ${r.total_ms} ms (target ${r.target_ms} ms), mostly ${r.dominant_stage}${r.hint ? `. ${r.hint}` : ''}`);
+  });
+
   // Show backend at startup if backend-status is emitted from Rust setup
   await listen('backend-status', (e) => {
     try {
@@ -341,6 +456,55 @@ window.addEventListener('DOMContentLoaded', async () => {
     } catch {}
   });
 
+  // Phone companion UI
+  const companionEnabled = document.getElementById('companion-enabled') as HTMLInputElement | null;
+  const companionPairing = document.getElementById('companion-pairing');
+  const companionQr = document.getElementById('companion-qr');
+  const companionUrl = document.getElementById('companion-url');
+  const companionStatus = document.getElementById('companion-status');
+
+  const renderCompanion = async () => {
+    try {
+      const status = (await invoke('get_companion_status')) as any;
+      if (companionEnabled) companionEnabled.checked = status.enabled;
+      if (companionPairing) companionPairing.style.display = status.pairing_url ? 'block' : 'none';
+      if (companionQr) companionQr.innerHTML = status.qr_svg ?? '';
+      if (companionUrl) companionUrl.textContent = status.pairing_url ?? '';
+      if (companionStatus) {
+        if (!status.enabled) companionStatus.textContent = '';
+        else if (!status.running) companionStatus.textContent = 'Not listening. Check the log for errors.';
+        else companionStatus.textContent = `Scan the code on your phone. ${status.connected} phone(s) connected.`;
+      }
+    } catch (e) {
+      console.error('Failed to load companion status', e);
+    }
+  };
+
+  await renderCompanion();
+
+  companionEnabled?.addEventListener('change', async () => {
+    try {
+      await invoke('set_companion_enabled', { enabled: companionEnabled.checked });
+    } catch (e) {
+      addLog(`❌ Phone companion: ${e}`);
+    }
+    await renderCompanion();
+  });
+  document.getElementById('companion-reset-btn')?.addEventListener('click', async () => {
+    try {
+      await invoke('reset_companion_pairing');
+      addLog('🔑 Phone pairing reset. Scan the new code on each phone.');
+    } catch (e) {
+      addLog(`❌ Phone companion: ${e}`);
+    }
+    await renderCompanion();
+  });
+  await listen('companion-transcript', async (e) => {
+    const t = e.payload as any;
+    addLog(t.text != null ? `📱 Phone upload: ${t.text}` : `❌ Phone upload failed: ${t.error}`);
+    await renderCompanion();
+  });
+
   // Block recording when no model selected
   await listen('no-model-selected', () => {
     addLog('⚠️ No model selected. Please choose a model in the Models section.');
@@ -348,6 +512,40 @@ window.addEventListener('DOMContentLoaded', async () => {
     if (statusEl) statusEl.textContent = 'Please select a model first';
   });
 
+  // Recordings without speech are skipped rather than transcribed
+  await listen('no-speech-detected', (e) => {
+    const r = e.payload as { audio_ms: number; levels: { rms_db: number } };
+    addLog(`🔇 No speech detected in ${(r.audio_ms / 1000).toFixed(1)} s of audio (${r.levels.rms_db.toFixed(1)} dBFS), nothing was transcribed`);
+  });
+
+  // Surface lost or failing audio streams instead of recording silence
+  await listen('audio-device-changed', (e) => {
+    const change = e.payload as { device: string | null; reason: string };
+    if (change.reason === 'default-device-changed') addLog(`🎧 System default input is now ${change.device ?? 'unknown'}`);
+  });
+
+  await listen('audio-error', (e) => {
+    const err = e.payload as { kind: string; stream: string; device: string | null; message: string; recording: boolean };
+    const device = err.device ?? 'input device';
+    const text = err.kind === 'device-lost' ? `Microphone lost: ${device}` : `Audio problem with ${device}: ${err.message}`;
+    addLog(`⚠️ ${text}${err.recording ? ' (the current recording may be missing audio)' : ''}`);
+    const statusEl = document.getElementById('status');
+    if (statusEl) statusEl.textContent = text;
+  });
+
+  // Keyboard-only navigation: the open-settings/open-history actions show the window, and the
+  // matching section is scrolled into view and focused here.
+  const focusSection = (id: string) => {
+    const el = document.getElementById(id);
+    if (!el) return;
+    el.scrollIntoView({ block: 'center' });
+    // The log is a plain div, which only takes focus with a tabindex.
+    if (el.tabIndex < 0) el.setAttribute('tabindex', '-1');
+    el.focus();
+  };
+  await listen('open-settings', () => focusSection('audio-device-select'));
+  await listen('open-history', () => focusSection('log'));
+
   // Shortcut configuration
   const shortcutInput = document.getElementById(
     'shortcut-input'
@@ -371,6 +569,8 @@ window.addEventListener('DOMContentLoaded', async () => {
   const promptStatus = document.getElementById('prompt-status') as HTMLElement;
   const autoPasteCheckbox = document.getElementById('auto-paste-checkbox') as HTMLInputElement | null;
   const holdToRecordCheckbox = document.getElementById('hold-to-record-checkbox') as HTMLInputElement | null;
+  const flashAttnCheckbox = document.getElementById('flash-attn-checkbox') as HTMLInputElement | null;
+  const dtwTimestampsCheckbox = document.getElementById('dtw-timestamps-checkbox') as HTMLInputElement | null;
 
   // Load current shortcut
   try {
@@ -416,18 +616,36 @@ window.addEventListener('DOMContentLoaded', async () => {
   // Load audio devices and current selection
   try {
     if (audioDeviceSelect) {
-      const devices = (await invoke('list_audio_input_devices')) as string[];
+      const devices = (await invoke('list_audio_input_devices')) as { name: string; kind: 'microphone' | 'system_audio' }[];
       const selected = (await invoke('get_selected_audio_input_device')) as string | null;
+      const following = (await invoke('get_follow_system_default')) as boolean;
+      const capabilities = (await invoke('list_input_device_capabilities').catch(() => [])) as {
+        name: string;
+        is_default: boolean;
+        default_format: { channels: number; sample_rate_hz: number } | null;
+      }[];
+      const describe = (name: string) => {
+        const caps = capabilities.find((c) => c.name === name);
+        if (!caps) return name;
+        const details = [];
+        if (caps.is_default) details.push('default');
+        if (caps.default_format) {
+          details.push(`${caps.default_format.channels} ch`, `${caps.default_format.sample_rate_hz / 1000} kHz`);
+        }
+        return details.length ? `${name} (${details.join(', ')})` : name;
+      };
       audioDeviceSelect.innerHTML = '';
+      // Recording from the system default follows it as it changes, ignoring fallbacks.
       const sys = document.createElement('option');
       sys.value = '';
-      sys.textContent = 'System default';
+      sys.textContent = 'System default (switches when it changes)';
+      sys.selected = following || !selected;
       audioDeviceSelect.appendChild(sys);
-      devices.forEach((name) => {
+      devices.forEach(({ name, kind }) => {
         const opt = document.createElement('option');
         opt.value = name;
-        opt.textContent = name;
-        if (selected && name === selected) opt.selected = true;
+        opt.textContent = kind === 'system_audio' ? `${name} (what the Mac is playing)` : describe(name);
+        if (!following && selected && name === selected) opt.selected = true;
         audioDeviceSelect.appendChild(opt);
       });
     }
@@ -466,6 +684,15 @@ window.addEventListener('DOMContentLoaded', async () => {
     console.error('Failed to load hold-to-record setting:', e);
   }
 
+  // Load flash attention and DTW settings
+  try {
+    const config = (await invoke('get_inference_config')) as { flash_attn: boolean; dtw_timestamps: boolean };
+    if (flashAttnCheckbox) flashAttnCheckbox.checked = !!config.flash_attn;
+    if (dtwTimestampsCheckbox) dtwTimestampsCheckbox.checked = !!config.dtw_timestamps;
+  } catch (e) {
+    console.error('Failed to load inference settings:', e);
+  }
+
   // Capture shortcut
   if (shortcutInput) {
     shortcutInput.addEventListener('focus', () => {
@@ -592,9 +819,11 @@ window.addEventListener('DOMContentLoaded', async () => {
   if (saveAudioDeviceBtn && audioDeviceSelect) {
     saveAudioDeviceBtn.addEventListener('click', async () => {
       try {
-        const name = audioDeviceSelect.value || null;
-        await invoke('save_selected_audio_input_device', { name });
-        await invoke('apply_selected_audio_input_device');
+        const followDefault = audioDeviceSelect.value === '';
+        if (!followDefault) {
+          await invoke('save_selected_audio_input_device', { name: audioDeviceSelect.value });
+        }
+        await invoke('save_follow_system_default', { enabled: followDefault });
         if (audioDeviceStatus) {
           audioDeviceStatus.textContent = '✅ Device applied!';
           audioDeviceStatus.style.color = 'green';
@@ -639,4 +868,32 @@ window.addEventListener('DOMContentLoaded', async () => {
       }
     });
   }
+
+  // Toggle flash attention
+  if (flashAttnCheckbox) {
+    flashAttnCheckbox.addEventListener('change', async () => {
+      try {
+        const enabled = !!flashAttnCheckbox.checked;
+        const config = (await invoke('get_inference_config')) as Record<string, unknown>;
+        await invoke('save_inference_config', { config: { ...config, flash_attn: enabled } });
+        addLog(enabled ? '💾 Flash attention enabled.' : '💾 Flash attention disabled.');
+      } catch (e) {
+        console.error('Failed to save flash attention setting:', e);
+      }
+    });
+  }
+
+  // Toggle DTW word timings
+  if (dtwTimestampsCheckbox) {
+    dtwTimestampsCheckbox.addEventListener('change', async () => {
+      try {
+        const enabled = !!dtwTimestampsCheckbox.checked;
+        const config = (await invoke('get_inference_config')) as Record<string, unknown>;
+        await invoke('save_inference_config', { config: { ...config, dtw_timestamps: enabled } });
+        addLog(enabled ? '💾 DTW word timings enabled.' : '💾 DTW word timings disabled.');
+      } catch (e) {
+        console.error('Failed to save DTW setting:', e);
+      }
+    });
+  }
 });
@@ -0,0 +1,48 @@
+import { listen } from '@tauri-apps/api/event';
+import { invoke } from '@tauri-apps/api/core';
+
+window.addEventListener('DOMContentLoaded', async () => {
+  const textArea = document.getElementById('review-text') as HTMLTextAreaElement;
+  const count = document.getElementById('review-count') as HTMLSpanElement;
+  const pasteBtn = document.getElementById('review-paste-btn') as HTMLButtonElement;
+  const discardBtn = document.getElementById('review-discard-btn') as HTMLButtonElement;
+
+  const updateCount = () => {
+    count.textContent = `${textArea.value.length} characters`;
+  };
+  const show = (text: string) => {
+    textArea.value = text;
+    updateCount();
+    textArea.focus();
+  };
+
+  textArea.addEventListener('input', updateCount);
+  pasteBtn.addEventListener('click', async () => {
+    try {
+      await invoke('confirm_paste_review', { text: textArea.value });
+    } catch (e) {
+      console.error('Failed to paste reviewed transcript:', e);
+    }
+  });
+  discardBtn.addEventListener('click', async () => {
+    try {
+      await invoke('discard_paste_review');
+    } catch (e) {
+      console.error('Failed to discard reviewed transcript:', e);
+    }
+  });
+  // Cmd+Enter pastes, Escape discards
+  window.addEventListener('keydown', (e) => {
+    if (e.key === 'Enter' && e.metaKey) {
+      e.preventDefault();
+      pasteBtn.click();
+    } else if (e.key === 'Escape') {
+      e.preventDefault();
+      discardBtn.click();
+    }
+  });
+
+  await listen<string>('paste-review', (e) => show(e.payload));
+  const pending = await invoke<string | null>('get_pending_paste_review');
+  if (pending) show(pending);
+});
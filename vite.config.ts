@@ -27,4 +27,13 @@ export default defineConfig(async () => ({
       ignored: ["**/src-tauri/**"],
     },
   },
+  // The paste review window is a separate page
+  build: {
+    rollupOptions: {
+      input: {
+        main: "index.html",
+        review: "review.html",
+      },
+    },
+  },
 }));